@@ -1,10 +1,64 @@
 use anyhow::{Context, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, SampleRate, StreamConfig};
+use cpal::{
+    BufferSize, Device, FromSample, SampleFormat, SampleRate, SizedSample, StreamConfig,
+    SupportedBufferSize,
+};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::{info, warn};
 
 use crate::config::{AudioConfig, Config};
+use crate::stt::wav_utils;
+
+/// Enum representing the different sources audio can be recorded from
+pub enum AudioSource {
+    Device(AudioRecorder),
+    File(FileAudioSource),
+}
+
+impl AudioSource {
+    /// Create the configured source: the `--input-file` WAV if set, otherwise the default input device
+    pub fn new(config: &Config) -> Result<Self> {
+        match &config.audio.input_file {
+            Some(path) => Ok(AudioSource::File(FileAudioSource::new(config, path)?)),
+            None => Ok(AudioSource::Device(AudioRecorder::new(config)?)),
+        }
+    }
+
+    pub fn device_name(&self) -> String {
+        match self {
+            AudioSource::Device(recorder) => recorder.device_name(),
+            AudioSource::File(source) => source.device_name(),
+        }
+    }
+
+    /// Whether the default input device is no longer the one this source records from
+    pub fn device_changed(&self) -> bool {
+        match self {
+            AudioSource::Device(recorder) => recorder.device_changed(),
+            AudioSource::File(_) => false,
+        }
+    }
+
+    pub fn start_recording(&mut self, audio_tx: Sender<AudioData>) -> Result<()> {
+        match self {
+            AudioSource::Device(recorder) => recorder.start_recording(audio_tx),
+            AudioSource::File(source) => source.start_recording(audio_tx),
+        }
+    }
+
+    pub fn stop_recording(&mut self) {
+        match self {
+            AudioSource::Device(recorder) => recorder.stop_recording(),
+            AudioSource::File(source) => source.stop_recording(),
+        }
+    }
+}
 
 pub struct AudioRecorder {
     config: AudioConfig,
@@ -19,10 +73,7 @@ pub struct AudioData {
 
 impl AudioRecorder {
     pub fn new(config: &Config) -> Result<Self> {
-        let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .context("No input device available")?;
+        let device = select_input_device(&config.audio)?;
         info!("Using audio device: {}", device.name().unwrap_or_default());
 
         Ok(Self {
@@ -39,27 +90,115 @@ impl AudioRecorder {
         })
     }
 
+    /// Whether the default input device changed since this recorder was created,
+    /// e.g. a headset was plugged in. A device chosen with `audio.device` stays put.
+    pub fn device_changed(&self) -> bool {
+        self.config.device.is_none()
+            && default_input_device_name().is_some_and(|name| name != self.device_name())
+    }
+
     pub fn start_recording(&mut self, audio_tx: Sender<AudioData>) -> Result<()> {
         // Stop any existing stream
         self.stop_recording();
 
+        let target_rate = self.config.sample_rate;
+        let capture_rate = self.negotiate_sample_rate();
+        let channels = self.config.channels;
+        if capture_rate == target_rate {
+            info!("Capturing audio at {} Hz", capture_rate);
+        } else {
+            warn!(
+                "Device doesn't support {} Hz, capturing at {} Hz and resampling to {} Hz",
+                target_rate, capture_rate, target_rate
+            );
+        }
+
+        let buffer_size = self.negotiate_buffer_size();
+        match buffer_size {
+            BufferSize::Fixed(frames) => info!("Using fixed audio buffer of {} frames", frames),
+            BufferSize::Default => warn!(
+                "Device doesn't support a buffer of {} frames, using its default buffer size",
+                self.config.chunk_size
+            ),
+        }
+
         let config = StreamConfig {
-            channels: self.config.channels,
-            sample_rate: SampleRate(self.config.sample_rate),
-            buffer_size: cpal::BufferSize::Fixed(self.config.chunk_size as u32),
+            channels,
+            sample_rate: SampleRate(capture_rate),
+            buffer_size,
         };
 
+        let sample_format = self.negotiate_sample_format();
+        let stream =
+            match self.build_format_stream(sample_format, &config, audio_tx.clone(), target_rate) {
+                // A device that doesn't report its buffer sizes may still reject the fixed one
+                Err(e) if config.buffer_size != BufferSize::Default => {
+                    warn!("Failed to open a fixed audio buffer, using the device default: {e:#}");
+                    let config = StreamConfig {
+                        buffer_size: BufferSize::Default,
+                        ..config
+                    };
+                    self.build_format_stream(sample_format, &config, audio_tx, target_rate)
+                }
+                result => result,
+            }?;
+
+        stream.play()?;
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            stream.pause().ok();
+        }
+    }
+
+    /// Build a capture stream for `sample_format`
+    fn build_format_stream(
+        &self,
+        sample_format: SampleFormat,
+        config: &StreamConfig,
+        audio_tx: Sender<AudioData>,
+        target_rate: u32,
+    ) -> Result<cpal::Stream> {
+        match sample_format {
+            SampleFormat::F32 => self.build_stream::<f32>(config, audio_tx, target_rate),
+            SampleFormat::F64 => self.build_stream::<f64>(config, audio_tx, target_rate),
+            SampleFormat::I8 => self.build_stream::<i8>(config, audio_tx, target_rate),
+            SampleFormat::I16 => self.build_stream::<i16>(config, audio_tx, target_rate),
+            SampleFormat::I32 => self.build_stream::<i32>(config, audio_tx, target_rate),
+            SampleFormat::U8 => self.build_stream::<u8>(config, audio_tx, target_rate),
+            SampleFormat::U16 => self.build_stream::<u16>(config, audio_tx, target_rate),
+            SampleFormat::U32 => self.build_stream::<u32>(config, audio_tx, target_rate),
+            other => anyhow::bail!("Unsupported input sample format: {other}"),
+        }
+    }
+
+    /// Build a capture stream for the device's native sample type, converting to f32
+    fn build_stream<T>(
+        &self,
+        config: &StreamConfig,
+        audio_tx: Sender<AudioData>,
+        target_rate: u32,
+    ) -> Result<cpal::Stream>
+    where
+        T: SizedSample,
+        f32: FromSample<T>,
+    {
+        let capture_rate = config.sample_rate.0;
+        let mut resampler = (capture_rate != target_rate)
+            .then(|| StreamResampler::new(config.channels, capture_rate, target_rate));
         let stream = self.device.build_input_stream(
-            &config,
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                let level = calculate_rms(data);
-                if audio_tx
-                    .send(AudioData {
-                        samples: data.to_vec(),
-                        level,
-                    })
-                    .is_err()
-                {
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                let data: Vec<f32> = data.iter().map(|&sample| sample.to_sample()).collect();
+                let samples = match resampler.as_mut() {
+                    Some(resampler) => resampler.process(&data),
+                    None => data,
+                };
+                let level = calculate_rms(&samples);
+                if audio_tx.send(AudioData { samples, level }).is_err() {
                     warn!("Failed to send audio data to TUI");
                 }
             },
@@ -68,23 +207,923 @@ impl AudioRecorder {
             },
             None,
         )?;
+        Ok(stream)
+    }
 
-        stream.play()?;
-        self.stream = Some(stream);
+    /// Capture in the device's preferred sample format, some devices only support integer samples
+    fn negotiate_sample_format(&self) -> SampleFormat {
+        match self.device.default_input_config() {
+            Ok(config) => {
+                let format = config.sample_format();
+                if format != SampleFormat::F32 {
+                    info!("Capturing {} samples, converting to f32", format);
+                }
+                format
+            }
+            Err(e) => {
+                warn!("Failed to query default input config, assuming f32: {}", e);
+                SampleFormat::F32
+            }
+        }
+    }
+
+    /// Use `chunk_size` as a fixed buffer when the device reports supporting it,
+    /// otherwise leave the buffer size to the device
+    fn negotiate_buffer_size(&self) -> BufferSize {
+        let ranges: Vec<SupportedBufferSize> = match self.device.supported_input_configs() {
+            Ok(configs) => configs
+                .filter(|range| range.channels() == self.config.channels)
+                .map(|range| *range.buffer_size())
+                .collect(),
+            Err(e) => {
+                warn!("Failed to query supported buffer sizes: {}", e);
+                Vec::new()
+            }
+        };
+        choose_buffer_size(self.config.chunk_size, &ranges)
+    }
+
+    /// Standard `(sample_rate, channels)` combinations the device can capture, for
+    /// offering valid choices instead of arbitrary numbers
+    pub fn supported_configs(&self) -> Vec<(u32, u16)> {
+        device_supported_configs(&self.device)
+    }
+
+    /// Pick the supported capture rate closest to the configured sample rate
+    fn negotiate_sample_rate(&self) -> u32 {
+        let requested = self.config.sample_rate;
+        let ranges: Vec<(u32, u32)> = match self.device.supported_input_configs() {
+            Ok(configs) => configs
+                .filter(|range| range.channels() == self.config.channels)
+                .map(|range| (range.min_sample_rate().0, range.max_sample_rate().0))
+                .collect(),
+            Err(e) => {
+                warn!("Failed to query supported input configs: {}", e);
+                return requested;
+            }
+        };
+        nearest_supported_rate(requested, &ranges).unwrap_or(requested)
+    }
+}
+
+/// Rates offered as choices, whatever range a device reports
+const STANDARD_RATES: &[u32] = &[8000, 11025, 16000, 22050, 32000, 44100, 48000, 96000];
+
+fn device_supported_configs(device: &Device) -> Vec<(u32, u16)> {
+    match device.supported_input_configs() {
+        Ok(configs) => standard_configs(
+            &configs
+                .map(|range| {
+                    (
+                        range.channels(),
+                        range.min_sample_rate().0,
+                        range.max_sample_rate().0,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => {
+            warn!("Failed to query supported input configs: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Supported `(sample_rate, channels)` combinations of the input device `audio.device`
+/// selects, empty when there's no device or it can't be queried
+pub fn supported_input_configs(config: &AudioConfig) -> Vec<(u32, u16)> {
+    match select_input_device(config) {
+        Ok(device) => device_supported_configs(&device),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Standard rates within each `(channels, min_rate, max_rate)` range, sorted and deduplicated
+fn standard_configs(ranges: &[(u16, u32, u32)]) -> Vec<(u32, u16)> {
+    let mut configs: Vec<(u32, u16)> = ranges
+        .iter()
+        .flat_map(|&(channels, min, max)| {
+            STANDARD_RATES
+                .iter()
+                .filter(move |rate| (min..=max).contains(*rate))
+                .map(move |&rate| (rate, channels))
+        })
+        .collect();
+    configs.sort_unstable();
+    configs.dedup();
+    configs
+}
+
+/// Fixed buffer of `chunk_size` frames if it's within a supported range, or the device
+/// doesn't report one (many ALSA and PulseAudio devices), the device default otherwise
+fn choose_buffer_size(chunk_size: usize, ranges: &[SupportedBufferSize]) -> BufferSize {
+    match u32::try_from(chunk_size) {
+        Ok(frames)
+            if frames > 0
+                && ranges.iter().any(|range| match *range {
+                    SupportedBufferSize::Range { min, max } => (min..=max).contains(&frames),
+                    SupportedBufferSize::Unknown => true,
+                }) =>
+        {
+            BufferSize::Fixed(frames)
+        }
+        _ => BufferSize::Default,
+    }
+}
+
+/// Find the rate closest to `requested` within any of the supported `(min, max)` ranges
+fn nearest_supported_rate(requested: u32, ranges: &[(u32, u32)]) -> Option<u32> {
+    ranges
+        .iter()
+        .map(|&(min, max)| requested.clamp(min, max))
+        .min_by_key(|rate| rate.abs_diff(requested))
+}
+
+/// Resamples interleaved capture callbacks to the target rate. Filter and interpolation
+/// state carry over from one callback to the next, so chunk boundaries neither click nor
+/// drift, and a low-pass keeps content above the target Nyquist frequency from aliasing.
+struct StreamResampler {
+    channels: usize,
+    step: f64,                 // Input frames per output frame
+    lowpass: Vec<[Biquad; 2]>, // Per channel, empty when upsampling
+    previous: Vec<f32>,        // Last input frame of the previous callback
+    position: f64,             // Next output frame, in input frames after `previous`
+}
+
+impl StreamResampler {
+    fn new(channels: u16, from_rate: u32, to_rate: u32) -> Self {
+        let channels = channels.max(1) as usize;
+        // 4th-order Butterworth a little below the target Nyquist frequency
+        let cutoff = 0.45 * to_rate as f32;
+        let lowpass = if to_rate < from_rate {
+            let stage = |q| Biquad::lowpass(cutoff, from_rate as f32, q);
+            vec![[stage(0.5412), stage(1.3066)]; channels]
+        } else {
+            Vec::new()
+        };
+        Self {
+            channels,
+            step: from_rate as f64 / to_rate as f64,
+            lowpass,
+            previous: vec![0.0; channels],
+            position: 1.0,
+        }
+    }
+
+    /// Resample one callback's interleaved samples
+    fn process(&mut self, data: &[f32]) -> Vec<f32> {
+        let channels = self.channels;
+        let mut input = data.to_vec();
+        for (i, sample) in input.iter_mut().enumerate() {
+            if let Some([first, second]) = self.lowpass.get_mut(i % channels) {
+                *sample = second.process(first.process(*sample));
+            }
+        }
+
+        // Frame 0 is the previous callback's last frame, so interpolation spans the boundary
+        let frames = input.len() / channels;
+        let frame = |index: usize, channel: usize| match index {
+            0 => self.previous[channel],
+            _ => input[(index - 1) * channels + channel],
+        };
+        let mut output = Vec::with_capacity(((frames as f64 / self.step) as usize + 1) * channels);
+        while self.position < frames as f64 {
+            let index = self.position as usize;
+            let fraction = (self.position - index as f64) as f32;
+            for channel in 0..channels {
+                let current = frame(index, channel);
+                let next = frame(index + 1, channel);
+                output.push(current + (next - current) * fraction);
+            }
+            self.position += self.step;
+        }
+        if frames > 0 {
+            self.position -= frames as f64;
+            self.previous
+                .copy_from_slice(&input[(frames - 1) * channels..frames * channels]);
+        }
+        output
+    }
+}
+
+/// Second-order IIR filter section
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    /// Low-pass at `cutoff` Hz with quality factor `q`
+    fn lowpass(cutoff: f32, sample_rate: f32, q: f32) -> Self {
+        let w0 = 2.0 * std::f32::consts::PI * cutoff / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: (1.0 - cos) / 2.0 / a0,
+            b1: (1.0 - cos) / a0,
+            b2: (1.0 - cos) / 2.0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+}
+
+/// Plays a WAV file through the recording pipeline at real-time pace, for testing without a microphone
+pub struct FileAudioSource {
+    config: AudioConfig,
+    path: PathBuf,
+    stop_flag: Option<Arc<AtomicBool>>,
+}
+
+impl FileAudioSource {
+    pub fn new(config: &Config, path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Err(anyhow::anyhow!("Input file not found: {:?}", path));
+        }
+        info!("Using audio input file: {:?}", path);
+
+        Ok(Self {
+            config: config.audio.clone(),
+            path: path.to_path_buf(),
+            stop_flag: None,
+        })
+    }
+
+    pub fn device_name(&self) -> String {
+        file_source_name(&self.path)
+    }
+
+    pub fn start_recording(&mut self, audio_tx: Sender<AudioData>) -> Result<()> {
+        self.stop_recording();
+
+        let channels = self.config.channels.max(1) as usize;
+        let mono = wav_utils::load_mono(&self.path, self.config.sample_rate)?;
+        // Duplicate the mono signal across the configured channel count
+        let samples: Vec<f32> = mono
+            .iter()
+            .flat_map(|&sample| std::iter::repeat_n(sample, channels))
+            .collect();
+
+        let chunk_len = self.config.chunk_size.max(1) * channels;
+        let chunk_duration = Duration::from_secs_f64(
+            self.config.chunk_size.max(1) as f64 / self.config.sample_rate as f64,
+        );
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let thread_stop_flag = stop_flag.clone();
+
+        std::thread::spawn(move || {
+            for chunk in samples.chunks(chunk_len) {
+                if thread_stop_flag.load(Ordering::Relaxed) {
+                    return;
+                }
+                let data = AudioData {
+                    samples: chunk.to_vec(),
+                    level: calculate_rms(chunk),
+                };
+                if audio_tx.send(data).is_err() {
+                    warn!("Failed to send audio data to TUI");
+                    return;
+                }
+                std::thread::sleep(chunk_duration);
+            }
+            info!("Finished playing audio input file");
+        });
+
+        self.stop_flag = Some(stop_flag);
         Ok(())
     }
 
     pub fn stop_recording(&mut self) {
-        if let Some(stream) = self.stream.take() {
-            stream.pause().ok();
+        if let Some(stop_flag) = self.stop_flag.take() {
+            stop_flag.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+fn file_source_name(path: &Path) -> String {
+    format!("File: {}", path.display())
+}
+
+/// Name of the configured input: the `--input-file` WAV, the `audio.device` match or
+/// the default input device
+pub fn input_device_name(config: &AudioConfig) -> Option<String> {
+    match (&config.input_file, &config.device) {
+        (Some(path), _) => Some(file_source_name(path)),
+        (None, Some(_)) => select_input_device(config)
+            .ok()
+            .and_then(|device| device.name().ok()),
+        (None, None) => default_input_device_name(),
+    }
+}
+
+/// The input device named by `audio.device`, or the default one
+fn select_input_device(config: &AudioConfig) -> Result<Device> {
+    let host = cpal::default_host();
+    match &config.device {
+        Some(wanted) => host
+            .input_devices()
+            .context("Failed to list input devices")?
+            .find(|device| {
+                device
+                    .name()
+                    .is_ok_and(|name| device_matches(&name, wanted))
+            })
+            .with_context(|| {
+                format!("No input device matching \"{wanted}\", see `simple-stt devices`")
+            }),
+        None => host
+            .default_input_device()
+            .context("No input device available"),
+    }
+}
+
+/// Whether a device name matches `audio.device`, ignoring case
+fn device_matches(name: &str, wanted: &str) -> bool {
+    name.to_lowercase().contains(&wanted.to_lowercase())
+}
+
+/// Whether an input device captures what's playing rather than a microphone, like
+/// PulseAudio/PipeWire monitor sources or Windows' Stereo Mix
+pub fn is_monitor_device(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["monitor", "loopback", "stereo mix", "what u hear"]
+        .iter()
+        .any(|marker| name.contains(marker))
+}
+
+/// Names of the available input devices, with whether each is the default
+pub fn list_input_devices() -> Result<Vec<(String, bool)>> {
+    let default = default_input_device_name();
+    let devices = cpal::default_host()
+        .input_devices()
+        .context("Failed to list input devices")?
+        .filter_map(|device| device.name().ok())
+        .map(|name| {
+            let is_default = default.as_deref() == Some(name.as_str());
+            (name, is_default)
+        })
+        .collect();
+    Ok(devices)
+}
+
+/// Name of the current default input device, or `None` if no device is available
+pub fn default_input_device_name() -> Option<String> {
+    let device = cpal::default_host().default_input_device()?;
+    Some(device.name().unwrap_or_else(|e| {
+        warn!("Failed to get device name: {}", e);
+        "Unknown Device".to_string()
+    }))
+}
+
+/// Fixed-size ring buffer holding the most recent audio captured before a
+/// recording starts, so the first word isn't clipped by stream start-up latency.
+pub struct PrerollBuffer {
+    samples: VecDeque<f32>,
+    capacity: usize,
+    channels: usize,
+    fade_len: usize, // Samples cross-faded into the live audio
+    seam: bool,      // The live audio doesn't carry straight on from the buffered samples
+}
+
+impl PrerollBuffer {
+    pub fn new(config: &AudioConfig) -> Self {
+        // A file source replays from the start on each recording, so there's nothing to buffer
+        let preroll_ms = if config.input_file.is_some() {
+            0
+        } else {
+            config.preroll_ms
+        };
+        let capacity = (config.sample_rate as u64 * config.channels as u64 * preroll_ms as u64
+            / 1000) as usize;
+        let fade_len =
+            (config.sample_rate as u64 * config.channels as u64 * CROSSFADE_MS / 1000) as usize;
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            channels: config.channels as usize,
+            fade_len,
+            seam: false,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Append samples, discarding the oldest ones beyond capacity
+    pub fn push(&mut self, samples: &[f32]) {
+        if self.capacity == 0 {
+            return;
         }
+        let samples = &samples[samples.len().saturating_sub(self.capacity)..];
+        let overflow = (self.samples.len() + samples.len()).saturating_sub(self.capacity);
+        self.samples.drain(..overflow);
+        self.samples.extend(samples);
+    }
+
+    /// Take all buffered samples, leaving the buffer empty
+    pub fn take(&mut self) -> Vec<f32> {
+        self.seam = false;
+        self.samples.drain(..).collect()
     }
+
+    /// Note that the next live audio comes from a different stream than the buffered
+    /// samples, so `join` cross-fades them instead of concatenating
+    pub fn mark_seam(&mut self) {
+        self.seam = !self.samples.is_empty();
+    }
+
+    /// Take all buffered samples joined onto the start of `live`. Audio from the same
+    /// stream is contiguous and concatenated as-is; across a seam it's cross-faded.
+    pub fn join(&mut self, live: Vec<f32>) -> Vec<f32> {
+        if self.samples.is_empty() {
+            return live;
+        }
+        let fade_len = if self.seam { self.fade_len } else { 0 };
+        crossfade_join(self.take(), &live, fade_len, self.channels)
+    }
+}
+
+/// Length of the cross-fade where buffered pre-roll audio meets live audio
+const CROSSFADE_MS: u64 = 5;
+
+/// Join `head` and `tail`, overlapping up to `fade_len` interleaved samples with a linear
+/// cross-fade so a level jump at the seam doesn't become a click
+pub fn crossfade_join(
+    mut head: Vec<f32>,
+    tail: &[f32],
+    fade_len: usize,
+    channels: usize,
+) -> Vec<f32> {
+    let channels = channels.max(1);
+    // Whole frames only, so each channel fades into itself
+    let fade_len = fade_len.min(head.len()).min(tail.len()) / channels * channels;
+    let frames = fade_len / channels;
+    let start = head.len() - fade_len;
+    for (i, (sample, &next)) in head[start..].iter_mut().zip(tail).enumerate() {
+        let weight = (i / channels + 1) as f32 / (frames + 1) as f32;
+        *sample = *sample * (1.0 - weight) + next * weight;
+    }
+    head.extend_from_slice(&tail[fade_len..]);
+    head
+}
+
+/// Exponentially smoothed audio level with separate attack and release
+/// ballistics, like a VU meter: it rises quickly on speech and decays slowly.
+pub struct LevelMeter {
+    attack: Duration,
+    release: Duration,
+    level: Option<f32>,
+}
+
+impl LevelMeter {
+    pub fn new(config: &AudioConfig) -> Self {
+        Self {
+            attack: Duration::from_millis(config.level_attack_ms as u64),
+            release: Duration::from_millis(config.level_release_ms as u64),
+            level: None,
+        }
+    }
+
+    /// Feed the raw level of a chunk lasting `elapsed`, returning the smoothed level
+    pub fn update(&mut self, level: f32, elapsed: Duration) -> f32 {
+        let smoothed = match self.level {
+            None => level,
+            Some(previous) => {
+                let time_constant = if level > previous {
+                    self.attack
+                } else {
+                    self.release
+                };
+                if time_constant.is_zero() {
+                    level
+                } else {
+                    let alpha = 1.0 - (-elapsed.as_secs_f32() / time_constant.as_secs_f32()).exp();
+                    previous + (level - previous) * alpha
+                }
+            }
+        };
+        self.level = Some(smoothed);
+        smoothed
+    }
+
+    /// Forget the current level so the next recording starts from its first chunk
+    pub fn reset(&mut self) {
+        self.level = None;
+    }
+}
+
+/// Suggest a silence threshold from levels sampled while the room is quiet,
+/// placed comfortably above the loudest background noise observed
+pub fn suggest_silence_threshold(noise_levels: &[f32]) -> Option<f32> {
+    const MIN_SILENCE_THRESHOLD: f32 = 0.5;
+    const NOISE_MARGIN: f32 = 1.5;
+
+    if noise_levels.is_empty() {
+        return None;
+    }
+    let mut sorted = noise_levels.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    // Ignore the odd click or bump by using the 95th percentile rather than the peak
+    let noise_peak = sorted[(sorted.len() - 1) * 95 / 100];
+    let threshold = (noise_peak * NOISE_MARGIN).max(MIN_SILENCE_THRESHOLD);
+    Some((threshold * 10.0).round() / 10.0)
+}
+
+/// Amplify captured audio by `gain`, clipping at full scale
+pub fn apply_gain(data: AudioData, gain: f32) -> AudioData {
+    if gain == 1.0 {
+        return data;
+    }
+    let samples: Vec<f32> = data
+        .samples
+        .iter()
+        .map(|&sample| (sample * gain).clamp(-1.0, 1.0))
+        .collect();
+    let level = calculate_rms(&samples);
+    AudioData { samples, level }
 }
 
-fn calculate_rms(samples: &[f32]) -> f32 {
+/// How much of a recording is louder than `silence_threshold`, measured in 10ms windows
+pub fn speech_duration(samples: &[f32], config: &AudioConfig) -> Duration {
+    const WINDOW_MS: u64 = 10;
+    let window_len =
+        (config.sample_rate as u64 * config.channels as u64 * WINDOW_MS / 1000).max(1) as usize;
+    let loud_windows = samples
+        .chunks(window_len)
+        .filter(|window| calculate_rms(window) >= config.silence_threshold)
+        .count();
+    Duration::from_millis(loud_windows as u64 * WINDOW_MS)
+}
+
+/// Quiet audio kept around the speech when trimming, so soft word endings aren't cut
+const TRIM_PADDING_MS: u64 = 100;
+
+/// One-pole high-pass filter over interleaved samples, removing rumble and DC offset
+/// below `cutoff_hz` (e.g. AC hum and desk thumps) before transcription
+pub fn highpass(samples: &mut [f32], cutoff_hz: f32, config: &AudioConfig) {
+    let channels = config.channels.max(1) as usize;
+    let rc = 1.0 / (2.0 * std::f32::consts::PI * cutoff_hz);
+    let dt = 1.0 / config.sample_rate as f32;
+    let alpha = rc / (rc + dt);
+    for channel in 0..channels {
+        let mut previous_in = 0.0;
+        let mut previous_out = 0.0;
+        for sample in samples.iter_mut().skip(channel).step_by(channels) {
+            let input = *sample;
+            previous_out = alpha * (previous_out + input - previous_in);
+            previous_in = input;
+            *sample = previous_out;
+        }
+    }
+}
+
+/// Drop the leading and trailing 10ms windows that are below `silence_threshold`,
+/// keeping a little padding. Recordings without any speech are returned as-is.
+pub fn trim_silence<'a>(samples: &'a [f32], config: &AudioConfig) -> &'a [f32] {
+    const WINDOW_MS: u64 = 10;
+    let frame_len = config.channels.max(1) as usize;
+    let frames_per_ms = config.sample_rate as u64 / 1000;
+    // Whole frames, so channels stay aligned
+    let window_len = ((frames_per_ms * WINDOW_MS).max(1) as usize) * frame_len;
+    let padding = (frames_per_ms * TRIM_PADDING_MS) as usize * frame_len;
+
+    let is_loud = |window: &[f32]| calculate_rms(window) >= config.silence_threshold;
+    let Some(first) = samples.chunks(window_len).position(is_loud) else {
+        return samples;
+    };
+    let last = samples
+        .chunks(window_len)
+        .rposition(is_loud)
+        .unwrap_or(first);
+
+    let start = (first * window_len).saturating_sub(padding);
+    let end = ((last + 1) * window_len + padding).min(samples.len());
+    &samples[start..end]
+}
+
+pub fn calculate_rms(samples: &[f32]) -> f32 {
     if samples.is_empty() {
         return 0.0;
     }
     let sum_squares: f32 = samples.iter().map(|&s| s * s).sum();
     (sum_squares / samples.len() as f32).sqrt() * 100.0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preroll_config(preroll_ms: u32) -> AudioConfig {
+        AudioConfig {
+            sample_rate: 1000,
+            channels: 1,
+            preroll_ms,
+            ..AudioConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_apply_gain() {
+        let samples = vec![0.1, -0.2, 0.6];
+        let level = calculate_rms(&samples);
+        let data = apply_gain(AudioData { samples, level }, 2.0);
+        assert_eq!(data.samples, vec![0.2, -0.4, 1.0]);
+        assert_eq!(data.level, calculate_rms(&[0.2, -0.4, 1.0]));
+    }
+
+    #[test]
+    fn test_speech_duration() {
+        let config = AudioConfig {
+            sample_rate: 1000,
+            channels: 1,
+            silence_threshold: 15.0,
+            ..AudioConfig::default()
+        };
+        // 50ms of silence, then 30ms of signal well above the threshold
+        let mut samples = vec![0.0; 50];
+        samples.extend(std::iter::repeat_n(0.5, 30));
+        assert_eq!(
+            speech_duration(&samples, &config),
+            Duration::from_millis(30)
+        );
+        assert_eq!(speech_duration(&[], &config), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_level_meter_ballistics() {
+        let config = AudioConfig {
+            level_attack_ms: 10,
+            level_release_ms: 300,
+            ..AudioConfig::default()
+        };
+        let mut meter = LevelMeter::new(&config);
+        let chunk = Duration::from_millis(100);
+
+        assert_eq!(meter.update(50.0, chunk), 50.0);
+        // Release is slow: a single quiet chunk only drops the level part of the way
+        let released = meter.update(0.0, chunk);
+        assert!(released > 30.0 && released < 50.0, "{released}");
+        // Attack is fast: a loud chunk brings it almost all the way back up
+        assert!(meter.update(50.0, chunk) > 49.9);
+
+        meter.reset();
+        assert_eq!(meter.update(5.0, chunk), 5.0);
+    }
+
+    #[test]
+    fn test_level_meter_without_smoothing() {
+        let config = AudioConfig {
+            level_attack_ms: 0,
+            level_release_ms: 0,
+            ..AudioConfig::default()
+        };
+        let mut meter = LevelMeter::new(&config);
+        meter.update(50.0, Duration::from_millis(10));
+        assert_eq!(meter.update(1.0, Duration::from_millis(10)), 1.0);
+    }
+
+    #[test]
+    fn test_preroll_keeps_most_recent_samples() {
+        let mut buffer = PrerollBuffer::new(&preroll_config(5));
+        buffer.push(&[1.0, 2.0, 3.0]);
+        buffer.push(&[4.0, 5.0, 6.0, 7.0]);
+        assert_eq!(buffer.take(), vec![3.0, 4.0, 5.0, 6.0, 7.0]);
+        assert!(buffer.take().is_empty());
+    }
+
+    #[test]
+    fn test_choose_buffer_size() {
+        let ranges =
+            [(64, 1024), (4096, 8192)].map(|(min, max)| SupportedBufferSize::Range { min, max });
+        assert_eq!(choose_buffer_size(512, &ranges), BufferSize::Fixed(512));
+        assert_eq!(choose_buffer_size(4096, &ranges), BufferSize::Fixed(4096));
+        assert_eq!(choose_buffer_size(2048, &ranges), BufferSize::Default);
+        assert_eq!(choose_buffer_size(0, &ranges), BufferSize::Default);
+        assert_eq!(choose_buffer_size(2048, &[]), BufferSize::Default);
+
+        // Kept as configured when the device doesn't report a range
+        let unknown = [SupportedBufferSize::Unknown];
+        assert_eq!(choose_buffer_size(2048, &unknown), BufferSize::Fixed(2048));
+        assert_eq!(choose_buffer_size(0, &unknown), BufferSize::Default);
+    }
+
+    #[test]
+    fn test_nearest_supported_rate() {
+        assert_eq!(nearest_supported_rate(16000, &[(8000, 48000)]), Some(16000));
+        assert_eq!(
+            nearest_supported_rate(16000, &[(44100, 44100), (48000, 48000)]),
+            Some(44100)
+        );
+        assert_eq!(nearest_supported_rate(16000, &[]), None);
+    }
+
+    #[test]
+    fn test_resampler_keeps_channels_apart() {
+        let stereo = [1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0];
+        let output = StreamResampler::new(2, 8000, 16000).process(&stereo);
+        // The last half frame waits for the next callback to interpolate towards
+        assert_eq!(output.len(), 12);
+        assert!(output.chunks(2).all(|frame| frame == [1.0, -1.0]));
+    }
+
+    fn tone(hz: f32, rate: u32, secs: f32) -> Vec<f32> {
+        (0..(rate as f32 * secs) as usize)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * hz * i as f32 / rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_resampler_is_continuous_across_callbacks() {
+        let input = tone(440.0, 44100, 0.5);
+        let whole = StreamResampler::new(1, 44100, 16000).process(&input);
+
+        // Odd callback sizes that don't divide evenly into output frames
+        let mut resampler = StreamResampler::new(1, 44100, 16000);
+        let chunked: Vec<f32> = input
+            .chunks(441)
+            .flat_map(|chunk| resampler.process(chunk))
+            .collect();
+        assert_eq!(chunked.len(), whole.len());
+        assert!(chunked
+            .iter()
+            .zip(&whole)
+            .all(|(a, b)| (a - b).abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_resampler_filters_above_target_nyquist() {
+        let level = |hz| {
+            let mut resampler = StreamResampler::new(1, 48000, 16000);
+            let output: Vec<f32> = tone(hz, 48000, 0.5)
+                .chunks(480)
+                .flat_map(|chunk| resampler.process(chunk))
+                .collect();
+            // Skip the filter settling in
+            calculate_rms(&output[800..])
+        };
+        let passband = calculate_rms(&tone(1000.0, 48000, 0.5));
+        assert!(level(1000.0) > 0.9 * passband);
+        // 15 kHz would alias to 1 kHz without the low-pass
+        assert!(level(15000.0) < 0.1 * passband);
+    }
+
+    #[test]
+    fn test_suggest_silence_threshold() {
+        assert_eq!(suggest_silence_threshold(&[]), None);
+        // Quiet room stays at the floor
+        assert_eq!(suggest_silence_threshold(&[0.0, 0.1, 0.2]), Some(0.5));
+        // A single spike doesn't drag the suggestion up
+        let mut levels = vec![2.0; 40];
+        levels.push(50.0);
+        assert_eq!(suggest_silence_threshold(&levels), Some(3.0));
+    }
+
+    #[test]
+    fn test_preroll_disabled() {
+        let mut buffer = PrerollBuffer::new(&preroll_config(0));
+        assert!(!buffer.is_enabled());
+        buffer.push(&[1.0, 2.0]);
+        assert!(buffer.take().is_empty());
+    }
+
+    #[test]
+    fn test_trim_silence() {
+        let config = AudioConfig {
+            sample_rate: 1000,
+            channels: 1,
+            silence_threshold: 15.0,
+            ..AudioConfig::default()
+        };
+        // 300ms of silence either side of 50ms of signal, trimmed to 100ms padding
+        let mut samples = vec![0.0; 300];
+        samples.extend(std::iter::repeat_n(0.5, 50));
+        samples.extend(std::iter::repeat_n(0.0, 300));
+        let trimmed = trim_silence(&samples, &config);
+        assert_eq!(trimmed.len(), 250);
+        assert_eq!(trimmed[100], 0.5);
+
+        let silence = vec![0.0; 500];
+        assert_eq!(trim_silence(&silence, &config).len(), 500);
+    }
+
+    #[test]
+    fn test_device_matching() {
+        assert!(device_matches(
+            "Monitor of Built-in Audio Analog Stereo",
+            "monitor"
+        ));
+        assert!(!device_matches("Built-in Audio Analog Stereo", "USB"));
+
+        assert!(is_monitor_device(
+            "alsa_output.pci-0000_00_1f.3.analog-stereo.monitor"
+        ));
+        assert!(is_monitor_device("Stereo Mix (Realtek Audio)"));
+        assert!(!is_monitor_device("Blue Yeti"));
+    }
+
+    #[test]
+    fn test_highpass_removes_dc_offset() {
+        let config = AudioConfig::default();
+        let rate = config.sample_rate as usize;
+        let mut samples: Vec<f32> = (0..rate)
+            .map(|i| {
+                0.5 + 0.1 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / rate as f32).sin()
+            })
+            .collect();
+        highpass(&mut samples, 80.0, &config);
+
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+        assert!(mean.abs() < 0.01, "mean {mean}");
+        // The speech-band tone survives
+        let peak = samples[rate / 2..]
+            .iter()
+            .fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!(peak > 0.08, "peak {peak}");
+    }
+
+    #[test]
+    fn test_standard_configs() {
+        let configs = standard_configs(&[(1, 16000, 48000), (2, 44100, 48000), (1, 48000, 48000)]);
+        assert_eq!(
+            configs,
+            [
+                (16000, 1),
+                (22050, 1),
+                (32000, 1),
+                (44100, 1),
+                (44100, 2),
+                (48000, 1),
+                (48000, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preroll_join_crossfades_seam() {
+        let config = AudioConfig {
+            sample_rate: 16000,
+            channels: 1,
+            preroll_ms: 50,
+            ..AudioConfig::default()
+        };
+        let mut buffer = PrerollBuffer::new(&config);
+        buffer.push(&[0.5; 800]);
+        buffer.mark_seam();
+        let joined = buffer.join(vec![-0.5; 1600]);
+
+        // The 80-sample fade overlaps the two buffers
+        assert_eq!(joined.len(), 800 + 1600 - 80);
+        let max_step = joined
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs())
+            .fold(0.0, f32::max);
+        assert!(max_step < 0.05, "discontinuity of {max_step} at the seam");
+        assert!(buffer.is_empty());
+
+        // Nothing buffered leaves the live audio untouched
+        assert_eq!(buffer.join(vec![1.0, 2.0]), vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_preroll_join_concatenates_same_stream() {
+        let config = AudioConfig {
+            sample_rate: 16000,
+            channels: 1,
+            preroll_ms: 50,
+            ..AudioConfig::default()
+        };
+        let mut buffer = PrerollBuffer::new(&config);
+        buffer.push(&[0.5; 800]);
+        let joined = buffer.join(vec![-0.5; 1600]);
+        assert_eq!(joined.len(), 800 + 1600);
+        assert_eq!(joined[799], 0.5);
+        assert_eq!(joined[800], -0.5);
+    }
+
+    #[test]
+    fn test_crossfade_join_keeps_channels_apart() {
+        let joined = crossfade_join(vec![1.0, -1.0, 1.0, -1.0], &[1.0, -1.0, 1.0, -1.0], 3, 2);
+        assert_eq!(joined, vec![1.0, -1.0, 1.0, -1.0, 1.0, -1.0]);
+    }
+}
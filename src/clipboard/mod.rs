@@ -1,14 +1,39 @@
 use anyhow::{Context, Result};
 use std::process::Command;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 use which::which;
-use wl_clipboard_rs::copy::{MimeType, Options, Source};
+use wl_clipboard_rs::copy::{self, ClipboardType, MimeType, Options, Seat, Source};
 
-use crate::config::{ClipboardConfig, Config};
+use crate::config::{ClipboardConfig, ClipboardTarget, ClipboardWrap, Config};
+use crate::redact;
+
+/// What `wl-paste` found in the clipboard
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipboardContents {
+    Text(String),
+    /// Empty, or holding an image or other data that isn't UTF-8 text
+    NonText,
+}
+
+impl ClipboardContents {
+    /// The text, with empty and non-text contents both as `None`
+    pub fn into_text(self) -> Option<String> {
+        match self {
+            Self::Text(text) if !text.is_empty() => Some(text),
+            _ => None,
+        }
+    }
+}
 
 pub struct ClipboardManager {
     config: ClipboardConfig,
+    /// Clipboard contents before our last copy, `None` if it was empty or not text
+    previous_clipboard: Option<Option<String>>,
+    /// Text of our last copy, to skip copying the same text again
+    last_copied: Option<String>,
+    /// When the last copy was made, to clear it after `clear_after`
+    copied_at: Option<Instant>,
 }
 
 impl ClipboardManager {
@@ -16,15 +41,122 @@ impl ClipboardManager {
         debug!("Initializing Wayland clipboard manager");
         Ok(Self {
             config: config.clipboard.clone(),
+            previous_clipboard: None,
+            last_copied: None,
+            copied_at: None,
         })
     }
 
-    /// Copy text to clipboard using Wayland native clipboard
-    pub fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+    /// Copy text to clipboard, remembering the previous contents so the copy can be undone.
+    ///
+    /// Copying the same text as last time while the clipboard still holds it is skipped,
+    /// keeping the undo state, unless `force` is set. Returns `true` if the text was written.
+    pub fn copy_to_clipboard(&mut self, text: &str, force: bool) -> Result<bool> {
+        let text = truncate_text(text, self.config.max_length);
+        let text = wrap_text(text, self.config.wrap);
+        let previous = match self.get_clipboard_text() {
+            Ok(previous) => previous.into_text(),
+            Err(e) => {
+                debug!("Could not read previous clipboard contents: {}", e);
+                None
+            }
+        };
+        if !force && is_unchanged(self.last_copied.as_deref(), previous.as_deref(), &text) {
+            info!("📋 Clipboard unchanged, skipped copying the same text");
+            return Ok(false);
+        }
+        self.previous_clipboard = Some(previous);
+
+        self.write_clipboard(&text)?;
+        self.last_copied = Some(text);
+        self.copied_at = Some(Instant::now());
+        Ok(true)
+    }
+
+    /// Clear our last copy once `clipboard.clear_after` seconds have passed, so sensitive
+    /// text doesn't linger. Returns whether the clipboard was cleared.
+    pub fn clear_expired(&mut self) -> Result<bool> {
+        if !is_expired(self.config.clear_after, self.copied_at) {
+            return Ok(false);
+        }
+        self.clear_if_ours()
+    }
+
+    /// With `clipboard.clear_after` set, clear our last copy right away, e.g. on exit
+    pub fn clear_pending(&mut self) -> Result<bool> {
+        if self.config.clear_after.is_none() || self.copied_at.is_none() {
+            return Ok(false);
+        }
+        self.clear_if_ours()
+    }
+
+    /// Clear the clipboard if it still holds our last copy, leaving anything copied since alone
+    fn clear_if_ours(&mut self) -> Result<bool> {
+        self.copied_at = None;
+        let current = self.get_clipboard_text()?.into_text();
+        if current.is_none() || current != self.last_copied {
+            debug!("Clipboard changed since our copy, not clearing it");
+            return Ok(false);
+        }
+        self.clear_clipboard()?;
+        self.last_copied = None;
+        info!("🧹 Cleared the copied text from the clipboard");
+        Ok(true)
+    }
+
+    /// Restore the clipboard contents from before the last copy.
+    ///
+    /// If the clipboard was previously empty or held non-text data it is cleared instead.
+    /// Returns `true` if text was restored and `false` if the clipboard was cleared.
+    pub fn restore_previous(&mut self) -> Result<bool> {
+        self.last_copied = None;
+        self.copied_at = None;
+        match self.previous_clipboard.take() {
+            Some(Some(previous)) => {
+                self.write_clipboard(&previous)?;
+                info!("↩️ Restored previous clipboard contents");
+                Ok(true)
+            }
+            Some(None) => {
+                self.clear_clipboard()?;
+                info!("↩️ Cleared clipboard (previous contents were empty or not text)");
+                Ok(false)
+            }
+            None => Err(anyhow::anyhow!("Nothing to undo")),
+        }
+    }
+
+    /// Clear the clipboard
+    fn clear_clipboard(&self) -> Result<()> {
+        if let Err(e) = copy::clear(clipboard_type(self.config.target), Seat::All) {
+            debug!(
+                "Wayland native clipboard clear failed: {}, trying wl-copy",
+                e
+            );
+            for selection_args in wl_copy_selections(self.config.target) {
+                let output = Command::new("wl-copy")
+                    .arg("--clear")
+                    .args(*selection_args)
+                    .output()
+                    .context("Failed to execute wl-copy")?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Err(anyhow::anyhow!("wl-copy --clear failed: {}", stderr));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write text to clipboard using Wayland native clipboard
+    fn write_clipboard(&mut self, text: &str) -> Result<()> {
         // Try Wayland native clipboard first
         match self.copy_wayland_native(text) {
             Ok(_) => {
-                info!("✅ Text copied to clipboard (Wayland native): \"{}\"", text);
+                info!(
+                    "✅ Text copied to clipboard (Wayland native): {}",
+                    redact::transcript(text)
+                );
                 return Ok(());
             }
             Err(e) => {
@@ -38,10 +170,11 @@ impl ClipboardManager {
 
     /// Copy using native Wayland clipboard
     fn copy_wayland_native(&self, text: &str) -> Result<()> {
-        let opts = Options::new();
+        let mut opts = Options::new();
+        opts.clipboard(clipboard_type(self.config.target));
         opts.copy(
             Source::Bytes(text.as_bytes().into()),
-            MimeType::Specific("text/plain;charset=utf-8".to_string()),
+            MimeType::Specific(self.config.mime_type.clone()),
         )
         .context("Failed to copy to Wayland clipboard")?;
         Ok(())
@@ -56,26 +189,44 @@ impl ClipboardManager {
         }
 
         debug!("Using wl-copy for clipboard");
-        let output = Command::new("wl-copy")
-            .arg(text)
-            .output()
-            .context("Failed to execute wl-copy")?;
-
-        if output.status.success() {
-            info!("✅ Text copied to clipboard (wl-copy): \"{}\"", text);
-            Ok(())
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            Err(anyhow::anyhow!("wl-copy failed: {}", stderr))
+        for selection_args in wl_copy_selections(self.config.target) {
+            let output = Command::new("wl-copy")
+                .args(*selection_args)
+                .args(["--type", &self.config.mime_type])
+                .arg(text)
+                .output()
+                .context("Failed to execute wl-copy")?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(anyhow::anyhow!("wl-copy failed: {}", stderr));
+            }
         }
+        info!(
+            "✅ Text copied to clipboard (wl-copy): {}",
+            redact::transcript(text)
+        );
+        Ok(())
     }
 
     /// Paste text directly to the active window using Wayland tools
     pub async fn paste_text(&mut self, text: &str) -> Result<()> {
-        // First copy to clipboard
-        self.copy_to_clipboard(text)?;
+        // First copy to clipboard, the paste works the same if it's already there
+        self.copy_to_clipboard(text, false)?;
 
         if self.config.auto_paste {
+            let focused_app = self.focused_app_id();
+            if !is_paste_allowed(
+                focused_app.as_deref(),
+                &self.config.paste_allowlist,
+                &self.config.paste_denylist,
+            ) {
+                info!(
+                    "📋 Skipping auto-paste into {}, text is in clipboard only",
+                    focused_app.as_deref().unwrap_or("unknown app")
+                );
+                return Ok(());
+            }
+
             info!("🖱️ Auto-pasting text to active window");
 
             // Wait for configured delay
@@ -95,6 +246,38 @@ impl ClipboardManager {
         Ok(())
     }
 
+    /// App id of the focused window, from `focused_app_command` or Hyprland's `hyprctl`
+    fn focused_app_id(&self) -> Option<String> {
+        let result = match &self.config.focused_app_command {
+            Some(command) => Command::new("sh")
+                .args(["-c", command])
+                .output()
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string()),
+            None if which("hyprctl").is_ok() => Command::new("hyprctl")
+                .args(["activewindow", "-j"])
+                .output()
+                .map(|output| {
+                    serde_json::from_slice::<serde_json::Value>(&output.stdout)
+                        .ok()
+                        .and_then(|window| window["class"].as_str().map(str::to_string))
+                        .unwrap_or_default()
+                }),
+            None => return None,
+        };
+
+        match result {
+            Ok(app_id) if !app_id.is_empty() => {
+                debug!("Focused app: {}", app_id);
+                Some(app_id)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Failed to get focused app: {}", e);
+                None
+            }
+        }
+    }
+
     /// Try Wayland paste methods - prioritize wtype, fallback to ydotool
     async fn try_wayland_paste(&self) -> Result<()> {
         // Try wtype first (Wayland native)
@@ -144,29 +327,29 @@ impl ClipboardManager {
         Ok(())
     }
 
-    /// Get current clipboard content using wl-paste
-    pub fn get_clipboard_text(&mut self) -> Result<String> {
+    /// Get current clipboard content using wl-paste. Errors only when wl-paste couldn't
+    /// be run, an empty or binary clipboard is `ClipboardContents::NonText`.
+    pub fn get_clipboard_text(&mut self) -> Result<ClipboardContents> {
         self.get_with_wl_paste()
     }
 
     /// Get clipboard content using wl-paste command
-    fn get_with_wl_paste(&self) -> Result<String> {
+    fn get_with_wl_paste(&self) -> Result<ClipboardContents> {
         if which("wl-paste").is_err() {
             return Err(anyhow::anyhow!(
                 "wl-paste not found. Install wl-clipboard for Wayland clipboard support"
             ));
         }
 
-        let output = Command::new("wl-paste")
+        let output = wl_paste_command(self.config.target)
             .output()
             .context("Failed to execute wl-paste")?;
+        parse_wl_paste(output.status.success(), output.stdout, &output.stderr)
+    }
 
-        if output.status.success() {
-            String::from_utf8(output.stdout).context("Clipboard contents are not valid UTF-8")
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            Err(anyhow::anyhow!("wl-paste failed: {}", stderr))
-        }
+    /// Swap in reloaded clipboard settings, keeping the undo state
+    pub fn update_config(&mut self, config: &Config) {
+        self.config = config.clipboard.clone();
     }
 
     /// Check if auto-paste is enabled
@@ -206,10 +389,208 @@ impl ClipboardManager {
     }
 }
 
+/// Whether auto-paste may target the focused app. An unknown app is only
+/// allowed when no allowlist is configured.
+fn is_paste_allowed(app_id: Option<&str>, allowlist: &[String], denylist: &[String]) -> bool {
+    let listed =
+        |list: &[String], app_id: &str| list.iter().any(|app| app.eq_ignore_ascii_case(app_id));
+    match app_id {
+        Some(app_id) if listed(denylist, app_id) => false,
+        Some(app_id) => allowlist.is_empty() || listed(allowlist, app_id),
+        None => allowlist.is_empty(),
+    }
+}
+
+/// Selection(s) to copy to for `clipboard.target`
+fn clipboard_type(target: ClipboardTarget) -> ClipboardType {
+    match target {
+        ClipboardTarget::Clipboard => ClipboardType::Regular,
+        ClipboardTarget::Primary => ClipboardType::Primary,
+        ClipboardTarget::Both => ClipboardType::Both,
+    }
+}
+
+/// Selection arguments for each wl-copy run, which sets one selection at a time
+fn wl_copy_selections(target: ClipboardTarget) -> &'static [&'static [&'static str]] {
+    match target {
+        ClipboardTarget::Clipboard => &[&[]],
+        ClipboardTarget::Primary => &[&["--primary"]],
+        ClipboardTarget::Both => &[&[], &["--primary"]],
+    }
+}
+
+/// Cut text down to `max_length` characters, a safety valve for runaway output that
+/// could hang paste targets. 0 means no limit.
+fn truncate_text(text: &str, max_length: usize) -> &str {
+    if max_length == 0 {
+        return text;
+    }
+    match text.char_indices().nth(max_length) {
+        Some((end, _)) => {
+            warn!(
+                "Text is {} characters, truncated to clipboard.max_length before copying",
+                text.chars().count()
+            );
+            &text[..end]
+        }
+        None => text,
+    }
+}
+
+/// Wrap copied text for pasting into documents: `Codeblock` fences it in triple
+/// backticks, `Quote` prefixes each line with `> `
+fn wrap_text(text: &str, wrap: ClipboardWrap) -> String {
+    match wrap {
+        ClipboardWrap::Codeblock => format!("```\n{text}\n```"),
+        ClipboardWrap::Quote => text
+            .lines()
+            .map(|line| format!("> {line}").trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ClipboardWrap::None => text.to_string(),
+    }
+}
+
+/// The command that reads back the clipboard `target`
+fn wl_paste_command(target: ClipboardTarget) -> Command {
+    let mut command = Command::new("wl-paste");
+    command.args(wl_paste_args(target));
+    command
+}
+
+/// wl-paste arguments for reading back what we copied. Undo restores the selection we copy
+/// to, primary if that's the only one. Without `--no-newline` wl-paste appends a newline,
+/// so the text would never match our last copy.
+fn wl_paste_args(target: ClipboardTarget) -> Vec<&'static str> {
+    let mut args = vec!["--no-newline"];
+    if target == ClipboardTarget::Primary {
+        args.push("--primary");
+    }
+    args
+}
+
+/// Interpret wl-paste's output, which fails with "Nothing is copied" on an empty clipboard
+fn parse_wl_paste(success: bool, stdout: Vec<u8>, stderr: &[u8]) -> Result<ClipboardContents> {
+    let stderr = String::from_utf8_lossy(stderr);
+    if !success {
+        if stderr.contains("Nothing is copied") || stderr.contains("No selection") {
+            return Ok(ClipboardContents::NonText);
+        }
+        return Err(anyhow::anyhow!("wl-paste failed: {}", stderr.trim()));
+    }
+    match String::from_utf8(stdout) {
+        Ok(text) => Ok(ClipboardContents::Text(text)),
+        Err(_) => {
+            debug!("Clipboard holds binary data, treating it as empty");
+            Ok(ClipboardContents::NonText)
+        }
+    }
+}
+
+/// Whether a copy made at `copied_at` is due to be cleared
+fn is_expired(clear_after: Option<f64>, copied_at: Option<Instant>) -> bool {
+    match (clear_after, copied_at) {
+        (Some(secs), Some(copied_at)) => copied_at.elapsed().as_secs_f64() >= secs,
+        _ => false,
+    }
+}
+
+/// Whether the clipboard still holds exactly the text we copied last
+fn is_unchanged(last_copied: Option<&str>, current: Option<&str>, text: &str) -> bool {
+    last_copied == Some(text) && current == Some(text)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_clipboard_target() {
+        assert_eq!(
+            clipboard_type(ClipboardTarget::Clipboard),
+            ClipboardType::Regular
+        );
+        assert_eq!(
+            clipboard_type(ClipboardTarget::Primary),
+            ClipboardType::Primary
+        );
+        assert_eq!(clipboard_type(ClipboardTarget::Both), ClipboardType::Both);
+        assert_eq!(wl_copy_selections(ClipboardTarget::Both).len(), 2);
+        assert_eq!(
+            wl_copy_selections(ClipboardTarget::Primary),
+            &[&["--primary"]]
+        );
+        assert_eq!(
+            wl_copy_selections(ClipboardTarget::Clipboard),
+            &[&[] as &[&str]]
+        );
+    }
+
+    #[test]
+    fn test_parse_wl_paste() {
+        let text = parse_wl_paste(true, b"hello".to_vec(), b"").unwrap();
+        assert_eq!(text, ClipboardContents::Text("hello".to_string()));
+        // A PNG header isn't UTF-8
+        let image = parse_wl_paste(true, vec![0x89, b'P', b'N', b'G', 0xff, 0xfe], b"").unwrap();
+        assert_eq!(image, ClipboardContents::NonText);
+        let empty = parse_wl_paste(false, Vec::new(), b"Nothing is copied\n").unwrap();
+        assert_eq!(empty.into_text(), None);
+        assert!(
+            parse_wl_paste(false, Vec::new(), b"Failed to connect to a Wayland server").is_err()
+        );
+    }
+
+    #[test]
+    fn test_read_back_matches_last_copy() {
+        assert_eq!(
+            wl_paste_args(ClipboardTarget::Clipboard),
+            vec!["--no-newline"]
+        );
+        assert_eq!(
+            wl_paste_args(ClipboardTarget::Primary),
+            vec!["--no-newline", "--primary"]
+        );
+
+        // What `wl-paste --no-newline` prints for our copy is the copy itself, which is how
+        // clear_after recognizes it's still ours
+        let last_copied = "secret token";
+        let current = parse_wl_paste(true, last_copied.as_bytes().to_vec(), b"").unwrap();
+        assert_eq!(current.into_text().as_deref(), Some(last_copied));
+    }
+
+    #[test]
+    fn test_is_unchanged() {
+        assert!(is_unchanged(Some("hello"), Some("hello"), "hello"));
+        assert!(!is_unchanged(Some("hello"), Some("hello"), "hello there"));
+        // Something else was copied since, so copy again
+        assert!(!is_unchanged(Some("hello"), Some("other"), "hello"));
+        assert!(!is_unchanged(None, Some("hello"), "hello"));
+
+        // Text ending in a newline reads back exactly with --no-newline, so copying it
+        // again is still skipped
+        let command = wl_paste_command(ClipboardTarget::Clipboard);
+        assert_eq!(command.get_program(), "wl-paste");
+        assert!(command.get_args().any(|arg| arg == "--no-newline"));
+        let text = "first line\nsecond line\n";
+        let current = parse_wl_paste(true, text.as_bytes().to_vec(), b"")
+            .unwrap()
+            .into_text();
+        assert!(is_unchanged(Some(text), current.as_deref(), text));
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        assert_eq!(wrap_text("ls -la", ClipboardWrap::None), "ls -la");
+        assert_eq!(
+            wrap_text("ls -la", ClipboardWrap::Codeblock),
+            "```\nls -la\n```"
+        );
+        assert_eq!(
+            wrap_text("first\n\nsecond", ClipboardWrap::Quote),
+            "> first\n>\n> second"
+        );
+    }
+
     #[test]
     fn test_clipboard_manager_creation() {
         let config = Config::default();
@@ -224,6 +605,25 @@ mod tests {
         // Note: .len() is always >= 0 for Vec, so no assertion needed
     }
 
+    #[test]
+    fn test_restore_without_copy() {
+        let config = Config::default();
+        let mut clipboard = ClipboardManager::new(&config).unwrap();
+        assert!(clipboard.restore_previous().is_err());
+    }
+
+    #[test]
+    fn test_paste_allowlist_and_denylist() {
+        let allow = vec!["firefox".to_string()];
+        let deny = vec!["kitty".to_string()];
+        assert!(is_paste_allowed(Some("anything"), &[], &[]));
+        assert!(is_paste_allowed(Some("Firefox"), &allow, &deny));
+        assert!(!is_paste_allowed(Some("slack"), &allow, &deny));
+        assert!(!is_paste_allowed(Some("kitty"), &[], &deny));
+        assert!(is_paste_allowed(None, &[], &deny));
+        assert!(!is_paste_allowed(None, &allow, &[]));
+    }
+
     #[test]
     fn test_auto_paste_configuration() {
         let config = Config::default();
@@ -232,4 +632,20 @@ mod tests {
         clipboard.set_auto_paste(true);
         assert!(clipboard.is_auto_paste_enabled());
     }
+
+    #[test]
+    fn test_truncate_text() {
+        assert_eq!(truncate_text("héllo world", 5), "héllo");
+        assert_eq!(truncate_text("héllo", 5), "héllo");
+        assert_eq!(truncate_text("héllo", 0), "héllo");
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let copied_at = Instant::now() - Duration::from_secs(10);
+        assert!(is_expired(Some(5.0), Some(copied_at)));
+        assert!(!is_expired(Some(30.0), Some(copied_at)));
+        assert!(!is_expired(None, Some(copied_at)));
+        assert!(!is_expired(Some(5.0), None));
+    }
 }
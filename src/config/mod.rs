@@ -1,19 +1,38 @@
 use anyhow::{Context, Result};
-use dirs::config_dir;
+use dirs::{cache_dir, config_dir, home_dir};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tracing::{debug, info};
 
 const APP_NAME: &str = "simple-stt";
 const CONFIG_FILE: &str = "config.toml";
+/// Environment variable pointing at a config file to use instead of the XDG one
+pub const CONFIG_PATH_ENV: &str = "SIMPLE_STT_CONFIG";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AudioConfig {
     pub sample_rate: u32,
     pub channels: u16,
-    pub chunk_size: usize,
+    pub chunk_size: usize, // Capture buffer in frames, the device default is used when unsupported
     pub max_recording_time: f64,
+    pub silence_threshold: f32, // Audio level (0-100) below which input counts as silence
+    pub gain: f32, // Input volume multiplier for quiet microphones, +/- adjusts it live
+    pub silence_duration: f64, // Seconds of trailing silence that stop a voice-activated recording
+    pub voice_activated: bool, // Recording waits for speech to start capturing, then auto-stops
+    pub level_attack_ms: u32, // Time constant for the level meter to rise, 0 for no smoothing
+    pub level_release_ms: u32, // Time constant for the level meter to fall, 0 for no smoothing
+    pub preroll_ms: u32, // Audio kept from before recording starts, 0 to disable. Keeps the mic open while idle
+    pub min_duration_ms: u32, // Short recordings are padded with silence to this length, 0 to disable
+    pub min_speech_ms: u32, // Recordings with less audio above the silence threshold are skipped, 0 to disable
+    pub trim_silence: bool, // Drop leading and trailing audio below the silence threshold before transcribing
+    pub highpass_hz: Option<f32>, // Filter out rumble and hum below this frequency before transcribing, e.g. 80
+    pub save_recordings: Option<String>, // Directory to keep a WAV copy of every recording in
+    pub output_bits: u16,         // WAV bit depth: 8, 16 or 24 integer, or 32 float
+    pub device: Option<String>, // Input device name (or part of it) to record from, the default device when unset
+    #[serde(skip)]
+    pub input_file: Option<PathBuf>, // Set from --input-file, never persisted
 }
 
 impl Default for AudioConfig {
@@ -23,22 +42,66 @@ impl Default for AudioConfig {
             channels: 1,
             chunk_size: 2048,
             max_recording_time: 120.0,
+            silence_threshold: 15.0,
+            gain: 1.0,
+            silence_duration: 2.0,
+            voice_activated: false,
+            level_attack_ms: 20,
+            level_release_ms: 300,
+            preroll_ms: 0,
+            min_duration_ms: 1000,
+            min_speech_ms: 200,
+            trim_silence: false,
+            highpass_hz: None,
+            save_recordings: None,
+            output_bits: 16,
+            device: None,
+            input_file: None,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct WhisperConfig {
-    pub backend: String, // "api" or "local"
+    pub backend: String, // "api", "local", "server" or "vosk"
     pub api_key: Option<String>,
     pub model: String,
+    pub models: Vec<String>, // Shortlist offered by the model selector (M)
     pub language: Option<String>,
-    pub timeout: u64,
+    pub languages: Vec<String>, // Cycled with Shift+L, "auto" for auto-detect
+    #[serde(alias = "timeout")]
+    pub request_timeout: u64, // API/server: seconds for the whole request, upload included
+    pub connect_timeout: u64,   // API/server: seconds to establish the connection
 
     // Local-specific options
     pub model_path: Option<String>,
     pub download_models: bool,
-    pub device: String, // "auto", "cpu", "cuda"
+    pub fallback_to_api: bool, // Use the OpenAI API when the local model fails to load and a key is set
+    pub device: String,        // "auto", "cpu", "cuda"
+    pub filter_output: bool, // Strip filter_tokens from local output, false for raw whisper output
+    pub filter_tokens: Vec<String>, // Matched as written, lowercase and uppercase
+    pub suppress_blank: bool, // Local: stop whisper starting a segment with a blank
+    pub suppress_non_speech: bool, // Local: stop whisper emitting non-speech tokens like ♪ or (laughs)
+    pub debug_segments: bool,      // Show each raw and cleaned segment in the TUI log panel
+    pub collapse_repeats: usize, // Collapse a phrase repeated this many times in a row into one, 0 to disable
+    pub stream_interval: Option<f64>, // Seconds between live transcriptions of a recording in progress
+    pub vocab_file: Option<String>, // Word list to prompt with and correct near-misses to, one entry per line
+    pub use_context: bool, // Prompt each transcription with the previous one, for continued dictation
+    pub local_timeout: u64, // Seconds before a local transcription is aborted, 0 for no limit
+    pub benchmark_on_load: bool, // Local: time a 30 s test clip after loading to estimate speed
+    pub retry_on_empty: bool, // Local: decode again with another sampling strategy when audio with speech gives no text
+    pub no_speech_threshold: f32, // Local: drop segments this likely to be non-speech (0-1), 0 to disable
+    pub n_threads: Option<usize>, // Local: CPU threads for whisper, physical cores when unset
+    pub tag_language: bool, // Prefix transcriptions with their detected (or configured) language, e.g. "[es] "
+    pub sampling: SamplingMode,
+    pub best_of: usize,     // Greedy: candidates to pick the best from, 1-8
+    pub beam_size: usize,   // Beam: beams to search, 1-8
+    pub beam_patience: f32, // Beam: search patience, 0.5-4.0
+
+    // Server-specific options
+    pub server_url: String, // OpenAI-compatible transcription endpoint, e.g. whisper.cpp server
+    pub upload_format: UploadFormat,
 }
 
 impl Default for WhisperConfig {
@@ -47,11 +110,149 @@ impl Default for WhisperConfig {
             backend: "local".to_string(), // Default to local - better UX, no API keys needed
             api_key: None,
             model: "base.en".to_string(), // Use local model name for local backend
+            models: [
+                "tiny.en",
+                "base.en",
+                "small.en",
+                "medium.en",
+                "large",
+                "large-v3-turbo",
+            ]
+            .iter()
+            .map(|model| model.to_string())
+            .collect(),
             language: Some("en".to_string()), // Set default language for better accuracy
-            timeout: 60,
+            languages: Vec::new(),
+            request_timeout: 60,
+            connect_timeout: 10,
             model_path: None, // Will use default cache directory
             download_models: true,
+            fallback_to_api: false,
             device: "auto".to_string(),
+            filter_output: true,
+            filter_tokens: [
+                "[BLANK_AUDIO]",
+                "[MUSIC]",
+                "[NOISE]",
+                "[SILENCE]",
+                "[SPEAKING]",
+                "[SOUND]",
+                "[BEEP]",
+                "[APPLAUSE]",
+                "[LAUGHTER]",
+                "[COUGH]",
+                "(blank)",
+                "(no audio)",
+                "inaudible",
+            ]
+            .iter()
+            .map(|token| token.to_string())
+            .collect(),
+            suppress_blank: true,
+            suppress_non_speech: false,
+            debug_segments: false,
+            collapse_repeats: 6, // Well past deliberate repetition like "no, no, no, no"
+            vocab_file: None,
+            stream_interval: None,
+            use_context: false,
+            local_timeout: 0,
+            tag_language: false,
+            benchmark_on_load: false,
+            retry_on_empty: false,
+            no_speech_threshold: 0.0,
+            n_threads: None,
+            sampling: SamplingMode::Greedy,
+            best_of: 1,
+            beam_size: 5,
+            beam_patience: 1.0,
+            server_url: "http://127.0.0.1:8080/inference".to_string(),
+            upload_format: UploadFormat::Wav,
+        }
+    }
+}
+
+/// Upper bound for `best_of` and `beam_size`, whisper.cpp runs at most 8 decoders
+const MAX_DECODERS: usize = 8;
+
+/// How the API and server backends upload audio, `whisper.upload_format`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadFormat {
+    #[default]
+    Wav,
+    Opus, // Ogg Opus, a fraction of the size
+}
+
+/// Local whisper decoding strategy named by `whisper.sampling`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplingMode {
+    #[default]
+    Greedy, // Faster
+    Beam, // More accurate
+}
+
+/// Local whisper decoding strategy, with the configured values clamped to sane ranges
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    Greedy { best_of: usize },
+    BeamSearch { beam_size: usize, patience: f32 },
+}
+
+impl Sampling {
+    /// Strategy for a second attempt when this one produced nothing: beam search after
+    /// greedy, several greedy candidates after beam search
+    pub fn fallback(self) -> Sampling {
+        match self {
+            Sampling::Greedy { .. } => Sampling::BeamSearch {
+                beam_size: 5,
+                patience: 1.0,
+            },
+            Sampling::BeamSearch { .. } => Sampling::Greedy { best_of: 5 },
+        }
+    }
+}
+
+impl std::fmt::Display for Sampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sampling::Greedy { best_of } => write!(f, "greedy, best of {best_of}"),
+            Sampling::BeamSearch {
+                beam_size,
+                patience,
+            } => write!(f, "beam {beam_size}, patience {patience:.1}"),
+        }
+    }
+}
+
+impl WhisperConfig {
+    /// Models for the selector: the configured shortlist, plus the current model if it's missing
+    pub fn model_list(&self) -> Vec<String> {
+        let mut models = self.models.clone();
+        if !models.contains(&self.model) {
+            models.push(self.model.clone());
+        }
+        models
+    }
+
+    /// Threads whisper runs on, at least one
+    pub fn thread_count(&self) -> usize {
+        self.n_threads.unwrap_or_else(num_cpus::get_physical).max(1)
+    }
+
+    pub fn sampling(&self) -> Sampling {
+        match self.sampling {
+            SamplingMode::Beam => Sampling::BeamSearch {
+                beam_size: self.beam_size.clamp(1, MAX_DECODERS),
+                patience: if self.beam_patience.is_finite() {
+                    self.beam_patience.clamp(0.5, 4.0)
+                } else {
+                    1.0
+                },
+            },
+            SamplingMode::Greedy => Sampling::Greedy {
+                best_of: self.best_of.clamp(1, MAX_DECODERS),
+            },
         }
     }
 }
@@ -60,16 +261,38 @@ impl Default for WhisperConfig {
 pub struct LlmProfile {
     pub name: String,
     pub prompt: String,
+    #[serde(default)]
+    pub temperature: Option<f32>, // Overrides llm.temperature for this profile
+    #[serde(default)]
+    pub model: Option<String>, // Overrides llm.model for this profile
+    #[serde(default)]
+    pub inline_prompt: bool, // Send the prompt in the user message with the text, not as a system prompt
+    #[serde(default)]
+    pub output: Option<String>, // "clipboard" (default), "file:<path>" or "command:<shell command>"
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LlmConfig {
     pub provider: String,
     pub model: String,
     pub max_tokens: u32,
+    pub temperature: Option<f32>, // Unset for the provider's default, 0.3 with OpenAI
     pub default_profile: String,
+    pub compare_profiles: Vec<String>, // Refined side by side with P, empty for every profile
     pub profiles: HashMap<String, LlmProfile>,
     pub api_key: Option<String>,
+    pub request_timeout: u64, // Seconds for the whole request, generation included
+    pub connect_timeout: u64, // Seconds to establish the connection
+    pub auto_profile_rules: Vec<AutoProfileRule>, // Pick the profile P refines with from the transcript's first word
+    pub strip_trigger: bool,                      // Remove the matched keyword before refining
+}
+
+/// Refine with `profile` when a transcript starts with `keyword`, e.g. "email ..."
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutoProfileRule {
+    pub keyword: String,
+    pub profile: String,
 }
 
 impl Default for LlmConfig {
@@ -81,6 +304,10 @@ impl Default for LlmConfig {
             LlmProfile {
                 name: "General Text Cleanup".to_string(),
                 prompt: "Please clean up and format this transcribed text, fixing any grammar issues and making it more readable. It is extremely important to maintain the original meaning and not add any additional information:".to_string(),
+                temperature: None,
+                model: None,
+                inline_prompt: false,
+                output: None,
             },
         );
 
@@ -89,6 +316,10 @@ impl Default for LlmConfig {
             LlmProfile {
                 name: "Todo/Task".to_string(),
                 prompt: "Convert this speech into a clear, actionable todo item or task description. Make it specific, concise, and action-oriented. Use bullet points (markdown format) if multiple tasks are mentioned:".to_string(),
+                temperature: None,
+                model: None,
+                inline_prompt: false,
+                output: None,
             },
         );
 
@@ -97,6 +328,10 @@ impl Default for LlmConfig {
             LlmProfile {
                 name: "Email Format".to_string(),
                 prompt: "Format this transcribed text as a professional email. Fix grammar, structure sentences properly, and ensure appropriate tone:".to_string(),
+                temperature: None,
+                model: None,
+                inline_prompt: false,
+                output: None,
             },
         );
 
@@ -105,6 +340,10 @@ impl Default for LlmConfig {
             LlmProfile {
                 name: "Slack Message".to_string(),
                 prompt: "Format this transcribed text as a clear, concise Slack message. Keep it casual but professional, fix any grammar issues:".to_string(),
+                temperature: None,
+                model: None,
+                inline_prompt: false,
+                output: None,
             },
         );
 
@@ -112,17 +351,92 @@ impl Default for LlmConfig {
             provider: "openai".to_string(),
             model: "gpt-3.5-turbo".to_string(),
             max_tokens: 500,
+            temperature: None,
             default_profile: "general".to_string(),
+            compare_profiles: Vec::new(),
             profiles,
             api_key: None,
+            request_timeout: 30,
+            connect_timeout: 10,
+            auto_profile_rules: Vec::new(),
+            strip_trigger: true,
         }
     }
 }
 
+impl LlmConfig {
+    /// Model to use for a profile, its own override or the global model
+    pub fn model_for<'a>(&'a self, profile: &'a LlmProfile) -> &'a str {
+        profile.model.as_deref().unwrap_or(&self.model)
+    }
+
+    /// Profile picked by the first `auto_profile_rules` keyword the text starts with
+    /// (case-insensitive, whole word), with the text to refine: the keyword and the
+    /// punctuation after it removed when `strip_trigger` is set
+    pub fn auto_profile<'a>(&'a self, text: &'a str) -> Option<(&'a str, &'a str)> {
+        let text = text.trim_start();
+        self.auto_profile_rules.iter().find_map(|rule| {
+            let keyword = rule.keyword.trim();
+            let rest = text.get(..keyword.len()).and_then(|start| {
+                start
+                    .eq_ignore_ascii_case(keyword)
+                    .then(|| &text[keyword.len()..])
+            })?;
+            if rest.starts_with(char::is_alphanumeric) || keyword.is_empty() {
+                return None; // "emails" doesn't trigger "email"
+            }
+            let rest = if self.strip_trigger {
+                rest.trim_start_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation())
+            } else {
+                text
+            };
+            Some((rule.profile.as_str(), rest))
+        })
+    }
+
+    /// Sampling temperature for a profile, its own override or the global temperature,
+    /// `None` when neither is set
+    pub fn temperature_for(&self, profile: &LlmProfile) -> Option<f32> {
+        profile.temperature.or(self.temperature)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ClipboardConfig {
     pub auto_paste: bool,
     pub paste_delay: f64,
+    pub auto_copy: bool, // Copy finished transcriptions automatically, otherwise press C
+    pub paste_allowlist: Vec<String>, // App ids auto-paste may target, empty allows any app
+    pub paste_denylist: Vec<String>, // App ids auto-paste never targets, e.g. terminals
+    pub focused_app_command: Option<String>, // Prints the focused app id, defaults to hyprctl
+    pub wrap: ClipboardWrap,
+    pub target: ClipboardTarget,
+    pub mime_type: String,            // MIME type offered for the copied text
+    pub accumulate: bool, // Copy every transcription of the session joined together, N starts a new session
+    pub accumulate_separator: String, // Joins accumulated transcriptions, e.g. " " or "\n"
+    pub max_length: usize, // Truncate longer text (in characters) before copying, 0 for no limit
+    pub clear_after: Option<f64>, // Seconds after which copied text is cleared, unless something else was copied
+}
+
+/// How `clipboard.wrap` formats copied text for pasting into documents
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardWrap {
+    #[default]
+    None,
+    Codeblock, // Fenced in triple backticks
+    Quote,     // "> " before each line
+}
+
+/// Selection(s) `clipboard.target` copies to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardTarget {
+    #[default]
+    Clipboard,
+    Primary, // Middle-click paste
+    Both,
 }
 
 impl Default for ClipboardConfig {
@@ -130,16 +444,68 @@ impl Default for ClipboardConfig {
         Self {
             auto_paste: false,
             paste_delay: 0.1,
+            auto_copy: true,
+            paste_allowlist: Vec::new(),
+            paste_denylist: Vec::new(),
+            focused_app_command: None,
+            wrap: ClipboardWrap::None,
+            target: ClipboardTarget::Clipboard,
+            mime_type: "text/plain;charset=utf-8".to_string(),
+            accumulate: false,
+            accumulate_separator: " ".to_string(),
+            max_length: 100_000,
+            clear_after: None,
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct UiConfig {
     pub enabled: bool,
     pub position_x: u32,
     pub position_y: u32,
     pub auto_hide_delay: f64,
+    // Return to idle while earlier recordings are still transcribing, for back-to-back
+    // dictation. Off waits for each result, keeping it cancellable (Esc) and refinable.
+    pub queue_recordings: bool,
+    pub waveform_style: WaveformStyle,
+    pub persist_stats: bool, // Add session stats to the all-time totals in the cache directory
+    pub record_mode: RecordMode,
+    pub record_countdown: u32, // Seconds counted down in the status bar before recording starts, 0 for none
+    pub record_bell: bool,     // Ring the terminal bell as recording starts
+    pub alt_screen: bool, // Draw on the alternate screen, false to run inline and keep results in scrollback
+    pub spinner: bool, // Animate the transcribing status with elapsed and estimated remaining time
+    pub auto_quit_after: Option<f64>, // Seconds without activity before idle_action
+    pub idle_action: IdleAction,
+}
+
+/// What `ui.auto_quit_after` does once it passes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleAction {
+    #[default]
+    Quit,
+    Unload, // Free the local model until the next transcription
+}
+
+/// How `ui.waveform_style` draws the recording waveform
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WaveformStyle {
+    #[default]
+    Bars,
+    Envelope, // Min/max per column
+    Rms,      // Loudness per column
+}
+
+/// How Space controls recording, `ui.record_mode`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordMode {
+    #[default]
+    Toggle, // Space starts and stops
+    Hold, // Record while Space is held
 }
 
 impl Default for UiConfig {
@@ -149,10 +515,79 @@ impl Default for UiConfig {
             position_x: 50,
             position_y: 50,
             auto_hide_delay: 3.0,
+            queue_recordings: true,
+            waveform_style: WaveformStyle::Bars,
+            persist_stats: true,
+            record_mode: RecordMode::Toggle,
+            record_countdown: 0,
+            record_bell: false,
+            alt_screen: true,
+            spinner: true,
+            auto_quit_after: None,
+            idle_action: IdleAction::Quit,
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    pub format: String,      // "text" or "json"
+    pub level: String,       // Overridden by RUST_LOG when set
+    pub dir: Option<String>, // Defaults to the cache directory
+    pub transcripts: bool, // Write transcript text to the log, otherwise only its length and a hash
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: "text".to_string(),
+            level: "debug".to_string(),
+            dir: None,
+            transcripts: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TextConfig {
+    pub spoken_punctuation: bool, // Turn "comma", "period", "new paragraph" etc. into symbols
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct OutputConfig {
+    pub file: Option<PathBuf>, // Append each transcription to this file, e.g. for `tail -f`
+    pub timestamp: bool,       // Prefix each line with the local date and time
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct HooksConfig {
+    pub on_record_start: Option<String>, // Shell command run when recording starts
+    pub on_record_stop: Option<String>,  // Shell command run when recording stops
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct KeybindingsConfig {
+    // Media key that also toggles recording, e.g. "play_pause" for headset buttons. It's read
+    // through the terminal, so it only works while that has focus and speaks the kitty protocol.
+    pub record_media_key: Option<String>,
+}
+
+/// A transcript fixup, applied in config order after transcription
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replacement {
+    pub pattern: String,
+    pub replacement: String,
+    #[serde(default)]
+    pub regex: bool, // Treat pattern as a regex, otherwise it matches whole words literally
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
     pub audio: AudioConfig,
@@ -160,24 +595,75 @@ pub struct Config {
     pub llm: LlmConfig,
     pub clipboard: ClipboardConfig,
     pub ui: UiConfig,
+    #[serde(default)]
+    pub log: LogConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub text: TextConfig,
+    #[serde(default)]
+    pub keybindings: KeybindingsConfig,
+    #[serde(default)]
+    pub replacements: Vec<Replacement>,
+}
+
+/// Rebuild a config from `root` with the dotted `key` set to `value`, or removed for
+/// `None`. Returns `None` when a parent of the key isn't a section.
+fn with_nested(
+    root: &toml::Value,
+    key: &str,
+    value: Option<toml::Value>,
+) -> Option<Result<Config>> {
+    let mut root = root.clone();
+    let (sections, field) = key.rsplit_once('.').unwrap_or(("", key));
+    let mut table = root.as_table_mut()?;
+    for section in sections.split('.').filter(|section| !section.is_empty()) {
+        table = table.get_mut(section)?.as_table_mut()?;
+    }
+    match value {
+        Some(value) => table.insert(field.to_string(), value),
+        None => table.remove(field),
+    };
+    Some(root.try_into::<Config>().map_err(anyhow::Error::from))
 }
 
 impl Config {
-    /// Load configuration from XDG config directory
+    /// Load the configuration from the XDG config directory, writing a default config file
+    /// first if there isn't one
     pub fn load() -> Result<Self> {
-        let config_path = Self::config_path()?;
+        Self::load_from(&Self::config_path()?, true)
+    }
 
+    /// Load the configuration, using the defaults in memory without writing anything
+    /// when there's no config file, e.g. in read-only or throwaway environments
+    pub fn load_or_default() -> Result<Self> {
+        Self::load_from(&Self::config_path()?, false)
+    }
+
+    fn load_from(config_path: &Path, create: bool) -> Result<Self> {
         if !config_path.exists() {
-            info!(
-                "Configuration file not found, creating default: {:?}",
-                config_path
-            );
-            let config = Self::default();
-            config.save()?;
+            let mut config = Self::default();
+            if create {
+                info!(
+                    "Configuration file not found, creating default: {:?}",
+                    config_path
+                );
+                config.save_to(config_path).with_context(|| {
+                    format!(
+                        "No config file at {config_path:?} and a default couldn't be created there, \
+                         create it or point {CONFIG_PATH_ENV} at a writable path"
+                    )
+                })?;
+            } else {
+                debug!("No config file at {:?}, using defaults", config_path);
+            }
+            config.apply_env_overrides();
             return Ok(config);
         }
 
-        let content = std::fs::read_to_string(&config_path)
+        let content = std::fs::read_to_string(config_path)
             .with_context(|| format!("Failed to read config file: {config_path:?}"))?;
 
         let mut config: Self =
@@ -192,8 +678,10 @@ impl Config {
 
     /// Save configuration to XDG config directory
     pub fn save(&self) -> Result<()> {
-        let config_path = Self::config_path()?;
+        self.save_to(&Self::config_path()?)
+    }
 
+    fn save_to(&self, config_path: &Path) -> Result<()> {
         if let Some(parent) = config_path.parent() {
             std::fs::create_dir_all(parent)
                 .with_context(|| format!("Failed to create config directory: {parent:?}"))?;
@@ -201,33 +689,401 @@ impl Config {
 
         let content = toml::to_string(self).with_context(|| "Failed to serialize configuration")?;
 
-        std::fs::write(&config_path, content)
+        std::fs::write(config_path, content)
             .with_context(|| format!("Failed to write config file: {config_path:?}"))?;
 
         debug!("Configuration saved to: {:?}", config_path);
         Ok(())
     }
 
-    /// Get the configuration file path using XDG config directory
+    /// Read a setting by dotted key, e.g. `audio.silence_threshold`, formatted as in the
+    /// config file. Unset optional settings and unknown keys return `None`.
+    pub fn get_nested(&self, key: &str) -> Option<String> {
+        let root = toml::Value::try_from(self).ok()?;
+        let value = key
+            .split('.')
+            .try_fold(&root, |value, part| value.get(part))?;
+        Some(match value {
+            toml::Value::String(text) => text.clone(),
+            other => other.to_string(),
+        })
+    }
+
+    /// Change a setting by dotted key. The value is parsed as TOML, falling back to a plain
+    /// string, and must fit the setting's type. An empty value resets it to its default.
+    /// Nothing changes on error; the caller saves.
+    pub fn set_nested(&mut self, key: &str, value: &str) -> Result<()> {
+        let value = value.trim();
+        let root = toml::Value::try_from(&*self).context("Failed to serialize configuration")?;
+        let unknown = || anyhow::anyhow!("Unknown setting: {key}");
+        if value.is_empty() {
+            let config = with_nested(&root, key, None).ok_or_else(unknown)??;
+            self.replace_settings(config);
+            return Ok(());
+        }
+
+        let text = toml::Value::String(value.to_string());
+        let parsed = toml::from_str::<toml::Table>(&format!("value = {value}"))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .filter(|parsed| parsed != &text);
+        let mut first_error = None;
+        for candidate in parsed.into_iter().chain([text]) {
+            match with_nested(&root, key, Some(candidate)).ok_or_else(unknown)? {
+                Ok(config) if config.get_nested(key).is_some() => {
+                    self.replace_settings(config);
+                    return Ok(());
+                }
+                Ok(_) => return Err(unknown()), // Not a field, so it was dropped
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        Err(first_error
+            .unwrap_or_else(unknown)
+            .context(format!("Invalid value for {key}: {value}")))
+    }
+
+    /// Whether `other` holds the same settings, to tell an edit of the config file from
+    /// one of our own saves
+    pub fn same_settings(&self, other: &Config) -> bool {
+        toml::Value::try_from(self).ok() == toml::Value::try_from(other).ok()
+    }
+
+    /// Take edited settings, keeping values that never come from the config file
+    fn replace_settings(&mut self, mut config: Config) {
+        config.audio.input_file = self.audio.input_file.take();
+        *self = config;
+    }
+
+    /// Set the silence threshold and persist it to the config file
+    pub fn update_silence_threshold(&mut self, threshold: f32) -> Result<()> {
+        self.audio.silence_threshold = threshold;
+        self.save()?;
+        info!("Silence threshold set to {:.1}", threshold);
+        Ok(())
+    }
+
+    /// Set the input gain and persist it to the config file
+    pub fn update_gain(&mut self, gain: f32) -> Result<()> {
+        self.audio.gain = gain;
+        self.save()?;
+        info!("Input gain set to {:.1}x", gain);
+        Ok(())
+    }
+
+    /// Get the configuration file path: `SIMPLE_STT_CONFIG` when set, otherwise the
+    /// file in the XDG config directory
     pub fn config_path() -> Result<PathBuf> {
+        if let Some(path) = std::env::var_os(CONFIG_PATH_ENV).filter(|path| !path.is_empty()) {
+            return Ok(PathBuf::from(path));
+        }
+
         let config_dir = config_dir().context("Could not determine config directory")?;
 
         Ok(config_dir.join(APP_NAME).join(CONFIG_FILE))
     }
 
+    /// Platform cache directory for the app: `~/.cache/simple-stt` on Linux,
+    /// `~/Library/Caches/simple-stt` on macOS and `%LOCALAPPDATA%\simple-stt` on Windows
+    pub fn cache_dir() -> PathBuf {
+        cache_dir()
+            .or_else(|| home_dir().map(|h| h.join(".cache")))
+            .unwrap_or_else(std::env::temp_dir)
+            .join(APP_NAME)
+    }
+
+    /// Default directory for downloaded Whisper models
+    pub fn models_dir() -> PathBuf {
+        Self::cache_dir().join("models")
+    }
+
+    /// Default log directory: the cache directory on Linux, `~/Library/Logs/simple-stt`
+    /// on macOS and a `logs` folder next to the models on Windows
+    pub fn default_log_dir() -> PathBuf {
+        if cfg!(target_os = "macos") {
+            if let Some(home) = home_dir() {
+                return home.join("Library").join("Logs").join(APP_NAME);
+            }
+        } else if cfg!(windows) {
+            return Self::cache_dir().join("logs");
+        }
+        Self::cache_dir()
+    }
+
     /// Apply environment variable overrides
     fn apply_env_overrides(&mut self) {
-        if let Ok(api_key) = std::env::var("OPENAI_API_KEY") {
-            self.whisper.api_key = Some(api_key.clone());
-            self.llm.api_key = Some(api_key);
-            debug!("Using OPENAI_API_KEY from environment");
+        self.apply_env_overrides_from(|name| std::env::var(name).ok());
+    }
+
+    /// Take the STT key from `OPENAI_API_KEY` and the LLM key from the variable for
+    /// `llm.provider`, so one provider's key never ends up used for another
+    fn apply_env_overrides_from(&mut self, env: impl Fn(&str) -> Option<String>) {
+        if let Some(api_key) = env("OPENAI_API_KEY") {
+            self.whisper.api_key = Some(api_key);
+            debug!("Using OPENAI_API_KEY from environment for transcription");
         }
 
-        if let Ok(api_key) = std::env::var("ANTHROPIC_API_KEY") {
-            if self.llm.provider == "anthropic" {
+        if let Some(var) = llm_api_key_env(&self.llm.provider) {
+            if let Some(api_key) = env(var) {
                 self.llm.api_key = Some(api_key);
-                debug!("Using ANTHROPIC_API_KEY from environment");
+                debug!("Using {} from environment for the LLM", var);
             }
         }
     }
 }
+
+/// Environment variable holding the API key for an LLM provider
+pub fn llm_api_key_env(provider: &str) -> Option<&'static str> {
+    match provider {
+        "openai" => Some("OPENAI_API_KEY"),
+        "anthropic" => Some("ANTHROPIC_API_KEY"),
+        "gemini" => Some("GEMINI_API_KEY"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env_with_all_keys(name: &str) -> Option<String> {
+        match name {
+            "OPENAI_API_KEY" => Some("openai-key".to_string()),
+            "ANTHROPIC_API_KEY" => Some("anthropic-key".to_string()),
+            "GEMINI_API_KEY" => Some("gemini-key".to_string()),
+            _ => None,
+        }
+    }
+
+    fn llm_key_for(provider: &str, env: impl Fn(&str) -> Option<String>) -> Config {
+        let mut config = Config::default();
+        config.llm.provider = provider.to_string();
+        config.apply_env_overrides_from(env);
+        config
+    }
+
+    #[test]
+    fn test_env_keys_openai() {
+        let config = llm_key_for("openai", env_with_all_keys);
+        assert_eq!(config.whisper.api_key.as_deref(), Some("openai-key"));
+        assert_eq!(config.llm.api_key.as_deref(), Some("openai-key"));
+    }
+
+    #[test]
+    fn test_env_keys_anthropic() {
+        let config = llm_key_for("anthropic", env_with_all_keys);
+        assert_eq!(config.whisper.api_key.as_deref(), Some("openai-key"));
+        assert_eq!(config.llm.api_key.as_deref(), Some("anthropic-key"));
+
+        // An OpenAI key alone is never used for Anthropic
+        let config = llm_key_for("anthropic", |name| {
+            (name == "OPENAI_API_KEY").then(|| "openai-key".to_string())
+        });
+        assert_eq!(config.whisper.api_key.as_deref(), Some("openai-key"));
+        assert_eq!(config.llm.api_key, None);
+    }
+
+    #[test]
+    fn test_env_keys_gemini() {
+        let config = llm_key_for("gemini", env_with_all_keys);
+        assert_eq!(config.whisper.api_key.as_deref(), Some("openai-key"));
+        assert_eq!(config.llm.api_key.as_deref(), Some("gemini-key"));
+    }
+
+    #[test]
+    fn test_env_keys_keep_config_file_keys() {
+        let mut config = Config::default();
+        config.llm.provider = "unknown".to_string();
+        config.llm.api_key = Some("file-key".to_string());
+        config.apply_env_overrides_from(env_with_all_keys);
+        assert_eq!(config.llm.api_key.as_deref(), Some("file-key"));
+    }
+
+    #[test]
+    fn test_config_path_env_override() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("profiles").join("work.toml");
+        std::env::set_var(CONFIG_PATH_ENV, &path);
+
+        assert_eq!(Config::config_path().unwrap(), path);
+        let mut config = Config::default();
+        config.whisper.model = "small.en".to_string();
+        config.save().unwrap();
+        let loaded = Config::load().unwrap();
+
+        std::env::remove_var(CONFIG_PATH_ENV);
+        assert!(path.exists());
+        assert_eq!(loaded.whisper.model, "small.en");
+        assert_ne!(Config::config_path().unwrap(), path);
+    }
+
+    #[test]
+    fn test_sampling_is_clamped() {
+        let mut whisper = WhisperConfig::default();
+        assert_eq!(whisper.sampling(), Sampling::Greedy { best_of: 1 });
+        whisper.best_of = 0;
+        assert_eq!(whisper.sampling(), Sampling::Greedy { best_of: 1 });
+
+        whisper.sampling = SamplingMode::Beam;
+        whisper.beam_size = 50;
+        whisper.beam_patience = 10.0;
+        let sampling = whisper.sampling();
+        assert_eq!(
+            sampling,
+            Sampling::BeamSearch {
+                beam_size: 8,
+                patience: 4.0
+            }
+        );
+        assert_eq!(sampling.to_string(), "beam 8, patience 4.0");
+        assert!(toml::from_str::<WhisperConfig>("sampling = \"beam_search\"").is_err());
+        assert!(toml::from_str::<WhisperConfig>("upload_format = \"mp3\"").is_err());
+    }
+
+    #[test]
+    fn test_model_list_includes_current_model() {
+        let mut whisper = WhisperConfig::default();
+        assert_eq!(whisper.model_list(), whisper.models);
+
+        whisper.models = vec!["base.en".to_string(), "large-v3".to_string()];
+        whisper.model = "distil-large-v3".to_string();
+        assert_eq!(
+            whisper.model_list(),
+            vec!["base.en", "large-v3", "distil-large-v3"]
+        );
+    }
+
+    #[test]
+    fn test_thread_count() {
+        let mut whisper = WhisperConfig::default();
+        assert!(whisper.thread_count() >= 1);
+        whisper.n_threads = Some(6);
+        assert_eq!(whisper.thread_count(), 6);
+        whisper.n_threads = Some(0);
+        assert_eq!(whisper.thread_count(), 1);
+    }
+
+    #[test]
+    fn test_get_and_set_nested() {
+        let mut config = Config::default();
+        assert_eq!(
+            config.get_nested("audio.silence_threshold").as_deref(),
+            Some("15.0")
+        );
+        assert_eq!(config.get_nested("whisper.n_threads"), None);
+
+        config.set_nested("audio.silence_threshold", "20").unwrap();
+        assert_eq!(config.audio.silence_threshold, 20.0);
+        config.set_nested("clipboard.auto_paste", "true").unwrap();
+        assert!(config.clipboard.auto_paste);
+        config.set_nested("whisper.language", "de").unwrap();
+        assert_eq!(config.whisper.language.as_deref(), Some("de"));
+        config.set_nested("whisper.n_threads", "4").unwrap();
+        assert_eq!(config.whisper.n_threads, Some(4));
+        config.set_nested("whisper.language", "").unwrap();
+        assert_eq!(config.whisper.language.as_deref(), Some("en"));
+
+        assert!(config
+            .set_nested("audio.silence_threshold", "loud")
+            .is_err());
+        assert!(config.set_nested("audio.no_such_setting", "1").is_err());
+        assert!(config.set_nested("nowhere.silence_threshold", "1").is_err());
+        assert_eq!(config.audio.silence_threshold, 20.0);
+    }
+
+    #[test]
+    fn test_same_settings() {
+        let config = Config::default();
+        let mut edited = Config::default();
+        assert!(config.same_settings(&edited));
+        edited.whisper.model = "small.en".to_string();
+        assert!(!config.same_settings(&edited));
+    }
+
+    #[test]
+    fn test_clipboard_wrap() {
+        let clipboard: ClipboardConfig = toml::from_str("wrap = \"codeblock\"").unwrap();
+        assert_eq!(clipboard.wrap, ClipboardWrap::Codeblock);
+        assert!(toml::from_str::<ClipboardConfig>("wrap = \"fence\"").is_err());
+        assert!(toml::from_str::<ClipboardConfig>("target = \"selection\"").is_err());
+    }
+
+    #[test]
+    fn test_waveform_style() {
+        let ui: UiConfig = toml::from_str("waveform_style = \"envelope\"").unwrap();
+        assert_eq!(ui.waveform_style, WaveformStyle::Envelope);
+        assert!(toml::from_str::<UiConfig>("waveform_style = \"dots\"").is_err());
+        assert!(toml::from_str::<UiConfig>("record_mode = \"push\"").is_err());
+    }
+
+    #[test]
+    fn test_idle_action() {
+        let ui: UiConfig = toml::from_str("idle_action = \"unload\"").unwrap();
+        assert_eq!(ui.idle_action, IdleAction::Unload);
+        assert!(toml::from_str::<UiConfig>("idle_action = \"sleep\"").is_err());
+
+        let mut config = Config::default();
+        config.set_nested("ui.idle_action", "unload").unwrap();
+        assert_eq!(config.ui.idle_action, IdleAction::Unload);
+        assert_eq!(
+            config.get_nested("ui.idle_action").as_deref(),
+            Some("unload")
+        );
+        assert!(config.set_nested("ui.idle_action", "sleep").is_err());
+    }
+
+    #[test]
+    fn test_old_timeout_key_sets_request_timeout() {
+        let whisper: WhisperConfig = toml::from_str("timeout = 90").unwrap();
+        assert_eq!(whisper.request_timeout, 90);
+        assert_eq!(whisper.connect_timeout, 10);
+    }
+
+    #[test]
+    fn test_load_or_default_does_not_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        let config = Config::load_from(&path, false).unwrap();
+        assert!(!path.exists());
+        assert_eq!(config.whisper.model, Config::default().whisper.model);
+
+        Config::load_from(&path, true).unwrap();
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_auto_profile() {
+        let mut llm = LlmConfig {
+            auto_profile_rules: vec![
+                AutoProfileRule {
+                    keyword: "email".to_string(),
+                    profile: "email".to_string(),
+                },
+                AutoProfileRule {
+                    keyword: "todo".to_string(),
+                    profile: "todo".to_string(),
+                },
+            ],
+            ..LlmConfig::default()
+        };
+        assert_eq!(
+            llm.auto_profile("Email, tell Sam I'm late."),
+            Some(("email", "tell Sam I'm late."))
+        );
+        assert_eq!(
+            llm.auto_profile(" todo buy milk"),
+            Some(("todo", "buy milk"))
+        );
+        assert_eq!(llm.auto_profile("Emails are piling up"), None);
+        assert_eq!(llm.auto_profile("Send an email"), None);
+
+        llm.strip_trigger = false;
+        assert_eq!(
+            llm.auto_profile("todo buy milk"),
+            Some(("todo", "todo buy milk"))
+        );
+    }
+}
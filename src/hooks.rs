@@ -0,0 +1,61 @@
+use std::process::Command;
+use tracing::{debug, warn};
+
+use crate::config::HooksConfig;
+
+/// Runs the user's recording state hooks, e.g. to drive a recording LED or status bar
+pub struct Hooks {
+    config: HooksConfig,
+}
+
+impl Hooks {
+    pub fn new(config: &HooksConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    /// Recording started, `state` is the new app state
+    pub fn record_start(&self, state: &str) {
+        if let Some(command) = &self.config.on_record_start {
+            spawn(command, state);
+        }
+    }
+
+    /// Recording stopped, `state` is the new app state
+    pub fn record_stop(&self, state: &str) {
+        if let Some(command) = &self.config.on_record_stop {
+            spawn(command, state);
+        }
+    }
+}
+
+/// The hook runs through `sh -c` with the state appended as its last argument
+fn hook_command(command: &str, state: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", &format!("{command} \"$1\""), "sh", state]);
+    cmd
+}
+
+/// Run a hook in the background, failures are only logged
+fn spawn(command: &str, state: &str) {
+    debug!("Running hook for {}: {}", state, command);
+    let mut cmd = hook_command(command, state);
+    let command = command.to_string();
+    std::thread::spawn(move || match cmd.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("Hook `{}` exited with {}", command, status),
+        Err(e) => warn!("Failed to run hook `{}`: {}", command, e),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook_receives_state() {
+        let output = hook_command("echo", "recording").output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "recording\n");
+    }
+}
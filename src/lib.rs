@@ -1,7 +1,14 @@
 pub mod audio;
 pub mod clipboard;
 pub mod config;
+pub mod hooks;
+pub mod llm;
+pub mod output;
+pub mod redact;
+pub mod stats;
 pub mod stt;
+pub mod text;
 pub mod tui;
+pub mod watch;
 
 pub use config::Config;
@@ -4,8 +4,14 @@ use serde_json::{json, Value};
 use std::time::Duration;
 use tracing::{debug, info, warn};
 
-use simple_stt_rs::config::{Config, LlmConfig, LlmProfile};
+use crate::config::{Config, LlmConfig, LlmProfile};
+use crate::redact;
+use crate::stt::api_error_message;
 
+/// Temperature OpenAI refinements use when neither the profile nor `llm` sets one
+const OPENAI_TEMPERATURE: f32 = 0.3;
+
+#[derive(Clone)]
 pub struct LlmRefiner {
     config: LlmConfig,
     client: reqwest::Client,
@@ -14,7 +20,8 @@ pub struct LlmRefiner {
 impl LlmRefiner {
     pub fn new(config: &Config) -> Result<Self> {
         let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(30))
+            .connect_timeout(Duration::from_secs(config.llm.connect_timeout))
+            .timeout(Duration::from_secs(config.llm.request_timeout))
             .build()
             .context("Failed to create HTTP client")?;
 
@@ -58,6 +65,43 @@ impl LlmRefiner {
         }
     }
 
+    /// Refine text with several profiles concurrently, one task per profile, returning
+    /// `(profile, result)` pairs in the order the profiles were given
+    pub async fn refine_with_profiles(
+        &self,
+        text: &str,
+        profiles: &[String],
+    ) -> Vec<(String, Result<Option<String>>)> {
+        let tasks: Vec<_> = profiles
+            .iter()
+            .map(|profile| {
+                let refiner = self.clone();
+                let text = text.to_string();
+                let profile = profile.clone();
+                tokio::spawn(async move { refiner.refine_text(&text, Some(&profile)).await })
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(tasks.len());
+        for (profile, task) in profiles.iter().zip(tasks) {
+            let result = task
+                .await
+                .unwrap_or_else(|e| Err(anyhow::anyhow!("Refinement task failed: {e}")));
+            results.push((profile.clone(), result));
+        }
+        results
+    }
+
+    /// Profiles to compare side by side: `llm.compare_profiles`, or every profile by name
+    pub fn compare_profiles(&self) -> Vec<String> {
+        if !self.config.compare_profiles.is_empty() {
+            return self.config.compare_profiles.clone();
+        }
+        let mut profiles: Vec<String> = self.config.profiles.keys().cloned().collect();
+        profiles.sort();
+        profiles
+    }
+
     /// Refine text using OpenAI API
     async fn refine_with_openai(&self, text: &str, profile: &LlmProfile) -> Result<Option<String>> {
         let api_key = self
@@ -66,20 +110,17 @@ impl LlmRefiner {
             .as_ref()
             .context("OpenAI API key not configured")?;
 
+        let (system, user) = prompt_layout(profile, text);
+        let mut messages = Vec::new();
+        if let Some(system) = system {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        messages.push(json!({ "role": "user", "content": user }));
         let payload = json!({
-            "model": self.config.model,
-            "messages": [
-                {
-                    "role": "system",
-                    "content": profile.prompt
-                },
-                {
-                    "role": "user",
-                    "content": text
-                }
-            ],
+            "model": self.config.model_for(profile),
+            "messages": messages,
             "max_tokens": self.config.max_tokens,
-            "temperature": 0.3
+            "temperature": self.config.temperature_for(profile).unwrap_or(OPENAI_TEMPERATURE)
         });
 
         let mut headers = HeaderMap::new();
@@ -101,11 +142,12 @@ impl LlmRefiner {
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
-            return Err(anyhow::anyhow!(
-                "OpenAI API request failed with status {}: {}",
+            return Err(anyhow::anyhow!(api_error_message(
+                "OpenAI",
+                "OPENAI_API_KEY or llm.api_key",
                 status,
-                error_text
-            ));
+                &error_text
+            )));
         }
 
         let result: Value = response
@@ -126,7 +168,10 @@ impl LlmRefiner {
             warn!("OpenAI returned empty response");
             Ok(None)
         } else {
-            info!("✅ Text refined successfully: \"{}\"", refined_text);
+            info!(
+                "✅ Text refined successfully: {}",
+                redact::transcript(&refined_text)
+            );
             Ok(Some(refined_text))
         }
     }
@@ -143,16 +188,24 @@ impl LlmRefiner {
             .as_ref()
             .context("Anthropic API key not configured")?;
 
-        let payload = json!({
-            "model": self.config.model,
+        // Anthropic takes the system prompt as a top-level field rather than a message
+        let (system, user) = prompt_layout(profile, text);
+        let mut payload = json!({
+            "model": self.config.model_for(profile),
             "max_tokens": self.config.max_tokens,
             "messages": [
                 {
                     "role": "user",
-                    "content": format!("{}\n\nText to process: {}", profile.prompt, text)
+                    "content": user
                 }
             ]
         });
+        if let Some(system) = system {
+            payload["system"] = json!(system);
+        }
+        if let Some(temperature) = self.config.temperature_for(profile) {
+            payload["temperature"] = json!(temperature);
+        }
 
         let mut headers = HeaderMap::new();
         headers.insert("x-api-key", HeaderValue::from_str(api_key)?);
@@ -171,11 +224,12 @@ impl LlmRefiner {
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
-            return Err(anyhow::anyhow!(
-                "Anthropic API request failed with status {}: {}",
+            return Err(anyhow::anyhow!(api_error_message(
+                "Anthropic",
+                "ANTHROPIC_API_KEY or llm.api_key",
                 status,
-                error_text
-            ));
+                &error_text
+            )));
         }
 
         let result: Value = response
@@ -195,7 +249,10 @@ impl LlmRefiner {
             warn!("Anthropic returned empty response");
             Ok(None)
         } else {
-            info!("✅ Text refined successfully: \"{}\"", refined_text);
+            info!(
+                "✅ Text refined successfully: {}",
+                redact::transcript(&refined_text)
+            );
             Ok(Some(refined_text))
         }
     }
@@ -224,6 +281,19 @@ impl LlmRefiner {
     }
 }
 
+/// Split a profile's prompt and the text into an optional system prompt and the user
+/// message, the same for every provider
+fn prompt_layout(profile: &LlmProfile, text: &str) -> (Option<String>, String) {
+    if profile.inline_prompt {
+        (
+            None,
+            format!("{}\n\nText to process: {}", profile.prompt, text),
+        )
+    } else {
+        (Some(profile.prompt.clone()), text.to_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -249,6 +319,73 @@ mod tests {
         assert_eq!(refiner.model(), "gpt-3.5-turbo");
     }
 
+    #[test]
+    fn test_profile_overrides() {
+        let config = Config::default();
+        let mut profile = config.llm.profiles["general"].clone();
+        assert_eq!(config.llm.model_for(&profile), "gpt-3.5-turbo");
+        assert_eq!(config.llm.temperature_for(&profile), None);
+
+        profile.model = Some("gpt-4o".to_string());
+        profile.temperature = Some(0.9);
+        assert_eq!(config.llm.model_for(&profile), "gpt-4o");
+        assert_eq!(config.llm.temperature_for(&profile), Some(0.9));
+
+        let config = Config {
+            llm: LlmConfig {
+                temperature: Some(0.5),
+                ..LlmConfig::default()
+            },
+            ..Config::default()
+        };
+        assert_eq!(config.llm.temperature_for(&profile), Some(0.9));
+        profile.temperature = None;
+        assert_eq!(config.llm.temperature_for(&profile), Some(0.5));
+    }
+
+    #[test]
+    fn test_prompt_layout() {
+        let config = Config::default();
+        let mut profile = config.llm.profiles["general"].clone();
+        let (system, user) = prompt_layout(&profile, "hello");
+        assert_eq!(system.as_deref(), Some(profile.prompt.as_str()));
+        assert_eq!(user, "hello");
+
+        profile.inline_prompt = true;
+        let (system, user) = prompt_layout(&profile, "hello");
+        assert_eq!(system, None);
+        assert!(user.starts_with(&profile.prompt));
+        assert!(user.ends_with("Text to process: hello"));
+    }
+
+    #[test]
+    fn test_compare_profiles() {
+        let mut config = Config::default();
+        let refiner = LlmRefiner::new(&config).unwrap();
+        assert_eq!(
+            refiner.compare_profiles(),
+            vec!["email", "general", "slack", "todo"]
+        );
+
+        config.llm.compare_profiles = vec!["general".to_string(), "email".to_string()];
+        let refiner = LlmRefiner::new(&config).unwrap();
+        assert_eq!(refiner.compare_profiles(), vec!["general", "email"]);
+    }
+
+    #[tokio::test]
+    async fn test_refine_with_profiles_keeps_order() {
+        // Without an API key every profile returns the original text
+        let refiner = LlmRefiner::new(&Config::default()).unwrap();
+        let profiles = vec!["todo".to_string(), "general".to_string()];
+        let results = refiner.refine_with_profiles("hello", &profiles).await;
+
+        let names: Vec<&str> = results.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["todo", "general"]);
+        for (_, result) in results {
+            assert_eq!(result.unwrap().as_deref(), Some("hello"));
+        }
+    }
+
     #[test]
     fn test_is_configured() {
         let mut config = Config::default();
@@ -1,34 +1,193 @@
 use anyhow::{Context, Result};
-use cpal::traits::{DeviceTrait, HostTrait};
+use clap::{Parser, Subcommand, ValueEnum};
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
+    event::{KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, size as terminal_size, supports_keyboard_enhancement,
+        EnterAlternateScreen, LeaveAlternateScreen,
+    },
 };
-use dirs::cache_dir;
-use ratatui::{prelude::*, Terminal};
+use notify::{RecursiveMode, Watcher};
+use ratatui::{prelude::*, Terminal, TerminalOptions, Viewport};
 use simple_stt_rs::{
-    audio::{AudioData, AudioRecorder},
+    audio::{
+        apply_gain, calculate_rms, highpass, input_device_name, is_monitor_device,
+        list_input_devices, speech_duration, trim_silence, AudioData, AudioSource, LevelMeter,
+        PrerollBuffer,
+    },
     clipboard::ClipboardManager,
-    config::Config,
-    stt::{wav_utils, SttProcessor},
+    config::{AudioConfig, Config, IdleAction, LogConfig, RecordMode},
+    hooks::Hooks,
+    llm::LlmRefiner,
+    output::TranscriptFile,
+    redact,
+    stats::Stats,
+    stt::{
+        cached_models, format_size, remove_cached_model, remove_partial_download,
+        subtitles::{to_srt, to_vtt},
+        tag_language, wav_utils,
+        wer::word_error_rate,
+        SttProcessor, Transcription,
+    },
+    text::TextProcessor,
     tui::{
-        app::{App, AppState},
-        events::handle_key_events,
+        app::{App, AppState, Refinement, TranscriptionResult, VoiceActivity},
+        events::{handle_key_events, media_key},
         ui::draw,
     },
+    watch,
 };
+use std::collections::HashMap;
 use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{mpsc, Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tempfile::NamedTempFile;
 use tokio::sync::mpsc as tokio_mpsc;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
+/// A finished recording waiting to be transcribed
+struct TranscriptionJob {
+    id: u64,
+    samples: Vec<f32>, // Written to a WAV file by the worker, off the render loop
+    audio: AudioConfig,
+    cancelled: Arc<AtomicBool>,
+    fresh_context: bool, // Don't carry over context, e.g. when re-transcribing
+}
+
+impl TranscriptionJob {
+    /// Create a job along with the flag that cancels it
+    fn new(id: u64, samples: Vec<f32>, audio: &AudioConfig) -> (Self, Arc<AtomicBool>) {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let job = Self {
+            id,
+            samples,
+            audio: audio.clone(),
+            cancelled: cancelled.clone(),
+            fresh_context: false,
+        };
+        (job, cancelled)
+    }
+
+    /// Encode the samples as a temporary WAV file, which is deleted when dropped
+    async fn write_wav(&mut self) -> Result<NamedTempFile> {
+        let mut samples = std::mem::take(&mut self.samples);
+        let audio = self.audio.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Some(cutoff_hz) = audio.highpass_hz.filter(|hz| *hz > 0.0) {
+                highpass(&mut samples, cutoff_hz, &audio);
+            }
+            let samples = if audio.trim_silence {
+                let trimmed = trim_silence(&samples, &audio);
+                tracing::debug!(
+                    "Trimmed silence: {} of {} samples kept",
+                    trimmed.len(),
+                    samples.len()
+                );
+                trimmed
+            } else {
+                &samples
+            };
+            wav_utils::save_wav(
+                samples,
+                audio.sample_rate,
+                audio.channels,
+                audio.min_duration_ms,
+                audio.output_bits,
+            )
+        })
+        .await
+        .context("WAV writer task failed")?
+    }
+}
+
+/// Resolve once the cancel flag is set
+async fn wait_for_cancel(cancelled: &AtomicBool) {
+    while !cancelled.load(Ordering::Relaxed) {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+/// Height of the TUI when drawn inline with `ui.alt_screen = false`
+const INLINE_HEIGHT: u16 = 30;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Play a WAV file through the recording pipeline instead of using the microphone
+    #[arg(long, value_name = "WAV")]
+    input_file: Option<PathBuf>,
+
+    /// Draw the TUI inline instead of on the alternate screen, printing the final
+    /// transcription so it stays in the scrollback (same as ui.alt_screen = false)
+    #[arg(long)]
+    no_alt_screen: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Transcribe a WAV file and report the word error rate against a reference transcript
+    Bench {
+        /// WAV file to transcribe
+        wav: PathBuf,
+        /// Text file containing the reference transcript
+        reference: PathBuf,
+    },
+    /// Transcribe an audio file and print or save the result
+    Transcribe {
+        /// Audio file to transcribe
+        file: PathBuf,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Txt)]
+        format: OutputFormat,
+        /// Write the output to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Watch a directory and transcribe new audio files into .txt files next to them
+    Watch {
+        /// Directory to watch
+        dir: PathBuf,
+    },
+    /// List input devices for audio.device, including monitor sources for system audio
+    Devices,
+    /// List downloaded models with their sizes, or delete some to free up space
+    Models {
+        #[command(subcommand)]
+        action: Option<ModelsAction>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ModelsAction {
+    /// Delete downloaded models by name, e.g. `models prune large tiny.en`
+    Prune {
+        /// Models to delete, as in whisper.model
+        names: Vec<String>,
+        /// Delete every downloaded model except the configured whisper.model
+        #[arg(long, conflicts_with = "names")]
+        all_except_current: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Txt,
+    Srt,
+    Vtt,
+    Json,
+}
+
 async fn load_stt_processor(
     config: &Config,
     app: &Arc<Mutex<App>>,
     log_tx: &tokio_mpsc::Sender<String>,
-) -> Result<Arc<tokio::sync::Mutex<SttProcessor>>> {
+) -> Result<SttProcessor> {
     {
         let mut app = app.lock().unwrap();
         app.model_status = format!("Loading {}...", config.whisper.model);
@@ -50,11 +209,34 @@ async fn load_stt_processor(
     };
 
     match stt_processor.prepare().await {
+        Ok(_) if stt_processor.fallback_reason().is_some() => {
+            let reason = stt_processor.fallback_reason().unwrap_or_default();
+            {
+                let mut app = app.lock().unwrap();
+                app.model_status = "⚠️ Using OpenAI API (local model failed)".to_string();
+                app.ms_per_audio_sec = None;
+                app.state = AppState::Idle;
+                app.loaded_whisper = Some(config.whisper.clone());
+            }
+            log_tx
+                .send(format!(
+                    "⚠️ Local model {} failed ({reason}), falling back to the OpenAI API",
+                    config.whisper.model
+                ))
+                .await
+                .ok();
+        }
         Ok(_) => {
+            let speed = stt_processor.ms_per_audio_sec();
             {
                 let mut app = app.lock().unwrap();
-                app.model_status = "✅ Model Ready".to_string();
+                app.ms_per_audio_sec = speed;
+                app.model_status = match speed {
+                    Some(ms) => format!("✅ Model Ready (~{ms:.0} ms/s of audio)"),
+                    None => "✅ Model Ready".to_string(),
+                };
                 app.state = AppState::Idle;
+                app.loaded_whisper = Some(config.whisper.clone());
             }
             log_tx
                 .send(format!(
@@ -63,6 +245,15 @@ async fn load_stt_processor(
                 ))
                 .await
                 .ok();
+            if let Some(ms) = speed {
+                log_tx
+                    .send(format!(
+                        "⏱️ {} takes ~{ms:.0} ms per second of audio on this machine",
+                        config.whisper.model
+                    ))
+                    .await
+                    .ok();
+            }
         }
         Err(e) => {
             let error_msg = format!("❌ Error loading model: {e}");
@@ -76,24 +267,100 @@ async fn load_stt_processor(
         }
     }
 
-    Ok(Arc::new(tokio::sync::Mutex::new(stt_processor)))
+    Ok(stt_processor)
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    setup_logging()?;
-    let config = Config::load()?;
-    let device_name = cpal::default_host()
-        .default_input_device()
-        .and_then(|d| d.name().ok())
-        .unwrap_or_else(|| "Unknown Device".to_string());
+    let cli = Cli::parse();
+    // Logging is configured by the config file, so hold on to what loading it logs
+    let early_logs = EarlyLogs::default();
+    // Only the TUI creates a default config file, one-off subcommands leave the disk alone
+    let load: fn() -> Result<Config> = match cli.command {
+        Some(_) => Config::load_or_default,
+        None => Config::load,
+    };
+    let mut config = tracing::subscriber::with_default(
+        tracing_subscriber::registry().with(early_logs.clone()),
+        load,
+    )?;
+    setup_logging(&config.log)?;
+    early_logs.replay();
+    redact::set_log_transcripts(config.log.transcripts);
+    config.audio.input_file = cli.input_file;
+
+    if let Some(command) = cli.command {
+        return match command {
+            Command::Bench { wav, reference } => run_bench(&config, &wav, &reference).await,
+            Command::Transcribe {
+                file,
+                format,
+                output,
+            } => run_transcribe(&config, &file, format, output.as_deref()).await,
+            Command::Watch { dir } => watch::run(&config, &dir).await,
+            Command::Devices => list_devices(),
+            Command::Models { action } => match action {
+                None => list_models(&config),
+                Some(ModelsAction::Prune {
+                    names,
+                    all_except_current,
+                }) => prune_models(&config, &names, all_except_current),
+            },
+        };
+    }
+
+    // Recordings still in the temp directory after this long were left by a crash
+    const ORPHANED_WAV_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+    match wav_utils::cleanup_orphaned_wavs(ORPHANED_WAV_MAX_AGE) {
+        Ok(0) => {}
+        Ok(removed) => tracing::info!("Removed {} orphaned temp recordings", removed),
+        Err(e) => tracing::warn!("Failed to clean up orphaned temp recordings: {}", e),
+    }
+
+    let text_processor = Arc::new(Mutex::new(TextProcessor::new(&config)?));
+    let device_name = input_device_name(&config.audio);
+    if device_name.is_none() {
+        tracing::warn!("No audio input device found at startup");
+    }
     let app = Arc::new(Mutex::new(App::new(config.clone(), device_name)));
-    let mut terminal = setup_terminal()?;
+    let hold_to_record = config.ui.record_mode == RecordMode::Hold;
+    let record_media_key = config.keybindings.record_media_key.as_deref();
+    let alt_screen = config.ui.alt_screen && !cli.no_alt_screen;
+    // Media keys are only reported with the same keyboard enhancements as key releases
+    let (mut terminal, key_release_events) =
+        setup_terminal(hold_to_record || record_media_key.is_some(), alt_screen)?;
+    {
+        let mut app = app.lock().unwrap();
+        app.key_release_events = key_release_events;
+        if hold_to_record && !key_release_events {
+            app.add_log_message(
+                "⚠️ Terminal doesn't report key releases, Space toggles recording instead of hold"
+                    .to_string(),
+            );
+        }
+        match record_media_key {
+            Some(name) if media_key(name).is_none() => app.add_log_message(format!(
+                "⚠️ Unknown keybindings.record_media_key \"{}\", expected play_pause, play, pause, stop, next or previous",
+                name
+            )),
+            Some(_) if !key_release_events => app.add_log_message(
+                "⚠️ Terminal doesn't report media keys, use Space to toggle recording".to_string(),
+            ),
+            _ => {}
+        }
+    }
     let mut clipboard_manager = ClipboardManager::new(&app.lock().unwrap().config)?;
+    let mut transcript_file = TranscriptFile::from_config(&config.output)?;
+    let mut hooks = Hooks::new(&config.hooks);
+    let mut was_recording = false;
 
     let (audio_tx, audio_rx) = mpsc::channel::<AudioData>();
-    let (stt_tx, mut stt_rx) = tokio_mpsc::channel::<String>(1);
+    let (job_tx, mut job_rx) = tokio_mpsc::unbounded_channel::<TranscriptionJob>();
+    let (stt_tx, mut stt_rx) = tokio_mpsc::unbounded_channel::<(u64, TranscriptionResult)>();
     let (log_tx, mut log_rx) = tokio_mpsc::channel::<String>(10);
+    let (refine_tx, mut refine_rx) = tokio_mpsc::unbounded_channel::<Vec<Refinement>>();
+    let (saved_tx, mut saved_rx) = tokio_mpsc::unbounded_channel::<PathBuf>();
+    let (live_tx, mut live_rx) = tokio_mpsc::unbounded_channel::<(u64, String)>();
     let (stop_audio_tx, stop_audio_rx) = mpsc::channel::<()>();
     let (audio_stopped_tx, audio_stopped_rx) = mpsc::channel::<()>();
     let (start_audio_tx, start_audio_rx) = mpsc::channel::<()>();
@@ -102,16 +369,30 @@ async fn main() -> Result<()> {
     let log_tx_clone_prepare = log_tx.clone();
     let stt_prepare_task = tokio::spawn(async move {
         let config = { app_clone_for_stt.lock().unwrap().config.clone() };
-        (load_stt_processor(&config, &app_clone_for_stt, &log_tx_clone_prepare).await).ok()
+        (load_stt_processor(&config, &app_clone_for_stt, &log_tx_clone_prepare).await)
+            .ok()
+            .map(|processor| Arc::new(tokio::sync::Mutex::new(processor)))
     });
 
     // --- Audio Recording Thread ---
-    let config_clone_for_audio = config.clone();
+    let mut config_clone_for_audio = config.clone();
     let app_clone_for_audio = app.clone();
     let audio_stopped_tx_clone = audio_stopped_tx.clone();
     std::thread::spawn(move || {
-        let mut audio_recorder: Option<AudioRecorder> = None;
+        let mut audio_recorder: Option<AudioSource> = None;
         let mut recording_active = false;
+        let mut last_device: Option<String> = None; // Device of the previous session
+        let mut preroll = PrerollBuffer::new(&config_clone_for_audio.audio);
+        let mut last_device_check = Instant::now();
+
+        // The recorder always feeds this channel; samples are relayed to the main
+        // loop while recording, otherwise they fill the pre-roll buffer
+        let (raw_audio_tx, raw_audio_rx) = mpsc::channel::<AudioData>();
+
+        // With pre-roll enabled the stream stays open so audio is buffered before recording starts
+        if preroll.is_enabled() {
+            audio_recorder = open_preroll_stream(&config_clone_for_audio, &raw_audio_tx);
+        }
 
         loop {
             // Check if application should exit
@@ -132,36 +413,114 @@ async fn main() -> Result<()> {
                     // Silently clear leftover signals
                 }
 
-                // Create a fresh audio recorder for each session
-                match AudioRecorder::new(&config_clone_for_audio) {
-                    Ok(mut recorder) => {
-                        if let Err(e) = recorder.start_recording(audio_tx.clone()) {
-                            tracing::error!("Audio thread: Failed to start recording: {}", e);
-                        } else {
-                            tracing::info!("Audio thread: Successfully started recording");
-                            audio_recorder = Some(recorder);
-                            recording_active = true;
+                // Pick up audio settings changed by a config reload
+                let latest_config = app_clone_for_audio.lock().unwrap().config.clone();
+                if latest_config.audio != config_clone_for_audio.audio {
+                    tracing::info!("Audio thread: Audio settings changed, reopening stream");
+                    if let Some(mut recorder) = audio_recorder.take() {
+                        recorder.stop_recording();
+                    }
+                    while raw_audio_rx.try_recv().is_ok() {}
+                    preroll = PrerollBuffer::new(&latest_config.audio);
+                }
+                config_clone_for_audio = latest_config;
+
+                // The pre-roll stream may still be open on a device that was since replaced
+                if audio_recorder.as_ref().is_some_and(|r| r.device_changed()) {
+                    tracing::info!("Audio thread: Default input device changed, reopening stream");
+                    if let Some(mut recorder) = audio_recorder.take() {
+                        recorder.stop_recording();
+                    }
+                    while raw_audio_rx.try_recv().is_ok() {}
+                    preroll.take();
+                }
+
+                if audio_recorder.is_some() {
+                    // Stream is already running for pre-roll, just start relaying
+                    recording_active = true;
+                } else {
+                    // Create a fresh audio recorder for each session
+                    preroll.mark_seam();
+                    match AudioSource::new(&config_clone_for_audio) {
+                        Ok(mut recorder) => {
+                            if let Err(e) = recorder.start_recording(raw_audio_tx.clone()) {
+                                tracing::error!("Audio thread: Failed to start recording: {}", e);
+                            } else {
+                                tracing::info!("Audio thread: Successfully started recording");
+                                audio_recorder = Some(recorder);
+                                recording_active = true;
+                            }
+                        }
+                        Err(e) => {
+                            tracing::error!("Audio thread: Failed to create recorder: {}", e);
                         }
                     }
-                    Err(e) => {
-                        tracing::error!("Audio thread: Failed to create recorder: {}", e);
+                }
+
+                if let Some(recorder) = audio_recorder.as_ref().filter(|_| recording_active) {
+                    let device = recorder.device_name();
+                    let mut app = app_clone_for_audio.lock().unwrap();
+                    match last_device.as_deref() {
+                        Some(previous) if previous != device => app.add_log_message(format!(
+                            "🎙️ Input device changed: {previous} → {device}"
+                        )),
+                        _ => app.add_log_message(format!("🎤 Recording from {device}")),
                     }
+                    tracing::info!("Audio thread: Recording from {}", device);
+                    last_device = Some(device);
+                }
+
+                if recording_active && !preroll.is_empty() {
+                    // The buffered pre-roll audio is joined onto the first live chunk
+                    tracing::debug!("Audio thread: Prepending pre-roll to the recording");
                 }
             }
 
             // Check for stop signal
             if recording_active && stop_audio_rx.try_recv().is_ok() {
                 tracing::info!("Audio thread: Received stop signal, ending recording session");
-                if let Some(ref mut recorder) = audio_recorder {
-                    recorder.stop_recording();
+                if !preroll.is_enabled() {
+                    if let Some(ref mut recorder) = audio_recorder {
+                        recorder.stop_recording();
+                    }
+                    // Drop the recorder completely for next session
+                    audio_recorder = None;
+                }
+                // Relay whatever was captured before the stop
+                while let Ok(data) = raw_audio_rx.try_recv() {
+                    audio_tx.send(with_preroll(&mut preroll, data)).ok();
                 }
-                // Drop the recorder completely for next session
-                audio_recorder = None;
                 recording_active = false;
                 audio_stopped_tx_clone.send(()).ok();
             }
 
-            std::thread::sleep(Duration::from_millis(100));
+            // Follow the default device while idle, so the pre-roll comes from the device
+            // the next recording uses
+            if !recording_active && last_device_check.elapsed() >= DEVICE_CHECK_INTERVAL {
+                last_device_check = Instant::now();
+                if audio_recorder.as_ref().is_some_and(|r| r.device_changed()) {
+                    tracing::info!(
+                        "Audio thread: Default input device changed, reopening pre-roll stream"
+                    );
+                    if let Some(mut recorder) = audio_recorder.take() {
+                        recorder.stop_recording();
+                    }
+                    while raw_audio_rx.try_recv().is_ok() {}
+                    preroll.take();
+                    audio_recorder = open_preroll_stream(&config_clone_for_audio, &raw_audio_tx);
+                }
+            }
+
+            // Relay captured audio, waiting briefly for new data
+            if let Ok(data) = raw_audio_rx.recv_timeout(Duration::from_millis(100)) {
+                for data in std::iter::once(data).chain(raw_audio_rx.try_iter()) {
+                    if recording_active {
+                        audio_tx.send(with_preroll(&mut preroll, data)).ok();
+                    } else {
+                        preroll.push(&data.samples);
+                    }
+                }
+            }
         }
     });
 
@@ -173,6 +532,165 @@ async fn main() -> Result<()> {
         }
     };
     let mut recorded_audio: Vec<f32> = Vec::new();
+    let mut speech_lead_in = PrerollBuffer::new(&config.audio);
+    let mut level_meter = LevelMeter::new(&config.audio);
+    // Cancel flags of jobs without a result yet, so Esc and quitting can cancel them all
+    let mut pending_jobs: HashMap<u64, Arc<AtomicBool>> = HashMap::new();
+    let mut model_reload: Option<tokio::task::JoinHandle<()>> = None;
+    // Live transcription with whisper.stream_interval: one run at a time, tagged with the
+    // recording it belongs to so a late result can't show up in the next one
+    let live_busy = Arc::new(AtomicBool::new(false));
+    let mut live_recording: u64 = 0;
+    let mut last_live = Instant::now();
+    let mut status_before_unload: Option<String> = None;
+    let config_watch = match watch_config_file() {
+        Ok(watch) => Some(watch),
+        Err(e) => {
+            tracing::warn!("Not watching the config file for changes: {:#}", e);
+            None
+        }
+    };
+
+    // --- Transcription Worker ---
+    // Jobs are processed one at a time in the order recordings finished
+    let processor_clone = stt_processor_arc.clone();
+    let text_processor_clone = text_processor.clone();
+    let log_tx_clone_transcribe = log_tx.clone();
+    let transcription_worker = tokio::spawn(async move {
+        while let Some(mut job) = job_rx.recv().await {
+            if job.cancelled.load(Ordering::Relaxed) {
+                // Cancelled while still queued, e.g. when quitting
+                stt_tx.send((job.id, TranscriptionResult::Cancelled)).ok();
+                continue;
+            }
+            // Kept until the end of the job so the file outlives the transcription reading it
+            let audio_file = match job.write_wav().await {
+                Ok(file) => file,
+                Err(e) => {
+                    let message = format!("Failed to save audio: {e:#}");
+                    log_tx_clone_transcribe
+                        .send(format!("Transcription #{}: {message}", job.id))
+                        .await
+                        .ok();
+                    stt_tx
+                        .send((job.id, TranscriptionResult::Error(message)))
+                        .ok();
+                    continue;
+                }
+            };
+            if let Some(dir) = job.audio.save_recordings.clone() {
+                let wav = audio_file.path().to_path_buf();
+                let kept =
+                    tokio::task::spawn_blocking(move || wav_utils::keep_recording(&wav, &dir))
+                        .await
+                        .map_err(anyhow::Error::from)
+                        .and_then(|kept| kept);
+                match kept {
+                    Ok(path) => {
+                        saved_tx.send(path).ok();
+                    }
+                    Err(e) => {
+                        log_tx_clone_transcribe
+                            .send(format!("❌ Failed to keep recording: {e:#}"))
+                            .await
+                            .ok();
+                    }
+                }
+            }
+            let transcription = async {
+                let mut processor = processor_clone.lock().await;
+                // Unloaded by ui.idle_action while idle
+                match processor.ensure_loaded().await {
+                    Ok(true) => {
+                        log_tx_clone_transcribe
+                            .send(format!("🔄 Reloaded {}", processor.model()))
+                            .await
+                            .ok();
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        log_tx_clone_transcribe
+                            .send(format!("❌ Failed to reload the model: {e:#}"))
+                            .await
+                            .ok();
+                    }
+                }
+                if job.fresh_context {
+                    processor.reset_context();
+                }
+                processor.set_cancel_flag(Some(job.cancelled.clone()));
+                processor.set_silence_threshold(job.audio.silence_threshold);
+                let outcome = processor
+                    .transcribe(audio_file.path(), Some(log_tx_clone_transcribe.clone()))
+                    .await;
+                processor.set_cancel_flag(None);
+                let language = outcome
+                    .as_ref()
+                    .ok()
+                    .and_then(Option::as_ref)
+                    .and_then(|transcription| processor.language_tag(transcription))
+                    .map(str::to_string);
+                outcome.map(|transcription| (transcription, language))
+            };
+            // The processor lock lives inside the transcription future, so a cancel releases
+            // it (or stops waiting for it) straight away. Dropping the future also aborts API
+            // requests, the local and Vosk backends check the flag themselves.
+            let outcome = tokio::select! {
+                outcome = transcription => outcome,
+                _ = wait_for_cancel(&job.cancelled) => {
+                    // Don't leave the flag behind for live transcriptions to trip over
+                    processor_clone.lock().await.set_cancel_flag(None);
+                    Err(anyhow::anyhow!("Transcription cancelled"))
+                }
+            };
+
+            let result = match outcome {
+                _ if job.cancelled.load(Ordering::Relaxed) => TranscriptionResult::Cancelled,
+                Ok((Some(transcription), language)) => {
+                    tracing::debug!(
+                        "Transcription #{} by {} in {} ms, language {:?}, confidence {:?}",
+                        job.id,
+                        transcription.model,
+                        transcription.duration_ms,
+                        transcription.language,
+                        transcription.confidence
+                    );
+                    let text = text_processor_clone
+                        .lock()
+                        .unwrap()
+                        .process(&transcription.text);
+                    let text = match language {
+                        _ if text.trim().is_empty() => None,
+                        Some(language) => Some(tag_language(&text, &language)),
+                        None => Some(text),
+                    };
+                    match text {
+                        Some(text) => TranscriptionResult::Text(Transcription {
+                            text,
+                            ..transcription
+                        }),
+                        None => TranscriptionResult::NoSpeech,
+                    }
+                }
+                Ok((None, _)) => TranscriptionResult::NoSpeech,
+                Err(e) => TranscriptionResult::Error(e.to_string()),
+            };
+            let log_message = match &result {
+                TranscriptionResult::Text(_) => None,
+                TranscriptionResult::NoSpeech => Some("No speech detected.".to_string()),
+                TranscriptionResult::Error(e) => Some(format!("Transcription error: {e}")),
+                TranscriptionResult::Cancelled => Some("Cancelled.".to_string()),
+            };
+            if let Some(message) = log_message {
+                log_tx_clone_transcribe
+                    .send(format!("Transcription #{}: {message}", job.id))
+                    .await
+                    .ok();
+            }
+            stt_tx.send((job.id, result)).ok();
+            drop(audio_file); // Ensure the temporary file is dropped after transcription
+        }
+    });
 
     loop {
         let app_arc = app.clone(); // Store reference to Arc before locking
@@ -188,6 +706,58 @@ async fn main() -> Result<()> {
         while let Ok(log_message) = log_rx.try_recv() {
             app.add_log_message(log_message);
         }
+        while let Ok(path) = saved_rx.try_recv() {
+            app.recording_saved(path);
+        }
+
+        if app.export_requested {
+            app.export_requested = false;
+            let dir = Config::cache_dir().join("exports");
+            match wav_utils::export_samples(&app.last_recording, app.config.audio.sample_rate, &dir)
+            {
+                Ok(path) => {
+                    app.add_log_message(format!("📤 Samples exported to {}", path.display()))
+                }
+                Err(e) => app.add_log_message(format!("❌ Export failed: {e:#}")),
+            }
+        }
+
+        // A fresh processor is built, so a model that loaded but misbehaves gets a clean start
+        if app.model_reload_requested {
+            app.model_reload_requested = false;
+            let model = app.config.whisper.model.clone();
+            let reload = if app.model_redownload_requested {
+                app.model_redownload_requested = false;
+                match remove_cached_model(&app.config.whisper) {
+                    Ok(true) => {
+                        app.add_log_message(format!(
+                            "🗑️ Deleted cached {model}, downloading again"
+                        ));
+                        true
+                    }
+                    Ok(false) => {
+                        app.add_log_message(format!("No cached copy of {model} to delete"));
+                        true
+                    }
+                    Err(e) => {
+                        app.add_log_message(format!("❌ {e:#}"));
+                        false
+                    }
+                }
+            } else {
+                true
+            };
+            if reload {
+                tracing::info!("Reloading model {}", model);
+                app.add_log_message(format!("🔄 Reloading {model}..."));
+                model_reload = Some(spawn_model_reload(
+                    &mut app,
+                    &app_arc,
+                    &stt_processor_arc,
+                    &log_tx,
+                ));
+            }
+        }
 
         // Handle model selection confirmation
         if app.model_change_requested {
@@ -196,8 +766,6 @@ async fn main() -> Result<()> {
             if selected_model != app.get_current_model() {
                 // Update config and reload model
                 app.config.whisper.model = selected_model.clone();
-                app.model_status = format!("Loading {selected_model}...");
-                app.state = AppState::LoadingModel;
 
                 // Save config
                 if let Err(e) = app.config.save() {
@@ -205,55 +773,243 @@ async fn main() -> Result<()> {
                 }
 
                 tracing::info!("Model changed to: {}, reloading...", selected_model);
+                model_reload = Some(spawn_model_reload(
+                    &mut app,
+                    &app_arc,
+                    &stt_processor_arc,
+                    &log_tx,
+                ));
+            } else {
+                app.exit_model_selection();
+            }
+        }
 
-                // Reload the STT processor with new model
-                let app_clone_for_reload = app_arc.clone();
-                let log_tx_clone_reload = log_tx.clone();
-                let config_for_reload = app.config.clone();
-                let stt_processor_clone = stt_processor_arc.clone();
+        if app.model_load_cancel_requested {
+            app.model_load_cancel_requested = false;
+            match model_reload.take().filter(|task| !task.is_finished()) {
+                Some(task) => {
+                    task.abort();
+                    let cancelled_model = app.config.whisper.model.clone();
+                    if let Err(e) = remove_partial_download(&app.config.whisper) {
+                        tracing::warn!("{:#}", e);
+                    }
+                    app.revert_model_change();
+                    let message = format!(
+                        "⏹️ Cancelled loading {cancelled_model}, keeping {}",
+                        app.config.whisper.model
+                    );
+                    app.add_log_message(message);
+                }
+                // The initial load has no previous model to go back to
+                None => app.add_log_message("Can't cancel the initial model load".to_string()),
+            }
+        }
 
-                tokio::spawn(async move {
-                    match load_stt_processor(
-                        &config_for_reload,
-                        &app_clone_for_reload,
-                        &log_tx_clone_reload,
-                    )
-                    .await
-                    {
-                        Ok(new_processor) => {
-                            // Replace the old processor with the new one
-                            let new_processor_inner = Arc::try_unwrap(new_processor)
-                                .map_err(|_| "Failed to unwrap Arc")
-                                .unwrap()
-                                .into_inner();
-                            let mut old_processor = stt_processor_clone.lock().await;
-                            *old_processor = new_processor_inner;
-                            tracing::info!(
-                                "✅ Model {} loaded successfully",
-                                config_for_reload.whisper.model
-                            );
-                        }
-                        Err(e) => {
-                            tracing::error!(
-                                "Failed to reload model {}: {}",
-                                config_for_reload.whisper.model,
-                                e
-                            );
-                            let mut app = app_clone_for_reload.lock().unwrap();
-                            app.model_status =
-                                format!("❌ Failed to load {}", config_for_reload.whisper.model);
-                            app.state = AppState::Idle;
-                        }
+        // Edits to the config file reload it like R, our own saves are already applied
+        let config_edited = config_watch
+            .as_ref()
+            .is_some_and(|(_, changes)| changes.try_iter().count() > 0)
+            && Config::load().is_ok_and(|mut config| {
+                config.audio.input_file = app.config.audio.input_file.clone();
+                !config.same_settings(&app.config)
+            });
+        if app.config_reload_requested || config_edited {
+            app.config_reload_requested = false;
+            let reloaded = Config::load().and_then(|config| {
+                let processor = TextProcessor::new(&config)?;
+                Ok((config, processor))
+            });
+            match reloaded {
+                Ok((mut new_config, new_text_processor)) => {
+                    new_config.audio.input_file = app.config.audio.input_file.clone();
+                    let whisper_changed = new_config.whisper != app.config.whisper;
+                    *text_processor.lock().unwrap() = new_text_processor;
+                    clipboard_manager.update_config(&new_config);
+                    hooks = Hooks::new(&new_config.hooks);
+                    if new_config.output != app.config.output {
+                        transcript_file = match TranscriptFile::from_config(&new_config.output) {
+                            Ok(file) => file,
+                            Err(e) => {
+                                app.add_log_message(format!("❌ Transcript file disabled: {e:#}"));
+                                None
+                            }
+                        };
                     }
-                });
+                    app.apply_config(new_config);
+                    speech_lead_in = PrerollBuffer::new(&app.config.audio);
+                    level_meter = LevelMeter::new(&app.config.audio);
+                    app.add_log_message("🔄 Configuration reloaded".to_string());
+                    if whisper_changed {
+                        tracing::info!("Whisper settings changed, reloading model...");
+                        model_reload = Some(spawn_model_reload(
+                            &mut app,
+                            &app_arc,
+                            &stt_processor_arc,
+                            &log_tx,
+                        ));
+                    }
+                }
+                Err(e) => app.add_log_message(format!("❌ Config not reloaded: {e:#}")),
+            }
+        }
+
+        if app.language_change_requested {
+            app.language_change_requested = false;
+            // Language is a per-transcription parameter, so the loaded model is kept
+            let language = app.config.whisper.language.clone();
+            let processor = stt_processor_arc.clone();
+            tokio::spawn(async move {
+                processor.lock().await.set_language(language);
+            });
+        }
+
+        if app.cancel_requested {
+            app.cancel_requested = false;
+            if pending_jobs.is_empty() {
+                // The recording hadn't been handed over yet, the idle drain discards it
+                app.add_log_message("⏹️ Transcription cancelled".to_string());
             } else {
-                app.exit_model_selection();
+                for cancelled in pending_jobs.values() {
+                    cancelled.store(true, Ordering::Relaxed);
+                }
+                app.add_log_message("⏹️ Cancelling transcription...".to_string());
+            }
+        }
+
+        let mut auto_profile = None;
+        let refine_text = if app.refine_requested {
+            app.refine_requested = false;
+            let text = app.transcribed_text().unwrap_or_default();
+            let llm = &app.config.llm;
+            match llm.auto_profile(text) {
+                Some((profile, rest)) => {
+                    auto_profile = Some(profile.to_string());
+                    Some(rest.to_string())
+                }
+                // Without a trigger word P compares profiles as usual
+                None => Some(text.to_string()),
+            }
+        } else if app.clipboard_refine_requested {
+            app.clipboard_refine_requested = false;
+            // Choosing a refinement copies it back over the original
+            match clipboard_manager
+                .get_clipboard_text()
+                .map(|contents| contents.into_text())
+            {
+                Ok(Some(text)) if !text.trim().is_empty() => Some(text),
+                Ok(_) => {
+                    app.add_log_message("Clipboard has no text to refine".to_string());
+                    None
+                }
+                Err(e) => {
+                    app.add_log_message(format!("❌ Couldn't read the clipboard: {e}"));
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        if let Some(text) = refine_text {
+            match LlmRefiner::new(&app.config) {
+                Ok(refiner) if refiner.is_configured() => {
+                    let profiles = match auto_profile {
+                        Some(profile) => vec![profile],
+                        None => refiner.compare_profiles(),
+                    };
+                    app.add_log_message(format!("🤖 Refining with {}...", profiles.join(", ")));
+                    let refine_tx = refine_tx.clone();
+                    tokio::spawn(async move {
+                        let refinements = refiner
+                            .refine_with_profiles(&text, &profiles)
+                            .await
+                            .into_iter()
+                            .map(|(profile, result)| {
+                                let result = match result {
+                                    Ok(Some(text)) => Ok(text),
+                                    Ok(None) => Err("Empty response".to_string()),
+                                    Err(e) => Err(format!("{e:#}")),
+                                };
+                                (profile, result)
+                            })
+                            .collect();
+                        refine_tx.send(refinements).ok();
+                    });
+                }
+                Ok(_) => app.add_log_message(
+                    "❌ LLM not configured, set llm.api_key or the provider's API key variable"
+                        .to_string(),
+                ),
+                Err(e) => app.add_log_message(format!("❌ LLM unavailable: {e}")),
+            }
+        }
+
+        while let Ok(refinements) = refine_rx.try_recv() {
+            app.show_refinements(refinements);
+        }
+
+        if app.copy_requested {
+            app.copy_requested = false;
+            match app.transcribed_text().map(str::to_string) {
+                // Pressing C always copies, even if the text is already there
+                Some(text) => match clipboard_manager.copy_to_clipboard(&text, true) {
+                    Ok(_) => {
+                        app.add_log_message("📋 Copied transcription to clipboard".to_string())
+                    }
+                    Err(e) => app.add_log_message(format!("Clipboard copy failed: {e}")),
+                },
+                None => app.add_log_message("Nothing to copy".to_string()),
+            }
+        }
+
+        if let Some((output, text)) = app.profile_output.take() {
+            match output.deliver(&text) {
+                Ok(()) => app.add_log_message(format!("📤 Refinement sent to {output}")),
+                Err(e) => {
+                    app.add_log_message(format!("❌ Sending refinement to {output} failed: {e:#}"))
+                }
             }
         }
 
-        if app.state == AppState::Recording {
+        if app.logs_copy_requested {
+            app.logs_copy_requested = false;
+            let logs = app.logs.join("\n");
+            match clipboard_manager.copy_to_clipboard(&logs, true) {
+                Ok(_) => {
+                    let count = app.logs.len();
+                    app.add_log_message(format!("📋 Copied {count} log messages to clipboard"))
+                }
+                Err(e) => app.add_log_message(format!("Clipboard copy failed: {e}")),
+            }
+        }
+
+        match clipboard_manager.clear_expired() {
+            Ok(true) => app.add_log_message("🧹 Clipboard cleared".to_string()),
+            Ok(false) => {}
+            Err(e) => app.add_log_message(format!("Clipboard clear failed: {e}")),
+        }
+
+        if app.clipboard_restore_requested {
+            app.clipboard_restore_requested = false;
+            let message = match clipboard_manager.restore_previous() {
+                Ok(true) => "↩️ Restored previous clipboard contents".to_string(),
+                Ok(false) => {
+                    "↩️ Cleared clipboard (previous contents were empty or not text)".to_string()
+                }
+                Err(e) => format!("Clipboard undo failed: {e}"),
+            };
+            app.add_log_message(message);
+        }
+
+        if matches!(app.state, AppState::Recording | AppState::MicTest) {
             if let Ok(data) = audio_rx.try_recv() {
-                app.audio_level = data.level;
+                let data = apply_gain(data, app.config.audio.gain);
+                let chunk = Duration::from_secs_f64(
+                    data.samples.len() as f64
+                        / (app.config.audio.sample_rate as f64 * app.config.audio.channels as f64),
+                );
+                // Smoothed so the meter and silence detection don't jitter chunk to chunk
+                let level = level_meter.update(data.level, chunk);
+                app.audio_level = level;
 
                 // Update waveform for visualization (keep recent samples for display)
                 const WAVEFORM_SAMPLES: usize = 100;
@@ -279,6 +1035,7 @@ async fn main() -> Result<()> {
                     let excess = app.audio_waveform.len() - WAVEFORM_SAMPLES;
                     app.audio_waveform.drain(0..excess);
                 }
+                app.push_waveform_history(&data.samples);
 
                 // Debug: Log waveform data occasionally
                 static mut DEBUG_COUNTER: usize = 0;
@@ -300,11 +1057,62 @@ async fn main() -> Result<()> {
                     }
                 }
 
-                // Now extend recorded_audio (this consumes data.samples)
-                recorded_audio.extend(data.samples);
+                if app.state == AppState::MicTest {
+                    // Mic test audio is only measured, never kept
+                    app.record_mic_test_level(level);
+                } else {
+                    match app.voice_activity(level, chunk) {
+                        // Keep a short lead-in so the first word isn't clipped
+                        VoiceActivity::Armed => speech_lead_in.push(&data.samples),
+                        VoiceActivity::Capture => {
+                            // Now extend recorded_audio (this consumes data.samples)
+                            recorded_audio.extend(speech_lead_in.join(data.samples));
+                        }
+                    }
+                }
             }
         }
 
+        if let Some(interval) = app
+            .config
+            .whisper
+            .stream_interval
+            .filter(|secs| *secs > 0.0)
+        {
+            if app.state == AppState::Recording
+                && !recorded_audio.is_empty()
+                && last_live.elapsed() >= Duration::from_secs_f64(interval)
+                && !live_busy.swap(true, Ordering::Relaxed)
+            {
+                last_live = Instant::now();
+                let window = live_window(&recorded_audio, &app.config.audio).to_vec();
+                let (job, _) = TranscriptionJob::new(live_recording, window, &app.config.audio);
+                let processor = stt_processor_arc.clone();
+                let text_processor = text_processor.clone();
+                let live_tx = live_tx.clone();
+                let live_busy = live_busy.clone();
+                tokio::spawn(async move {
+                    let id = job.id;
+                    if let Some(text) = transcribe_live(job, &processor, &text_processor).await {
+                        live_tx.send((id, text)).ok();
+                    }
+                    live_busy.store(false, Ordering::Relaxed);
+                });
+            }
+        }
+        while let Ok((recording, text)) = live_rx.try_recv() {
+            if recording == live_recording {
+                app.set_live_transcript(text);
+            }
+        }
+
+        if app.state == AppState::Idle {
+            // Discard the tail of a finished mic test so it can't leak into the next recording
+            while audio_stopped_rx.try_recv().is_ok() {}
+            while audio_rx.try_recv().is_ok() {}
+            level_meter.reset();
+        }
+
         if app.state == AppState::Transcribing {
             if !app.transcription_initiated {
                 app.transcription_initiated = true;
@@ -315,63 +1123,146 @@ async fn main() -> Result<()> {
             if audio_stopped_rx.try_recv().is_ok() {
                 // Drain any remaining audio data from the channel
                 while let Ok(data) = audio_rx.try_recv() {
-                    recorded_audio.extend(data.samples);
+                    recorded_audio.extend(apply_gain(data, app.config.audio.gain).samples);
                 }
 
                 let audio_to_process = std::mem::take(&mut recorded_audio);
+                speech_lead_in.take(); // Stopped while still armed
                 let config = app.config.clone();
-                let stt_tx_clone = stt_tx.clone();
-                let processor_clone = stt_processor_arc.clone();
-                let log_tx_clone_transcribe = log_tx.clone();
 
-                let audio_duration_sec =
-                    audio_to_process.len() as f32 / config.audio.sample_rate as f32;
+                let audio_duration_sec = audio_to_process.len() as f32
+                    / (config.audio.sample_rate * config.audio.channels as u32) as f32;
                 tracing::debug!(
                     "Processing audio: {} samples, duration: {:.2} seconds",
                     audio_to_process.len(),
                     audio_duration_sec
                 );
 
-                // Save the audio file in the main thread to avoid race conditions
-                let audio_file = wav_utils::save_wav(
-                    &audio_to_process,
-                    config.audio.sample_rate,
-                    config.audio.channels,
-                )?;
-
-                tokio::spawn(async move {
-                    let processor = processor_clone.lock().await;
-                    let result = match processor
-                        .transcribe(audio_file.path(), Some(log_tx_clone_transcribe.clone()))
-                        .await
-                    {
-                        Ok(Some(text)) => text,
-                        Ok(None) => {
-                            log_tx_clone_transcribe
-                                .send("Transcription: No speech detected.".to_string())
-                                .await
-                                .ok();
-                            "No speech detected.".to_string()
-                        }
-                        Err(e) => {
-                            let error_msg = format!("Transcription error: {e}");
-                            log_tx_clone_transcribe.send(error_msg.clone()).await.ok();
-                            error_msg
-                        }
-                    };
-                    stt_tx_clone.send(result).await.ok();
-                    drop(audio_file); // Ensure the temporary file is dropped after transcription
-                });
+                let min_speech = Duration::from_millis(config.audio.min_speech_ms as u64);
+                let speech = speech_duration(&audio_to_process, &config.audio);
+                if speech < min_speech {
+                    // A double tap or a silent recording, not worth a transcription round-trip
+                    tracing::info!("Skipping recording with {}ms of speech", speech.as_millis());
+                    app.add_log_message("⏭️ Recording too short, skipped".to_string());
+                    app.discard_recording();
+                } else {
+                    app.stats.record_recording(audio_duration_sec as f64);
+                    let id = app.enqueue_transcription(&audio_to_process);
+                    tracing::info!("Queued transcription #{}", id);
+                    let (job, cancelled) =
+                        TranscriptionJob::new(id, audio_to_process.clone(), &config.audio);
+                    job_tx.send(job).ok();
+                    pending_jobs.insert(id, cancelled);
+                    app.last_recording = audio_to_process;
+                }
             }
         }
 
-        if let Ok(text) = stt_rx.try_recv() {
-            if text != "No speech detected." {
-                clipboard_manager.copy_to_clipboard(&text)?;
+        // Re-transcribe the last recording once the chosen model has loaded
+        if let Some(audio) = app.take_retry() {
+            let config = app.config.clone();
+            let id = app.enqueue_transcription(&audio);
+            app.add_log_message(format!(
+                "🔁 Re-transcribing last recording with {} (#{id})",
+                config.whisper.model
+            ));
+            let (mut job, cancelled) = TranscriptionJob::new(id, audio, &config.audio);
+            // The previous transcript is the one being replaced, so it's no use as context
+            job.fresh_context = true;
+            job_tx.send(job).ok();
+            pending_jobs.insert(id, cancelled);
+        }
+
+        while let Ok((job_id, result)) = stt_rx.try_recv() {
+            pending_jobs.remove(&job_id);
+            if app.model_unloaded {
+                // The worker loaded the model again for this transcription, unless a
+                // model change since has set a status of its own
+                app.model_unloaded = false;
+                if let Some(status) = status_before_unload
+                    .take()
+                    .filter(|_| app.model_status.starts_with("💤"))
+                {
+                    app.model_status = status;
+                }
             }
-            app.finish_processing(text);
+            let model = app.config.whisper.model.clone();
+            app.stats.record_transcription(&model, &result);
+            if let TranscriptionResult::Text(Transcription { text, .. }) = &result {
+                if let Some(file) = transcript_file.as_mut() {
+                    if let Err(e) = file.append(text) {
+                        app.add_log_message(format!(
+                            "Failed to append to {:?}: {e:#}",
+                            file.path()
+                        ));
+                    }
+                }
+                let text = if app.config.clipboard.accumulate {
+                    app.accumulate(text).to_string()
+                } else {
+                    text.clone()
+                };
+                if app.config.clipboard.auto_copy
+                    && !clipboard_manager.copy_to_clipboard(&text, false)?
+                {
+                    app.add_log_message("📋 Clipboard unchanged".to_string());
+                }
+            }
+            app.finish_processing(job_id, result);
             app.reset(); // Reset state for new transcription
-            recorded_audio.clear();
+        }
+
+        // Let external indicators follow the recording state
+        let recording = app.state == AppState::Recording;
+        if recording != was_recording {
+            was_recording = recording;
+            if recording {
+                live_recording += 1;
+                last_live = Instant::now();
+                hooks.record_start("recording");
+                if app.config.ui.record_bell {
+                    let backend = terminal.backend_mut();
+                    io::Write::write_all(backend, b"\x07")
+                        .and_then(|()| io::Write::flush(backend))
+                        .ok();
+                }
+            } else if app.state == AppState::Transcribing {
+                hooks.record_stop("transcribing");
+            } else {
+                hooks.record_stop("idle");
+            }
+        }
+
+        if app.idle_expired() {
+            let idle = app.last_activity.elapsed().as_secs() / 60;
+            if app.config.ui.idle_action == IdleAction::Unload {
+                // Skipped while the processor is busy, e.g. with a live transcription
+                if let Ok(mut processor) = stt_processor_arc.try_lock() {
+                    if processor.unload() {
+                        app.model_unloaded = true;
+                        tracing::info!("Unloaded the model after {} min idle", idle);
+                        status_before_unload = Some(std::mem::replace(
+                            &mut app.model_status,
+                            "💤 Unloaded while idle, reloads for the next transcription"
+                                .to_string(),
+                        ));
+                        app.add_log_message(format!(
+                            "💤 Unloaded {} after {idle} min idle",
+                            processor.model()
+                        ));
+                    } else {
+                        // Nothing held to free, e.g. the API backend, so wait out another period
+                        app.touch();
+                    }
+                }
+            } else {
+                tracing::info!("Quitting after {} min idle", idle);
+                app.quit();
+            }
+        }
+
+        if app.finishing_up && !app.has_pending_transcriptions() {
+            app.running = false;
         }
 
         app.tick();
@@ -379,45 +1270,459 @@ async fn main() -> Result<()> {
         std::thread::sleep(Duration::from_millis(10));
     }
 
-    restore_terminal(&mut terminal)?;
+    if let Err(e) = clipboard_manager.clear_pending() {
+        tracing::warn!("Failed to clear the clipboard on exit: {}", e);
+    }
+
+    // Cancel anything still pending and let the worker settle before tearing down the terminal
+    const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+    for cancelled in pending_jobs.values() {
+        cancelled.store(true, Ordering::Relaxed);
+    }
+    drop(job_tx);
+    drop(log_rx); // Don't let the worker block on a full log channel
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, transcription_worker)
+        .await
+        .is_err()
+    {
+        tracing::warn!("Transcription worker didn't stop in time");
+    }
+    restore_terminal(&mut terminal, key_release_events, alt_screen)?;
+
+    let app = app.lock().unwrap();
+    if !alt_screen {
+        if let Some(text) = app.transcribed_text() {
+            println!("{text}");
+        }
+    }
+    print_session_stats(&app.stats, app.config.ui.persist_stats);
     Ok(())
 }
 
-fn setup_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
+/// Only the last 30 s of a recording in progress are re-transcribed live
+const LIVE_WINDOW_SECS: usize = 30;
+
+/// The most recent `LIVE_WINDOW_SECS` of a recording in progress
+fn live_window<'a>(samples: &'a [f32], audio: &AudioConfig) -> &'a [f32] {
+    let window = LIVE_WINDOW_SECS * audio.sample_rate as usize * audio.channels as usize;
+    &samples[samples.len().saturating_sub(window)..]
+}
+
+/// Transcribe part of a recording in progress for the live transcript. Skipped while a
+/// finished recording or a model reload has the processor, and errors only show as `None`.
+async fn transcribe_live(
+    mut job: TranscriptionJob,
+    processor: &tokio::sync::Mutex<SttProcessor>,
+    text_processor: &Mutex<TextProcessor>,
+) -> Option<String> {
+    let audio_file = job.write_wav().await.ok()?;
+    let processor = processor.try_lock().ok()?;
+    // Segments don't become context for the next transcription like a full one does
+    let segments = match processor.transcribe_segments(audio_file.path(), None).await {
+        Ok(segments) => segments,
+        Err(e) => {
+            tracing::debug!("Live transcription failed: {e:#}");
+            return None;
+        }
+    };
+    drop(processor);
+    let text = segments
+        .iter()
+        .map(|(text, _, _)| text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(text_processor.lock().unwrap().process(&text))
+}
+
+/// How often the audio thread checks whether the pre-roll stream's device was replaced
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Open the stream that keeps filling the pre-roll buffer between recordings
+fn open_preroll_stream(config: &Config, audio_tx: &mpsc::Sender<AudioData>) -> Option<AudioSource> {
+    let mut recorder = match AudioSource::new(config) {
+        Ok(recorder) => recorder,
+        Err(e) => {
+            tracing::error!("Audio thread: Failed to create recorder: {}", e);
+            return None;
+        }
+    };
+    match recorder.start_recording(audio_tx.clone()) {
+        Ok(_) => {
+            tracing::info!(
+                "Audio thread: Pre-roll buffering enabled ({} ms)",
+                config.audio.preroll_ms
+            );
+            Some(recorder)
+        }
+        Err(e) => {
+            tracing::error!("Audio thread: Failed to start pre-roll stream: {}", e);
+            None
+        }
+    }
+}
+
+/// Join any buffered pre-roll audio onto a chunk relayed from the recorder
+fn with_preroll(preroll: &mut PrerollBuffer, data: AudioData) -> AudioData {
+    if preroll.is_empty() {
+        return data;
+    }
+    let samples = preroll.join(data.samples);
+    let level = calculate_rms(&samples);
+    AudioData { samples, level }
+}
+
+/// Print the session stats after the TUI exits, adding them to the all-time totals.
+/// They go to stderr so stdout only holds the transcription printed in inline mode.
+fn print_session_stats(stats: &Stats, persist: bool) {
+    if stats.is_empty() {
+        return;
+    }
+    eprintln!("Session summary:\n{}", stats.summary());
+    if !persist {
+        return;
+    }
+    match stats.persist_to(&Stats::path()) {
+        Ok(totals) => eprintln!("\nAll time:\n{}", totals.summary()),
+        Err(e) => tracing::warn!("Failed to save stats: {}", e),
+    }
+}
+
+/// Watch the config file for edits. Its directory is watched, since editors often
+/// replace the file instead of writing to it.
+fn watch_config_file() -> Result<(notify::RecommendedWatcher, mpsc::Receiver<()>)> {
+    let path = Config::config_path()?;
+    let dir = path
+        .parent()
+        .context("Config file has no parent directory")?
+        .to_path_buf();
+    let (change_tx, change_rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if event.is_ok_and(|event| event.paths.contains(&path)) {
+            change_tx.send(()).ok();
+        }
+    })?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {dir:?}"))?;
+    Ok((watcher, change_rx))
+}
+
+/// Reload the STT processor for the current whisper config in the background.
+/// The old processor keeps serving until the new one is ready, so aborting the returned
+/// task leaves the previous model in place
+fn spawn_model_reload(
+    app: &mut App,
+    app_arc: &Arc<Mutex<App>>,
+    stt_processor: &Arc<tokio::sync::Mutex<SttProcessor>>,
+    log_tx: &tokio_mpsc::Sender<String>,
+) -> tokio::task::JoinHandle<()> {
+    app.model_status = format!("Loading {}...", app.config.whisper.model);
+    app.state = AppState::LoadingModel;
+
+    let app_clone_for_reload = app_arc.clone();
+    let log_tx_clone_reload = log_tx.clone();
+    let config_for_reload = app.config.clone();
+    let stt_processor_clone = stt_processor.clone();
+
+    tokio::spawn(async move {
+        match load_stt_processor(
+            &config_for_reload,
+            &app_clone_for_reload,
+            &log_tx_clone_reload,
+        )
+        .await
+        {
+            Ok(new_processor) => {
+                // Swap the new processor in under the lock, once any transcription is done
+                *stt_processor_clone.lock().await = new_processor;
+                tracing::info!(
+                    "✅ Model {} loaded successfully",
+                    config_for_reload.whisper.model
+                );
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to reload model {}: {}",
+                    config_for_reload.whisper.model,
+                    e
+                );
+                let mut app = app_clone_for_reload.lock().unwrap();
+                app.model_status = format!("❌ Failed to load {}", config_for_reload.whisper.model);
+                app.state = AppState::Idle;
+            }
+        }
+    })
+}
+
+/// Transcribe a WAV file with the configured backend and print WER and timing
+async fn run_bench(config: &Config, wav: &Path, reference: &Path) -> Result<()> {
+    let reference_text = std::fs::read_to_string(reference)
+        .with_context(|| format!("Failed to read reference transcript: {reference:?}"))?;
+    let audio_duration = wav_utils::wav_duration(wav)?;
+
+    let mut processor = SttProcessor::new(config)?;
+    let load_start = Instant::now();
+    processor.prepare().await?;
+    let load_time = load_start.elapsed();
+
+    let transcribe_start = Instant::now();
+    let hypothesis = processor
+        .transcribe(wav, None)
+        .await?
+        .map(|transcription| transcription.text)
+        .unwrap_or_default();
+    let transcribe_time = transcribe_start.elapsed();
+
+    let wer = word_error_rate(&reference_text, &hypothesis);
+    let real_time_factor = transcribe_time.as_secs_f64() / audio_duration.as_secs_f64().max(0.001);
+
+    println!("Backend:        {}", processor.backend_type());
+    println!("Model:          {}", processor.model());
+    println!("Audio duration: {:.2}s", audio_duration.as_secs_f64());
+    println!("Load time:      {:.2}s", load_time.as_secs_f64());
+    println!(
+        "Transcription:  {:.2}s (real-time factor {:.2})",
+        transcribe_time.as_secs_f64(),
+        real_time_factor
+    );
+    println!("Hypothesis:     {hypothesis}");
+    println!("WER:            {:.2}%", wer * 100.0);
+
+    if config.ui.persist_stats {
+        let mut stats = Stats::default();
+        stats.record_wer(wer);
+        if let Err(e) = stats.persist_to(&Stats::path()) {
+            tracing::warn!("Failed to save stats: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the input devices, marking the default and labeling monitor sources
+fn list_devices() -> Result<()> {
+    for (name, is_default) in list_input_devices()? {
+        let default = if is_default { " (default)" } else { "" };
+        let monitor = if is_monitor_device(&name) {
+            " [monitor: system audio]"
+        } else {
+            ""
+        };
+        println!("{name}{default}{monitor}");
+    }
+    Ok(())
+}
+
+/// Print the downloaded models and the total size of the model cache
+fn list_models(config: &Config) -> Result<()> {
+    let models = cached_models()?;
+    if models.is_empty() {
+        println!("No models downloaded to {}", Config::models_dir().display());
+        return Ok(());
+    }
+    for model in &models {
+        let current = if model.name == config.whisper.model {
+            " (current)"
+        } else {
+            ""
+        };
+        println!("{:<20} {:>8}{current}", model.name, format_size(model.size));
+    }
+    let total: u64 = models.iter().map(|model| model.size).sum();
+    println!(
+        "Total: {} in {}",
+        format_size(total),
+        Config::models_dir().display()
+    );
+    Ok(())
+}
+
+/// Delete the named models, or all but the configured one
+fn prune_models(config: &Config, names: &[String], all_except_current: bool) -> Result<()> {
+    if names.is_empty() && !all_except_current {
+        anyhow::bail!("Name the models to delete, or pass --all-except-current");
+    }
+    let models = cached_models()?;
+    for name in names {
+        if !models.iter().any(|model| &model.name == name) {
+            println!("{name} is not downloaded");
+        }
+    }
+    let mut freed = 0;
+    for model in models.iter().filter(|model| {
+        if all_except_current {
+            model.name != config.whisper.model
+        } else {
+            names.contains(&model.name)
+        }
+    }) {
+        std::fs::remove_file(&model.path)
+            .with_context(|| format!("Failed to delete {:?}", model.path))?;
+        println!("Deleted {} ({})", model.name, format_size(model.size));
+        freed += model.size;
+    }
+    println!("Freed {}", format_size(freed));
+    Ok(())
+}
+
+/// Transcribe an audio file and write it as plain text, subtitles or JSON
+async fn run_transcribe(
+    config: &Config,
+    file: &Path,
+    format: OutputFormat,
+    output: Option<&Path>,
+) -> Result<()> {
+    let mut processor = SttProcessor::new(config)?;
+    processor.prepare().await?;
+    let segments = processor.transcribe_segments(file, None).await?;
+
+    let rendered = match format {
+        OutputFormat::Txt => {
+            let text: Vec<&str> = segments.iter().map(|(text, _, _)| text.as_str()).collect();
+            format!("{}\n", text.join(" "))
+        }
+        OutputFormat::Srt => to_srt(&segments),
+        OutputFormat::Vtt => to_vtt(&segments),
+        OutputFormat::Json => {
+            let segments: Vec<serde_json::Value> = segments
+                .iter()
+                .map(|(text, start, end)| {
+                    serde_json::json!({ "start": start, "end": end, "text": text })
+                })
+                .collect();
+            format!("{}\n", serde_json::to_string_pretty(&segments)?)
+        }
+    };
+
+    match output {
+        Some(path) => std::fs::write(path, rendered)
+            .with_context(|| format!("Failed to write output file: {path:?}"))?,
+        None => print!("{rendered}"),
+    }
+
+    Ok(())
+}
+
+/// Set up the TUI, on the alternate screen or inline below the prompt, also enabling key
+/// release reporting (kitty keyboard protocol) for hold-to-record when asked and the
+/// terminal supports it. Returns whether it was enabled.
+fn setup_terminal(
+    key_releases: bool,
+    alt_screen: bool,
+) -> Result<(Terminal<CrosstermBackend<io::Stdout>>, bool)> {
     let mut stdout = io::stdout();
     enable_raw_mode()?;
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    if alt_screen {
+        execute!(stdout, EnterAlternateScreen)?;
+    }
+    let key_releases = key_releases && supports_keyboard_enhancement().unwrap_or(false);
+    if key_releases {
+        // Report every key as an escape code so Space releases come through, with the
+        // shifted character for keys like ? and +
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                    | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                    | KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES
+                    | KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS
+            )
+        )?;
+    }
     let backend = CrosstermBackend::new(stdout);
-    let terminal = Terminal::new(backend)?;
-    Ok(terminal)
+    let terminal = if alt_screen {
+        Terminal::new(backend)?
+    } else {
+        // Room for the layout with the log panel open, without pushing the prompt off
+        let (_, rows) = terminal_size()?;
+        Terminal::with_options(
+            backend,
+            TerminalOptions {
+                viewport: Viewport::Inline(rows.min(INLINE_HEIGHT)),
+            },
+        )?
+    };
+    Ok((terminal, key_releases))
 }
 
-fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
+fn restore_terminal(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    key_releases: bool,
+    alt_screen: bool,
+) -> Result<()> {
+    if key_releases {
+        execute!(terminal.backend_mut(), PopKeyboardEnhancementFlags)?;
+    }
     disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+    if alt_screen {
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    } else {
+        // Leave the cursor where the viewport started, for output printed after exit
+        terminal.clear()?;
+    }
     terminal.show_cursor()?;
     Ok(())
 }
 
 use tracing_appender::rolling;
 
-fn setup_logging() -> Result<()> {
-    let cache_dir = cache_dir().context("Could not determine XDG cache directory")?;
-    let log_dir = cache_dir.join("simple-stt");
+/// Events logged before `setup_logging`, replayed into the log file once it's set up
+#[derive(Clone, Default)]
+struct EarlyLogs(Arc<Mutex<Vec<(tracing::Level, String)>>>);
+
+impl EarlyLogs {
+    fn replay(self) {
+        for (level, message) in self.0.lock().unwrap().drain(..) {
+            match level {
+                tracing::Level::ERROR => tracing::error!("{}", message),
+                tracing::Level::WARN => tracing::warn!("{}", message),
+                tracing::Level::INFO => tracing::info!("{}", message),
+                tracing::Level::DEBUG => tracing::debug!("{}", message),
+                tracing::Level::TRACE => tracing::trace!("{}", message),
+            }
+        }
+    }
+}
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for EarlyLogs {
+    fn on_event(
+        &self,
+        event: &tracing::Event<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let metadata = event.metadata();
+        let mut message = format!("{}:", metadata.target());
+        event.record(
+            &mut |field: &tracing::field::Field, value: &dyn std::fmt::Debug| match field.name() {
+                "message" => message.push_str(&format!(" {value:?}")),
+                name => message.push_str(&format!(" {name}={value:?}")),
+            },
+        );
+        self.0.lock().unwrap().push((*metadata.level(), message));
+    }
+}
+
+fn setup_logging(config: &LogConfig) -> Result<()> {
+    let log_dir = match &config.dir {
+        Some(dir) => PathBuf::from(shellexpand::tilde(dir).as_ref()),
+        None => Config::default_log_dir(),
+    };
     std::fs::create_dir_all(&log_dir)
         .with_context(|| format!("Failed to create log directory: {log_dir:?}"))?;
     let log_file = rolling::daily(log_dir, "simple-stt.log");
-    let log_level = "debug"; // Changed to debug for more verbose logging
-    let log_filter = EnvFilter::try_from_default_env()
-        .or_else(|_| EnvFilter::try_new(log_level))
-        .unwrap();
+    let log_filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) => EnvFilter::try_new(&config.level)
+            .with_context(|| format!("Invalid log level: {}", config.level))?,
+    };
+
+    let log_layer = match config.format.as_str() {
+        "json" => fmt::layer().json().with_writer(log_file).boxed(),
+        _ => fmt::layer().with_writer(log_file).boxed(),
+    };
 
     tracing_subscriber::registry()
-        .with(fmt::layer().with_writer(log_file).with_filter(log_filter))
+        .with(log_layer.with_filter(log_filter))
         .init();
 
     Ok(())
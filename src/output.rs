@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tracing::{debug, warn};
+
+use crate::config::OutputConfig;
+
+/// Plain-text running log of finished transcriptions, meant to be followed with `tail -f`
+pub struct TranscriptFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    timestamp: bool,
+}
+
+impl TranscriptFile {
+    /// Open the configured output file for appending, `None` when `output.file` isn't set
+    pub fn from_config(config: &OutputConfig) -> Result<Option<Self>> {
+        match &config.file {
+            Some(path) => {
+                let path = PathBuf::from(shellexpand::tilde(&path.to_string_lossy()).as_ref());
+                Self::open(&path, config.timestamp).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn open(path: &Path, timestamp: bool) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create output directory: {parent:?}"))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open output file: {path:?}"))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            timestamp,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a transcription as one line, flushed right away so a tail updates immediately
+    pub fn append(&mut self, text: &str) -> Result<()> {
+        if self.timestamp {
+            write!(
+                self.writer,
+                "[{}] ",
+                Local::now().format("%Y-%m-%d %H:%M:%S")
+            )?;
+        }
+        writeln!(self.writer, "{}", text.trim())?;
+        self.writer
+            .flush()
+            .with_context(|| format!("Failed to write output file: {:?}", self.path))
+    }
+}
+
+/// Where a refinement with an LLM profile goes, from the profile's `output`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProfileOutput {
+    Clipboard,
+    File(PathBuf),   // Appended as a line, like output.file
+    Command(String), // Run through `sh -c` with the text on stdin
+}
+
+impl ProfileOutput {
+    /// Parse "clipboard", "file:<path>" or "command:<shell command>", the clipboard when unset
+    pub fn parse(output: Option<&str>) -> Result<Self> {
+        let Some(spec) = output.map(str::trim).filter(|spec| *spec != "clipboard") else {
+            return Ok(Self::Clipboard);
+        };
+        match spec.split_once(':') {
+            Some(("file", path)) if !path.trim().is_empty() => Ok(Self::File(PathBuf::from(
+                shellexpand::tilde(path.trim()).as_ref(),
+            ))),
+            Some(("command", command)) if !command.trim().is_empty() => {
+                Ok(Self::Command(command.trim().to_string()))
+            }
+            _ => Err(anyhow::anyhow!(
+                "Unknown profile output {spec:?}, expected \"clipboard\", \"file:<path>\" or \"command:<shell command>\""
+            )),
+        }
+    }
+
+    /// Append the text to the file or start the command. The clipboard is left to the
+    /// caller's `ClipboardManager`; a command runs in the background and failures are logged.
+    pub fn deliver(&self, text: &str) -> Result<()> {
+        match self {
+            Self::Clipboard => Ok(()),
+            Self::File(path) => TranscriptFile::open(path, false)?.append(text),
+            Self::Command(command) => {
+                debug!("Sending refinement to `{}`", command);
+                let mut child = Command::new("sh")
+                    .args(["-c", command])
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .spawn()
+                    .with_context(|| format!("Failed to run `{command}`"))?;
+                let mut stdin = child.stdin.take().context("Command has no stdin")?;
+                let text = text.to_string();
+                let command = command.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = stdin.write_all(text.as_bytes()) {
+                        warn!("Failed to send text to `{}`: {}", command, e);
+                    }
+                    drop(stdin); // Close it so the command sees the end of the text
+                    match child.wait() {
+                        Ok(status) if status.success() => {}
+                        Ok(status) => warn!("Output command `{}` exited with {}", command, status),
+                        Err(e) => warn!("Failed to run output command `{}`: {}", command, e),
+                    }
+                });
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for ProfileOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Clipboard => write!(f, "clipboard"),
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Command(command) => write!(f, "`{command}`"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_transcripts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes").join("dictation.txt");
+
+        let mut output = TranscriptFile::open(&path, false).unwrap();
+        output.append("first note\n").unwrap();
+        // Written immediately, without dropping the writer
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first note\n");
+
+        let mut output = TranscriptFile::open(&path, true).unwrap();
+        output.append("second note").unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        let last = content.lines().last().unwrap();
+        assert!(
+            last.starts_with('[') && last.ends_with("] second note"),
+            "{last}"
+        );
+        assert_eq!(content.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_profile_output() {
+        assert_eq!(
+            ProfileOutput::parse(None).unwrap(),
+            ProfileOutput::Clipboard
+        );
+        assert_eq!(
+            ProfileOutput::parse(Some("clipboard")).unwrap(),
+            ProfileOutput::Clipboard
+        );
+        assert_eq!(
+            ProfileOutput::parse(Some("command: todo.sh add")).unwrap(),
+            ProfileOutput::Command("todo.sh add".to_string())
+        );
+        assert!(ProfileOutput::parse(Some("file:")).is_err());
+        assert!(ProfileOutput::parse(Some("printer")).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("drafts.txt");
+        let output = ProfileOutput::parse(Some(&format!("file:{}", path.display()))).unwrap();
+        output.deliver("Dear Sam").unwrap();
+        output.deliver("Hi all").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "Dear Sam\nHi all\n"
+        );
+    }
+}
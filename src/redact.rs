@@ -0,0 +1,40 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether transcript text goes into the logs, set from `log.transcripts` at startup
+static LOG_TRANSCRIPTS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_log_transcripts(enabled: bool) {
+    LOG_TRANSCRIPTS.store(enabled, Ordering::Relaxed);
+}
+
+/// Transcript text as it should appear in the logs: quoted with `log.transcripts`, otherwise
+/// only its length and a hash, enough to tell entries apart without storing what was said
+pub fn transcript(text: &str) -> String {
+    if LOG_TRANSCRIPTS.load(Ordering::Relaxed) {
+        format!("\"{text}\"")
+    } else {
+        redacted(text)
+    }
+}
+
+fn redacted(text: &str) -> String {
+    // FNV-1a, stable across runs unlike the std hasher
+    let hash = text.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+    format!("<{} chars, #{hash:08x}>", text.chars().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacted_hides_text() {
+        let redacted = redacted("my password is hunter2");
+        assert!(redacted.starts_with("<22 chars, #"));
+        assert!(!redacted.contains("hunter2"));
+        assert_eq!(redacted, super::redacted("my password is hunter2"));
+        assert_ne!(redacted, super::redacted("my password is hunter3"));
+    }
+}
@@ -0,0 +1,208 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::debug;
+
+use crate::config::Config;
+use crate::tui::app::TranscriptionResult;
+
+const STATS_FILE: &str = "stats.json";
+
+/// Usage counters for a session, also accumulated across runs in the cache directory
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    pub recordings: u64,
+    pub audio_secs: f64,
+    pub transcriptions: u64, // Finished jobs, including no-speech results and errors
+    pub no_speech: u64,
+    pub errors: u64,
+    pub total_latency_ms: u64,         // Of the transcriptions with text
+    pub models: BTreeMap<String, u64>, // Transcriptions per model
+    pub bench_runs: u64,               // WER is only known for `bench` runs with a reference
+    pub total_wer: f64,
+}
+
+impl Stats {
+    /// Path of the persisted all-time stats
+    pub fn path() -> PathBuf {
+        Config::cache_dir().join(STATS_FILE)
+    }
+
+    pub fn record_recording(&mut self, audio_secs: f64) {
+        self.recordings += 1;
+        self.audio_secs += audio_secs;
+    }
+
+    /// Count a finished transcription job, cancelled jobs are ignored. Results without
+    /// text are counted for `model`, text for the model that transcribed it.
+    pub fn record_transcription(&mut self, model: &str, result: &TranscriptionResult) {
+        let model = match result {
+            TranscriptionResult::Cancelled => return,
+            TranscriptionResult::NoSpeech => {
+                self.no_speech += 1;
+                model
+            }
+            TranscriptionResult::Error(_) => {
+                self.errors += 1;
+                model
+            }
+            TranscriptionResult::Text(transcription) => {
+                self.total_latency_ms += transcription.duration_ms;
+                &transcription.model
+            }
+        };
+        self.transcriptions += 1;
+        *self.models.entry(model.to_string()).or_default() += 1;
+    }
+
+    pub fn record_wer(&mut self, wer: f64) {
+        self.bench_runs += 1;
+        self.total_wer += wer;
+    }
+
+    pub fn average_latency(&self) -> Option<Duration> {
+        let texts = self
+            .transcriptions
+            .saturating_sub(self.no_speech + self.errors);
+        (texts > 0).then(|| Duration::from_millis(self.total_latency_ms / texts))
+    }
+
+    pub fn average_wer(&self) -> Option<f64> {
+        (self.bench_runs > 0).then(|| self.total_wer / self.bench_runs as f64)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recordings == 0 && self.transcriptions == 0 && self.bench_runs == 0
+    }
+
+    /// Add another set of counters to this one
+    pub fn merge(&mut self, other: &Stats) {
+        self.recordings += other.recordings;
+        self.audio_secs += other.audio_secs;
+        self.transcriptions += other.transcriptions;
+        self.no_speech += other.no_speech;
+        self.errors += other.errors;
+        self.total_latency_ms += other.total_latency_ms;
+        for (model, count) in &other.models {
+            *self.models.entry(model.clone()).or_default() += count;
+        }
+        self.bench_runs += other.bench_runs;
+        self.total_wer += other.total_wer;
+    }
+
+    /// Human-readable summary, one stat per line
+    pub fn summary(&self) -> String {
+        let mut lines = vec![
+            format!("Recordings:      {}", self.recordings),
+            format!("Audio recorded:  {:.1}s", self.audio_secs),
+            format!(
+                "Transcriptions:  {} ({} no speech, {} errors)",
+                self.transcriptions, self.no_speech, self.errors
+            ),
+        ];
+        if let Some(latency) = self.average_latency() {
+            lines.push(format!("Avg latency:     {:.2}s", latency.as_secs_f64()));
+        }
+        if !self.models.is_empty() {
+            let models: Vec<String> = self
+                .models
+                .iter()
+                .map(|(model, count)| format!("{model} ({count})"))
+                .collect();
+            lines.push(format!("Models:          {}", models.join(", ")));
+        }
+        if let Some(wer) = self.average_wer() {
+            lines.push(format!(
+                "Avg WER:         {:.2}% over {} bench runs",
+                wer * 100.0,
+                self.bench_runs
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// Load stats from a file, empty if it doesn't exist yet
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read stats file: {path:?}"))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse stats file: {path:?}"))
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create stats directory: {parent:?}"))?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write stats file: {path:?}"))?;
+        debug!("Stats saved to: {:?}", path);
+        Ok(())
+    }
+
+    /// Add these stats to the all-time totals in `path`, returning the new totals
+    pub fn persist_to(&self, path: &Path) -> Result<Stats> {
+        let mut totals = Self::load_from(path)?;
+        totals.merge(self);
+        totals.save_to(path)?;
+        Ok(totals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stt::Transcription;
+
+    #[test]
+    fn test_record_and_summary() {
+        let mut stats = Stats::default();
+        assert!(stats.is_empty());
+        assert_eq!(stats.average_latency(), None);
+
+        stats.record_recording(2.5);
+        for duration_ms in [400, 800] {
+            let text = TranscriptionResult::Text(Transcription {
+                duration_ms,
+                ..Transcription::new("hello".to_string(), "base.en")
+            });
+            stats.record_transcription("small.en", &text);
+        }
+        stats.record_transcription("base.en", &TranscriptionResult::NoSpeech);
+        stats.record_transcription("tiny.en", &TranscriptionResult::Cancelled);
+
+        assert_eq!(stats.transcriptions, 3);
+        assert_eq!(stats.no_speech, 1);
+        assert_eq!(stats.average_latency(), Some(Duration::from_millis(600)));
+        assert_eq!(stats.models.get("tiny.en"), None);
+        assert_eq!(stats.models.get("small.en"), None);
+
+        let summary = stats.summary();
+        assert!(summary.contains("Recordings:      1"));
+        assert!(summary.contains("base.en (3)"));
+        assert!(!summary.contains("WER"));
+    }
+
+    #[test]
+    fn test_persist_accumulates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STATS_FILE);
+
+        let mut session = Stats::default();
+        session.record_recording(1.0);
+        session.record_wer(0.1);
+        session.persist_to(&path).unwrap();
+        let totals = session.persist_to(&path).unwrap();
+
+        assert_eq!(totals.recordings, 2);
+        assert_eq!(totals.audio_secs, 2.0);
+        assert_eq!(totals.bench_runs, 2);
+        assert_eq!(Stats::load_from(&path).unwrap(), totals);
+    }
+}
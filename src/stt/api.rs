@@ -1,62 +1,186 @@
 use anyhow::{Context, Result};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
 use reqwest::multipart;
 use serde_json::Value;
 use std::path::Path;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 use tokio::sync::mpsc::Sender as TokioSender;
-use tracing::info; // New: Import TokioSender
+use tracing::{debug, info, warn}; // New: Import TokioSender
 
-use crate::config::{Config, WhisperConfig};
+use crate::config::{Config, UploadFormat, WhisperConfig};
+use crate::redact;
+use crate::stt::{api_error_message, send_log, wav_utils, Transcription};
+use crate::text::Vocabulary;
+
+const OPENAI_TRANSCRIPTION_URL: &str = "https://api.openai.com/v1/audio/transcriptions";
+
+/// Bitrate for Opus uploads, plenty for speech
+const OPUS_BITRATE: i32 = 24_000;
+
+/// Opus uploads are encoded at whisper's own sample rate, in 20 ms frames
+const OPUS_SAMPLE_RATE: u32 = 16_000;
+const OPUS_FRAME_LEN: usize = OPUS_SAMPLE_RATE as usize / 50;
+
+/// Ogg Opus granule positions count 48 kHz samples whatever the encoding rate
+const OPUS_GRANULE_RATE: u64 = 48_000;
+const OGG_SERIAL: u32 = 1;
 
 pub struct ApiSttBackend {
     config: WhisperConfig,
     client: reqwest::Client,
+    endpoint: String,
+    service_name: &'static str,
+    requires_api_key: bool,
+    prompt: Option<String>, // From whisper.vocab_file
 }
 
 impl ApiSttBackend {
+    /// Backend for the OpenAI Whisper API
     pub fn new(config: &Config) -> Result<Self> {
+        Self::openai(&config.whisper)
+    }
+
+    /// Backend for the OpenAI Whisper API from just the whisper settings
+    pub(crate) fn openai(config: &WhisperConfig) -> Result<Self> {
+        Self::with_endpoint(config, OPENAI_TRANSCRIPTION_URL, "OpenAI API", true)
+    }
+
+    /// Backend for an OpenAI-compatible whisper server (e.g. the whisper.cpp HTTP server)
+    pub fn server(config: &Config) -> Result<Self> {
+        Self::with_endpoint(
+            &config.whisper,
+            &config.whisper.server_url,
+            "Whisper server",
+            false,
+        )
+    }
+
+    fn with_endpoint(
+        config: &WhisperConfig,
+        endpoint: &str,
+        service_name: &'static str,
+        requires_api_key: bool,
+    ) -> Result<Self> {
         let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(config.whisper.timeout))
+            .connect_timeout(Duration::from_secs(config.connect_timeout))
+            .timeout(Duration::from_secs(config.request_timeout))
             .build()
             .context("Failed to create HTTP client")?;
 
         Ok(Self {
-            config: config.whisper.clone(),
+            config: config.clone(),
             client,
+            endpoint: endpoint.to_string(),
+            service_name,
+            requires_api_key,
+            prompt: Vocabulary::load(config.vocab_file.as_deref())?.prompt(),
         })
     }
 
     pub fn is_configured(&self) -> bool {
-        self.config.api_key.is_some()
+        !self.requires_api_key || self.config.api_key.is_some()
     }
 
     pub fn model(&self) -> &str {
         &self.config.model
     }
 
+    pub fn set_language(&mut self, language: Option<String>) {
+        self.config.language = language;
+    }
+
     pub async fn transcribe<P: AsRef<Path>>(
         &self,
         audio_path: P,
         log_tx: Option<TokioSender<String>>,
-    ) -> Result<Option<String>> {
-        let audio_path = audio_path.as_ref();
+    ) -> Result<Option<Transcription>> {
+        let result = self
+            .request_transcription(audio_path.as_ref(), "json", &log_tx)
+            .await?;
 
-        if !audio_path.exists() {
-            return Err(anyhow::anyhow!("Audio file not found: {:?}", audio_path));
+        let text = result
+            .get("text")
+            .and_then(|v| v.as_str())
+            .map(|s| s.trim().to_string())
+            .context("No text found in API response")?;
+
+        if text.is_empty() {
+            info!("❌ No speech detected in audio");
+            send_log(
+                &log_tx,
+                "API Transcription: No speech detected.".to_string(),
+            )
+            .await;
+            Ok(None)
+        } else {
+            info!(
+                "✅ API transcription successful: {}",
+                redact::transcript(&text)
+            );
+            Ok(Some(Transcription {
+                language: self.config.language.clone(),
+                ..Transcription::new(text, &self.config.model)
+            }))
         }
+    }
+
+    /// Transcribe an audio file into `(text, start_secs, end_secs)` segments
+    pub async fn transcribe_segments<P: AsRef<Path>>(
+        &self,
+        audio_path: P,
+        log_tx: Option<TokioSender<String>>,
+    ) -> Result<Vec<(String, f64, f64)>> {
+        let result = self
+            .request_transcription(audio_path.as_ref(), "verbose_json", &log_tx)
+            .await?;
 
-        let api_key = self.config.api_key
-            .as_ref()
-            .context("OpenAI API key not configured. Set OPENAI_API_KEY environment variable or configure in config file")?;
+        let segments = result
+            .get("segments")
+            .and_then(|v| v.as_array())
+            .context("No segments found in API response")?
+            .iter()
+            .filter_map(|segment| {
+                let text = segment.get("text")?.as_str()?.trim().to_string();
+                let start = segment.get("start")?.as_f64()?;
+                let end = segment.get("end")?.as_f64()?;
+                (!text.is_empty()).then_some((text, start, end))
+            })
+            .collect();
 
-        info!(
-            "🔄 Transcribing audio file with OpenAI API: {:?}",
-            audio_path
-        );
+        Ok(segments)
+    }
+
+    /// The bytes to upload with their file name and MIME type, Opus encoded when
+    /// `upload_format` asks for it and the encoding works, otherwise the file as-is
+    async fn read_upload(
+        &self,
+        audio_path: &Path,
+        log_tx: &Option<TokioSender<String>>,
+    ) -> Result<(Vec<u8>, &'static str, &'static str)> {
+        if self.config.upload_format == UploadFormat::Opus {
+            let encode_start = Instant::now();
+            match encode_opus(audio_path).await {
+                Ok(data) => {
+                    debug!(
+                        "Encoded {} bytes of Opus in {} ms",
+                        data.len(),
+                        encode_start.elapsed().as_millis()
+                    );
+                    return Ok((data, "audio.ogg", "audio/ogg"));
+                }
+                Err(e) => {
+                    warn!("Opus encoding failed, uploading WAV: {:#}", e);
+                    send_log(
+                        log_tx,
+                        format!("Opus encoding failed, uploading WAV: {e:#}"),
+                    )
+                    .await;
+                }
+            }
+        }
 
-        // Read audio file
         let mut file = File::open(audio_path)
             .await
             .context("Failed to open audio file")?;
@@ -65,64 +189,234 @@ impl ApiSttBackend {
         file.read_to_end(&mut audio_data)
             .await
             .context("Failed to read audio file")?;
+        Ok((audio_data, "audio.wav", "audio/wav"))
+    }
+
+    /// Upload an audio file to the transcription endpoint and return the parsed JSON response
+    async fn request_transcription(
+        &self,
+        audio_path: &Path,
+        response_format: &str,
+        log_tx: &Option<TokioSender<String>>,
+    ) -> Result<Value> {
+        if !audio_path.exists() {
+            return Err(anyhow::anyhow!("Audio file not found: {:?}", audio_path));
+        }
+
+        if !self.is_configured() {
+            return Err(anyhow::anyhow!("OpenAI API key not configured. Set OPENAI_API_KEY environment variable or configure in config file"));
+        }
+
+        info!(
+            "🔄 Transcribing audio file with {}: {:?}",
+            self.service_name, audio_path
+        );
+
+        let (audio_data, file_name, mime_type) = self.read_upload(audio_path, log_tx).await?;
 
         // Prepare multipart form
+        let audio_data_len = audio_data.len();
         let part = multipart::Part::bytes(audio_data)
-            .file_name("audio.wav")
-            .mime_str("audio/wav")
+            .file_name(file_name)
+            .mime_str(mime_type)
             .context("Failed to set MIME type")?;
 
         let mut form = multipart::Form::new()
             .part("file", part)
-            .text("model", "whisper-1"); // Use API model name
+            .text("model", "whisper-1") // Use API model name
+            .text("response_format", response_format.to_string());
 
         // Add language if specified
         if let Some(ref language) = self.config.language {
             form = form.text("language", language.clone());
         }
+        if let Some(ref prompt) = self.prompt {
+            form = form.text("prompt", prompt.clone());
+        }
 
-        // Make API request
-        let response = self
-            .client
-            .post("https://api.openai.com/v1/audio/transcriptions")
-            .header("Authorization", format!("Bearer {api_key}"))
-            .multipart(form)
+        send_log(
+            log_tx,
+            format!(
+                "Uploading {:.1} KB to {}...",
+                audio_data_len as f64 / 1024.0,
+                self.service_name
+            ),
+        )
+        .await;
+        let request_start = Instant::now();
+
+        // Make API request, the key is optional for self-hosted servers
+        let mut request = self.client.post(&self.endpoint).multipart(form);
+        if let Some(ref api_key) = self.config.api_key {
+            request = request.header("Authorization", format!("Bearer {api_key}"));
+        }
+        let response = request
             .send()
             .await
             .context("Failed to send transcription request")?;
 
+        send_log(
+            log_tx,
+            format!(
+                "{} responded in {} ms",
+                self.service_name,
+                request_start.elapsed().as_millis()
+            ),
+        )
+        .await;
+
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
-            let error_msg = format!("OpenAI API request failed with status {status}: {error_text}");
-            if let Some(tx) = log_tx {
-                tx.send(error_msg.clone()).await.ok();
-            }
+            let key_hint = if self.requires_api_key {
+                "OPENAI_API_KEY or whisper.api_key"
+            } else {
+                "whisper.api_key"
+            };
+            let error_msg = api_error_message(self.service_name, key_hint, status, &error_text);
+            send_log(log_tx, error_msg.clone()).await;
             return Err(anyhow::anyhow!(error_msg));
         }
 
-        let result: Value = response
+        response
             .json()
             .await
-            .context("Failed to parse JSON response")?;
+            .context("Failed to parse JSON response")
+    }
+}
 
-        let text = result
-            .get("text")
-            .and_then(|v| v.as_str())
-            .map(|s| s.trim().to_string())
-            .context("No text found in API response")?;
+/// Encode an audio file as mono Ogg Opus
+async fn encode_opus(audio_path: &Path) -> Result<Vec<u8>> {
+    let audio_path = audio_path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let samples = wav_utils::load_mono(&audio_path, OPUS_SAMPLE_RATE)?;
+        encode_ogg_opus(&samples)
+    })
+    .await
+    .context("Opus encoding task failed")?
+}
 
-        if text.is_empty() {
-            info!("❌ No speech detected in audio");
-            if let Some(tx) = log_tx {
-                tx.send("API Transcription: No speech detected.".to_string())
-                    .await
-                    .ok();
-            }
-            Ok(None)
+/// Encode 16 kHz mono samples as an Ogg Opus stream (RFC 7845)
+pub(super) fn encode_ogg_opus(samples: &[f32]) -> Result<Vec<u8>> {
+    let mut encoder = opus::Encoder::new(
+        OPUS_SAMPLE_RATE,
+        opus::Channels::Mono,
+        opus::Application::Voip,
+    )
+    .context("Failed to create Opus encoder")?;
+    encoder.set_bitrate(opus::Bitrate::Bits(OPUS_BITRATE))?;
+    let to_granule = |samples: usize| samples as u64 * OPUS_GRANULE_RATE / OPUS_SAMPLE_RATE as u64;
+    // The encoder's delay, which decoders skip at the start and padding covers at the end
+    let lookahead = encoder.get_lookahead()? as usize;
+
+    let mut writer = PacketWriter::new(Vec::new());
+    let pre_skip = to_granule(lookahead) as u16;
+    writer.write_packet(
+        opus_head(pre_skip),
+        OGG_SERIAL,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+    writer.write_packet(opus_tags(), OGG_SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+
+    let frames = (samples.len() + lookahead).div_ceil(OPUS_FRAME_LEN).max(1);
+    let mut frame = [0.0f32; OPUS_FRAME_LEN];
+    let mut packet = [0u8; 4000];
+    for index in 0..frames {
+        let start = (index * OPUS_FRAME_LEN).min(samples.len());
+        let end = (start + OPUS_FRAME_LEN).min(samples.len());
+        frame.fill(0.0);
+        frame[..end - start].copy_from_slice(&samples[start..end]);
+        let len = encoder
+            .encode_float(&frame, &mut packet)
+            .context("Failed to encode Opus")?;
+
+        let (end_info, granule) = if index + 1 == frames {
+            // The last granule position trims the padding off again
+            let granule = to_granule(lookahead + samples.len());
+            (PacketWriteEndInfo::EndStream, granule)
         } else {
-            info!("✅ API transcription successful: \"{}\"", text);
-            Ok(Some(text))
+            let granule = to_granule((index + 1) * OPUS_FRAME_LEN);
+            (PacketWriteEndInfo::NormalPacket, granule)
+        };
+        writer.write_packet(packet[..len].to_vec(), OGG_SERIAL, end_info, granule)?;
+    }
+    Ok(writer.into_inner())
+}
+
+/// Ogg Opus identification header for a mono stream
+fn opus_head(pre_skip: u16) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // Version
+    head.push(1); // Channels
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&OPUS_SAMPLE_RATE.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // Output gain
+    head.push(0); // Channel mapping family
+    head
+}
+
+/// Ogg Opus comment header, with no comments
+fn opus_tags() -> Vec<u8> {
+    let vendor = concat!("simple-stt-rs ", env!("CARGO_PKG_VERSION"));
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_ogg_opus_round_trip() {
+        let samples: Vec<f32> = (0..OPUS_SAMPLE_RATE as usize)
+            .map(|i| {
+                let t = i as f32 / OPUS_SAMPLE_RATE as f32;
+                0.3 * (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+            })
+            .collect();
+        let encoded = encode_ogg_opus(&samples).unwrap();
+        // Far smaller than the 16-bit WAV it replaces
+        assert!(
+            encoded.len() < samples.len() * 2 / 5,
+            "{} bytes",
+            encoded.len()
+        );
+
+        let mut reader = ogg::PacketReader::new(Cursor::new(encoded));
+        let head = reader.read_packet_expected().unwrap();
+        assert!(head.data.starts_with(b"OpusHead"));
+        let pre_skip = u16::from_le_bytes([head.data[10], head.data[11]]) as usize / 3;
+        assert!(reader
+            .read_packet_expected()
+            .unwrap()
+            .data
+            .starts_with(b"OpusTags"));
+
+        let mut decoder = opus::Decoder::new(OPUS_SAMPLE_RATE, opus::Channels::Mono).unwrap();
+        let mut decoded = Vec::new();
+        let mut last_granule = 0;
+        while let Some(packet) = reader.read_packet().unwrap() {
+            let mut frame = [0.0f32; OPUS_FRAME_LEN];
+            let len = decoder
+                .decode_float(&packet.data, &mut frame, false)
+                .unwrap();
+            decoded.extend_from_slice(&frame[..len]);
+            last_granule = packet.absgp_page();
         }
+        assert_eq!(last_granule as usize / 3 - pre_skip, samples.len());
+
+        let decoded = &decoded[pre_skip..pre_skip + samples.len()];
+        let rms =
+            |audio: &[f32]| (audio.iter().map(|s| s * s).sum::<f32>() / audio.len() as f32).sqrt();
+        let (original, round_trip) = (rms(&samples), rms(decoded));
+        assert!(
+            (round_trip - original).abs() < original * 0.2,
+            "RMS {round_trip} vs {original}"
+        );
     }
 }
@@ -2,17 +2,70 @@ use anyhow::{Context, Result};
 use hf_hub::api::tokio::Api;
 use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tempfile;
+use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc::Sender as TokioSender; // Import TokioSender
 use tracing::{debug, info, warn};
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters}; // Import Read trait for reading from gag
-
-use crate::config::{Config, WhisperConfig};
+use whisper_rs::{
+    FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState,
+}; // Import Read trait for reading from gag
+
+use crate::audio::speech_duration;
+use crate::config::{AudioConfig, Config, Sampling, WhisperConfig};
+use crate::redact;
+use crate::stt::{send_log, wav_utils, Transcription};
+use crate::text::Vocabulary;
+
+/// Whisper expects 16kHz mono input
+const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+const MB: u64 = 1_000_000;
+const GB: u64 = 1000 * MB;
+
+/// Approximate download size in MB of common ggml models
+const MODEL_SIZES_MB: &[(&str, u32)] = &[
+    ("tiny", 75),
+    ("tiny.en", 75),
+    ("base", 142),
+    ("base.en", 142),
+    ("small", 466),
+    ("small.en", 466),
+    ("medium", 1500),
+    ("medium.en", 1500),
+    ("large", 2900),
+    ("large-v2", 2900),
+    ("large-v3", 2900),
+    ("large-v3-turbo", 1600),
+];
+
+/// Length of the clip transcribed by `benchmark_on_load`. Whisper pads every input to
+/// a 30 s window, so anything shorter would be timed as if it were a full window.
+const BENCHMARK_SECS: u32 = 30;
+
+/// Models at least this big are painfully slow without a GPU
+const LARGE_MODEL_SIZE: u64 = GB;
 
 pub struct LocalSttBackend {
     config: WhisperConfig,
     context: Option<WhisperContext>,
     preparation_status: PreparationStatus,
+    cancel_flag: Option<Arc<AtomicBool>>,
+    silence_threshold: f32, // audio.silence_threshold, for retry_on_empty's speech check
+    last_transcript: Mutex<Option<String>>, // Prompt for the next transcription with use_context
+    state: Mutex<Option<WhisperState>>, // Decoding state kept between transcriptions
+    ms_per_audio_sec: Option<f32>, // Measured by benchmark_on_load
+    vocab_prompt: Option<String>, // Initial prompt from whisper.vocab_file
+}
+
+/// Cleaned segments of a whisper run, with what it found out about them
+#[derive(Debug, Default)]
+struct Decoded {
+    segments: Vec<(String, f64, f64)>,
+    language: Option<String>, // Language code whisper detected or was told
+    confidence: Option<f32>,  // Mean probability of the kept segments' tokens
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +83,12 @@ impl LocalSttBackend {
             config: config.whisper.clone(),
             context: None,
             preparation_status: PreparationStatus::NotStarted,
+            cancel_flag: None,
+            silence_threshold: config.audio.silence_threshold,
+            last_transcript: Mutex::new(None),
+            state: Mutex::new(None),
+            ms_per_audio_sec: None,
+            vocab_prompt: Vocabulary::load(config.whisper.vocab_file.as_deref())?.prompt(),
         })
     }
 
@@ -80,8 +139,13 @@ impl LocalSttBackend {
         // Load the model (this can be slow, so we do it during preparation)
         let ctx_params = WhisperContextParameters::default();
 
+        // On a blocking thread so the runtime stays responsive. If the load is cancelled
+        // the task finishes on its own and the context it returns is dropped.
+        let path = model_path.to_string_lossy().into_owned();
         let result =
-            WhisperContext::new_with_params(model_path.to_string_lossy().as_ref(), ctx_params);
+            tokio::task::spawn_blocking(move || WhisperContext::new_with_params(&path, ctx_params))
+                .await
+                .context("Model loading task failed")?;
 
         // Restore stderr
         drop(stderr_gag);
@@ -91,6 +155,15 @@ impl LocalSttBackend {
                 info!("✅ Whisper model loaded successfully");
                 self.context = Some(context);
                 self.preparation_status = PreparationStatus::Ready;
+                if self.config.benchmark_on_load {
+                    match self.benchmark() {
+                        Ok(speed) => {
+                            info!("Benchmark: ~{:.0} ms per second of audio", speed);
+                            self.ms_per_audio_sec = Some(speed);
+                        }
+                        Err(e) => warn!("Benchmark failed: {:#}", e),
+                    }
+                }
                 Ok(())
             }
             Err(e) => {
@@ -104,6 +177,22 @@ impl LocalSttBackend {
         }
     }
 
+    /// Set the flag that aborts whisper mid-run and skips remaining segments
+    pub fn set_cancel_flag(&mut self, cancel_flag: Option<Arc<AtomicBool>>) {
+        self.cancel_flag = cancel_flag;
+    }
+
+    /// Follow changes to `audio.silence_threshold` made since the backend was created
+    pub fn set_silence_threshold(&mut self, threshold: f32) {
+        self.silence_threshold = threshold;
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_flag
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
     /// Check if the backend is ready for transcription
     pub fn is_configured(&self) -> bool {
         matches!(self.preparation_status, PreparationStatus::Ready) && self.context.is_some()
@@ -126,11 +215,110 @@ impl LocalSttBackend {
         &self.config.model
     }
 
+    /// Change the transcription language, it's a per-run parameter so the model stays loaded
+    pub fn set_language(&mut self, language: Option<String>) {
+        self.config.language = language;
+    }
+
+    /// Transcribe a full window of synthetic audio and return the milliseconds taken per second
+    /// of audio. The state it allocates is kept for the first real transcription.
+    fn benchmark(&self) -> Result<f32> {
+        let context = self.context.as_ref().context("Model not loaded")?;
+        let clip = benchmark_clip();
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(self.config.language.as_deref());
+        params.set_n_threads(self.config.thread_count() as i32);
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+
+        let stderr_gag = gag::Redirect::stderr(tempfile::tempfile()?)?;
+        let start = Instant::now();
+        let mut state = context
+            .create_state()
+            .context("Failed to create whisper state")?;
+        let result = state.full(params, &clip);
+        let elapsed = start.elapsed();
+        drop(stderr_gag);
+        result.context("Failed to run Whisper transcription")?;
+
+        *self.state.lock().unwrap() = Some(state);
+        Ok(ms_per_audio_sec(elapsed, BENCHMARK_SECS))
+    }
+
+    /// Milliseconds per second of audio measured by `benchmark_on_load`
+    pub fn ms_per_audio_sec(&self) -> Option<f32> {
+        self.ms_per_audio_sec
+    }
+
+    /// Free the loaded model, `prepare` loads it again. Returns whether one was loaded.
+    pub fn unload(&mut self) -> bool {
+        // The decoding state holds on to the model too
+        *self.state.lock().unwrap() = None;
+        self.preparation_status = PreparationStatus::NotStarted;
+        self.context.take().is_some()
+    }
+
+    /// Whether the model was never loaded or was unloaded
+    pub fn is_unloaded(&self) -> bool {
+        matches!(self.preparation_status, PreparationStatus::NotStarted)
+    }
+
+    /// Forget the previous transcription so the next one starts without context
+    pub fn reset_context(&self) {
+        *self.last_transcript.lock().unwrap() = None;
+    }
+
     pub async fn transcribe<P: AsRef<Path>>(
         &self,
         audio_path: P,
         log_tx: Option<TokioSender<String>>,
-    ) -> Result<Option<String>> {
+    ) -> Result<Option<Transcription>> {
+        let decoded = self.decode(audio_path, log_tx).await?;
+
+        let text = decoded
+            .segments
+            .iter()
+            .map(|(text, _, _)| text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let text = collapse_repeats(&text, self.config.collapse_repeats);
+
+        if text.is_empty() {
+            info!("❌ No speech detected in audio");
+            Ok(None)
+        } else {
+            info!(
+                "✅ Local transcription successful: {}",
+                redact::transcript(&text)
+            );
+            if self.config.use_context {
+                *self.last_transcript.lock().unwrap() = Some(text.clone());
+            }
+            Ok(Some(Transcription {
+                language: decoded.language,
+                confidence: decoded.confidence,
+                ..Transcription::new(text, &self.config.model)
+            }))
+        }
+    }
+
+    /// Transcribe an audio file into cleaned `(text, start_secs, end_secs)` segments
+    pub async fn transcribe_segments<P: AsRef<Path>>(
+        &self,
+        audio_path: P,
+        log_tx: Option<TokioSender<String>>,
+    ) -> Result<Vec<(String, f64, f64)>> {
+        Ok(self.decode(audio_path, log_tx).await?.segments)
+    }
+
+    async fn decode<P: AsRef<Path>>(
+        &self,
+        audio_path: P,
+        log_tx: Option<TokioSender<String>>,
+    ) -> Result<Decoded> {
         let audio_path = audio_path.as_ref();
 
         if !audio_path.exists() {
@@ -149,20 +337,73 @@ impl LocalSttBackend {
         info!("🔄 Transcribing audio file locally: {:?}", audio_path);
 
         // Convert audio to required format (16kHz mono f32)
-        let audio_data = load_audio_file(audio_path).await?;
+        let audio_data = wav_utils::load_mono(audio_path, WHISPER_SAMPLE_RATE)?;
 
         if audio_data.is_empty() {
             warn!("Audio file appears to be empty or invalid");
-            return Ok(None);
+            return Ok(Decoded::default());
         }
 
         debug!("Audio data loaded: {} samples", audio_data.len());
+        send_log(
+            &log_tx,
+            format!(
+                "Loaded {} samples ({:.1}s of audio)",
+                audio_data.len(),
+                audio_data.len() as f32 / WHISPER_SAMPLE_RATE as f32
+            ),
+        )
+        .await;
+
+        let sampling = self.config.sampling();
+        let decoded = self
+            .run_whisper(context, &audio_data, sampling, &log_tx)
+            .await?;
+        if decoded.segments.is_empty()
+            && self.config.retry_on_empty
+            && has_speech(&audio_data, self.silence_threshold)
+        {
+            // A single greedy pass sometimes misses clear speech, decoding differently often doesn't
+            let fallback = sampling.fallback();
+            info!("No text from {}, retrying with {}", sampling, fallback);
+            send_log(
+                &log_tx,
+                format!("🔁 No text but the audio has speech, retrying with {fallback}"),
+            )
+            .await;
+            return self
+                .run_whisper(context, &audio_data, fallback, &log_tx)
+                .await;
+        }
+        Ok(decoded)
+    }
 
+    /// One whisper pass over 16kHz mono audio with the given sampling, returning the
+    /// cleaned segments
+    async fn run_whisper(
+        &self,
+        context: &WhisperContext,
+        audio_data: &[f32],
+        sampling: Sampling,
+        log_tx: &Option<TokioSender<String>>,
+    ) -> Result<Decoded> {
         // Use the prepared context directly (no need for spawn_blocking since context is already loaded)
         let language = self.config.language.clone();
 
         // Setup transcription parameters
-        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        let strategy = match sampling {
+            Sampling::Greedy { best_of } => SamplingStrategy::Greedy {
+                best_of: best_of as i32,
+            },
+            Sampling::BeamSearch {
+                beam_size,
+                patience,
+            } => SamplingStrategy::BeamSearch {
+                beam_size: beam_size as i32,
+                patience,
+            },
+        };
+        let mut params = FullParams::new(strategy);
 
         if let Some(ref lang) = language {
             params.set_language(Some(lang));
@@ -172,22 +413,95 @@ impl LocalSttBackend {
         params.set_print_progress(false);
         params.set_print_realtime(false);
         params.set_print_timestamps(false);
-        params.set_no_context(true); // Disable context from previous transcriptions
+        params.set_no_context(!self.config.use_context);
+        // Suppression keeps tokens out of the decoder output, filter_tokens then strips
+        // whole markers like [BLANK_AUDIO] that whisper still writes as text
+        params.set_suppress_blank(self.config.suppress_blank);
+        params.set_suppress_non_speech_tokens(self.config.suppress_non_speech);
+        let mut prompt = self.vocab_prompt.clone().unwrap_or_default();
+        if self.config.use_context {
+            // Continue from the previous transcription for multi-segment dictation
+            if let Some(previous) = self.last_transcript.lock().unwrap().as_deref() {
+                debug!(
+                    "Using previous transcription as prompt: {}",
+                    redact::transcript(previous)
+                );
+                if !prompt.is_empty() {
+                    prompt.push_str(". ");
+                }
+                prompt.push_str(previous);
+            }
+        }
+        if !prompt.is_empty() {
+            params.set_initial_prompt(&prompt);
+        }
+        let n_threads = self.config.thread_count();
+        params.set_n_threads(n_threads as i32);
         params.set_single_segment(false); // Allow multiple segments
 
-        debug!("Running Whisper transcription...");
+        // Abort on cancel, or when a huge buffer or slow CPU run exceeds local_timeout
+        let cancel_flag = self.cancel_flag.clone();
+        let deadline = (self.config.local_timeout > 0)
+            .then(|| Instant::now() + Duration::from_secs(self.config.local_timeout));
+        let timed_out = move || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        params.set_abort_callback_safe(move || {
+            timed_out()
+                || cancel_flag
+                    .as_ref()
+                    .is_some_and(|flag| flag.load(Ordering::Relaxed))
+        });
+
+        info!("Running Whisper transcription on {} threads", n_threads);
+        send_log(
+            log_tx,
+            format!(
+                "Running whisper ({}, {} threads)...",
+                self.config.model, n_threads
+            ),
+        )
+        .await;
+        let whisper_start = Instant::now();
 
         // Suppress stderr from the C++ library during transcription and capture it
         let temp_file = tempfile::tempfile()?;
         let stderr_gag = gag::Redirect::stderr(temp_file)?;
 
+        // Reuse the state from the last run, whisper_full resets it. It's taken out of the
+        // mutex for the run and put back at the end, so a failed run just drops it.
+        let state_start = Instant::now();
+        let cached_state = self.state.lock().unwrap().take();
+        let reused = cached_state.is_some();
+        let mut state = match cached_state {
+            Some(state) => state,
+            None => context
+                .create_state()
+                .context("Failed to create whisper state")?,
+        };
+        debug!(
+            "Whisper state {} in {} ms",
+            if reused { "reused" } else { "created" },
+            state_start.elapsed().as_millis()
+        );
+
         // Run transcription using the prepared context
-        let mut state = context
-            .create_state()
-            .context("Failed to create whisper state")?;
-        state
-            .full(params, &audio_data)
-            .context("Failed to run Whisper transcription")?;
+        let result = state.full(params, audio_data);
+        if self.is_cancelled() {
+            return Err(anyhow::anyhow!("Transcription cancelled"));
+        }
+        // A run that finished just past the deadline still counts, only an aborted one timed out
+        if result.is_err() && timed_out() {
+            return Err(anyhow::anyhow!(
+                "Local transcription timed out after {}s",
+                self.config.local_timeout
+            ));
+        }
+        result.context("Failed to run Whisper transcription")?;
+        let language = state
+            .full_lang_id_from_state()
+            .ok()
+            .and_then(whisper_rs::get_lang_str)
+            .map(str::to_string);
+        debug!("Detected language: {:?}", language);
 
         // Read captured stderr and send it as a log message
         let mut captured_stderr = String::new();
@@ -195,12 +509,12 @@ impl LocalSttBackend {
             .into_inner()
             .read_to_string(&mut captured_stderr)?;
 
-        if let Some(tx) = log_tx {
-            if !captured_stderr.trim().is_empty() {
-                tx.send(format!("Whisper stderr: {}", captured_stderr.trim()))
-                    .await
-                    .ok();
-            }
+        if !captured_stderr.trim().is_empty() {
+            send_log(
+                log_tx,
+                format!("Whisper stderr: {}", captured_stderr.trim()),
+            )
+            .await;
         }
 
         // Extract text using the state
@@ -209,37 +523,129 @@ impl LocalSttBackend {
             .context("Failed to get number of segments")?;
 
         debug!("Transcription completed: {} segments", num_segments);
-
-        let mut result = String::new();
+        send_log(
+            log_tx,
+            format!(
+                "Whisper finished in {} ms ({} state): got {} segments",
+                whisper_start.elapsed().as_millis(),
+                if reused { "reused" } else { "new" },
+                num_segments
+            ),
+        )
+        .await;
+
+        let mut segments = Vec::new();
+        let mut kept_probs = Vec::new();
         for i in 0..num_segments {
+            if self.is_cancelled() {
+                return Err(anyhow::anyhow!("Transcription cancelled"));
+            }
             let segment = state
                 .full_get_segment_text(i)
                 .context("Failed to get segment text")?;
 
-            debug!("Raw segment {}: \"{}\"", i, segment);
+            debug!("Raw segment {}: {}", i, redact::transcript(&segment));
+
+            let probs = text_token_probs(&state, i, context.token_eot())?;
+            if self.config.no_speech_threshold > 0.0 {
+                let no_speech = no_speech_estimate(&probs);
+                if no_speech > self.config.no_speech_threshold {
+                    debug!(
+                        "Dropped segment {} as likely non-speech ({:.2}): {}",
+                        i,
+                        no_speech,
+                        redact::transcript(&segment)
+                    );
+                    if self.config.debug_segments {
+                        send_log(
+                            log_tx,
+                            format!(
+                                "Segment {i}: dropped \"{segment}\" (no speech {no_speech:.2})"
+                            ),
+                        )
+                        .await;
+                    }
+                    continue;
+                }
+            }
 
             // Filter out Whisper special tokens and unwanted content
-            let cleaned_segment = clean_whisper_output(&segment);
+            let cleaned_segment = if self.config.filter_output {
+                clean_whisper_output(&segment, &self.config.filter_tokens)
+            } else {
+                segment.trim().to_string()
+            };
+            if self.config.debug_segments {
+                send_log(
+                    log_tx,
+                    format!("Segment {i}: raw \"{segment}\" → cleaned \"{cleaned_segment}\""),
+                )
+                .await;
+            }
             if !cleaned_segment.is_empty() {
-                result.push_str(&cleaned_segment);
-                debug!("Added cleaned segment {}: \"{}\"", i, cleaned_segment);
+                debug!(
+                    "Added cleaned segment {}: {}",
+                    i,
+                    redact::transcript(&cleaned_segment)
+                );
+                // Segment timestamps are reported in centiseconds
+                let start = state
+                    .full_get_segment_t0(i)
+                    .context("Failed to get segment start time")?;
+                let end = state
+                    .full_get_segment_t1(i)
+                    .context("Failed to get segment end time")?;
+                segments.push((cleaned_segment, start as f64 / 100.0, end as f64 / 100.0));
+                kept_probs.extend(probs);
             } else {
-                debug!("Filtered out segment {}: \"{}\"", i, segment);
+                debug!(
+                    "Filtered out segment {}: {}",
+                    i,
+                    redact::transcript(&segment)
+                );
             }
         }
 
-        let text = result.trim().to_string();
-
-        if text.is_empty() {
-            info!("❌ No speech detected in audio");
-            Ok(None)
-        } else {
-            info!("✅ Local transcription successful: \"{}\"", text);
-            Ok(Some(text))
-        }
+        let confidence = (!kept_probs.is_empty())
+            .then(|| kept_probs.iter().sum::<f32>() / kept_probs.len() as f32);
+        *self.state.lock().unwrap() = Some(state);
+        Ok(Decoded {
+            segments,
+            language,
+            confidence,
+        })
     }
 }
 
+/// Speech an empty result needs before `retry_on_empty` decodes it again
+const RETRY_MIN_SPEECH: Duration = Duration::from_millis(250);
+
+/// Whether 16kHz mono audio has enough speech above `silence_threshold` that an empty
+/// transcription is likely a decoding miss
+fn has_speech(audio: &[f32], silence_threshold: f32) -> bool {
+    let config = AudioConfig {
+        sample_rate: WHISPER_SAMPLE_RATE,
+        channels: 1,
+        silence_threshold,
+        ..AudioConfig::default()
+    };
+    speech_duration(audio, &config) >= RETRY_MIN_SPEECH
+}
+
+/// A quiet 440Hz tone, so the benchmark runs the decoder rather than bailing out on silence
+fn benchmark_clip() -> Vec<f32> {
+    (0..WHISPER_SAMPLE_RATE * BENCHMARK_SECS)
+        .map(|i| {
+            let t = i as f32 / WHISPER_SAMPLE_RATE as f32;
+            0.05 * (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+        })
+        .collect()
+}
+
+fn ms_per_audio_sec(elapsed: Duration, audio_secs: u32) -> f32 {
+    elapsed.as_secs_f32() * 1000.0 / audio_secs.max(1) as f32
+}
+
 /// Download a Whisper model from Hugging Face
 async fn download_model(model_name: &str, model_path: &Path) -> Result<()> {
     info!("📥 Downloading {} from Hugging Face...", model_name);
@@ -253,15 +659,15 @@ async fn download_model(model_name: &str, model_path: &Path) -> Result<()> {
 
     info!("🌐 Fetching model file: {}", filename);
 
-    // Download the model file
-    let model_file = repo
-        .get(&filename)
-        .await
-        .with_context(|| format!("Failed to download model file: {filename}"))?;
-
-    // Copy the downloaded file to the target location
+    // Stream into a partial file of our own rather than through hf-hub's cache, so a
+    // cancelled download leaves nothing behind that remove_partial_download doesn't know of
     debug!("💾 Saving model to: {:?}", model_path);
-    tokio::fs::copy(&model_file, &model_path)
+    let part_path = partial_path(model_path);
+    if let Err(e) = fetch_to_file(&repo.url(&filename), &part_path).await {
+        tokio::fs::remove_file(&part_path).await.ok();
+        return Err(e.context(format!("Failed to download model file: {filename}")));
+    }
+    tokio::fs::rename(&part_path, &model_path)
         .await
         .context("Failed to save model file")?;
 
@@ -271,180 +677,217 @@ async fn download_model(model_name: &str, model_path: &Path) -> Result<()> {
         .context("Failed to verify downloaded model")?;
 
     info!(
-        "✅ Model downloaded successfully: {:.1} MB",
-        metadata.len() as f64 / 1024.0 / 1024.0
+        "✅ Model downloaded successfully: {}",
+        format_size(metadata.len())
     );
 
     Ok(())
 }
 
-/// Get the path where the model should be located
-fn get_model_path(config: &WhisperConfig) -> Result<PathBuf> {
-    if let Some(ref path) = config.model_path {
-        let expanded = shellexpand::tilde(path);
-        Ok(PathBuf::from(expanded.as_ref()))
-    } else {
-        // Default model path in cache directory
-        let cache_dir = dirs::cache_dir()
-            .or_else(|| dirs::home_dir().map(|h| h.join(".cache")))
-            .unwrap_or_else(std::env::temp_dir);
-
-        let model_dir = cache_dir.join("simple-stt").join("models");
-        let model_file = format!("ggml-{}.bin", config.model);
-
-        Ok(model_dir.join(model_file))
+/// How long a model download may take to connect, and then to receive each chunk
+const DOWNLOAD_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const DOWNLOAD_READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Write the body of a GET request to `path`, giving up on a connection that stalls
+async fn fetch_to_file(url: &str, path: &Path) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .connect_timeout(DOWNLOAD_CONNECT_TIMEOUT)
+        .build()
+        .context("Failed to create HTTP client")?;
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .context("Failed to save model file")?;
+    while let Some(chunk) = tokio::time::timeout(DOWNLOAD_READ_TIMEOUT, response.chunk())
+        .await
+        .context("Download stalled")??
+    {
+        file.write_all(&chunk)
+            .await
+            .context("Failed to save model file")?;
     }
+    file.flush().await.context("Failed to save model file")?;
+    Ok(())
 }
 
-/// Load and convert audio file to the format required by Whisper (16kHz mono f32)
-async fn load_audio_file<P: AsRef<Path>>(audio_path: P) -> Result<Vec<f32>> {
-    let audio_path = audio_path.as_ref();
-
-    debug!("Loading audio file: {:?}", audio_path);
+/// Human-readable size in the decimal units models are published with
+pub fn format_size(bytes: u64) -> String {
+    if bytes >= GB {
+        format!("{:.1} GB", bytes as f64 / GB as f64)
+    } else {
+        format!("{:.0} MB", bytes as f64 / MB as f64)
+    }
+}
 
-    // Use hound to read the WAV file
-    let reader = hound::WavReader::open(audio_path).context("Failed to open audio file")?;
+/// Approximate download size of a model in bytes, if it's a known model
+pub fn model_size(model_name: &str) -> Option<u64> {
+    MODEL_SIZES_MB
+        .iter()
+        .find(|(name, _)| *name == model_name)
+        .map(|&(_, mb)| u64::from(mb) * MB)
+}
 
-    let spec = reader.spec();
-    debug!("Audio spec: {:?}", spec);
+/// Warnings to confirm before switching the local backend to `model_name`: the download
+/// size when the model isn't cached yet, and the speed of large models on the CPU
+pub fn model_download_warning(config: &WhisperConfig, model_name: &str) -> Option<String> {
+    if config.backend != "local" {
+        return None;
+    }
+    let size = model_size(model_name);
+    let mut warnings = Vec::new();
 
-    // Read samples based on the bit depth
-    let samples: Result<Vec<f32>, _> = match spec.bits_per_sample {
-        16 => reader
-            .into_samples::<i16>()
-            .map(|s| s.map(|sample| sample as f32 / 32768.0))
-            .collect(),
-        32 => {
-            if spec.sample_format == hound::SampleFormat::Float {
-                reader.into_samples::<f32>().collect()
-            } else {
-                reader
-                    .into_samples::<i32>()
-                    .map(|s| s.map(|sample| sample as f32 / 2147483648.0))
-                    .collect()
-            }
-        }
-        24 => {
-            // 24-bit samples are stored as i32 but only use 24 bits
-            reader
-                .into_samples::<i32>()
-                .map(|s| s.map(|sample| (sample >> 8) as f32 / 8388608.0))
-                .collect()
-        }
-        8 => {
-            // Convert 8-bit unsigned to signed first
-            reader
-                .into_samples::<i8>()
-                .map(|s| s.map(|sample| sample as f32 / 128.0))
-                .collect()
-        }
-        _ => {
-            return Err(anyhow::anyhow!(
-                "Unsupported bit depth: {} bits",
-                spec.bits_per_sample
-            ));
-        }
+    let target = WhisperConfig {
+        model: model_name.to_string(),
+        ..config.clone()
     };
+    let cached = get_model_path(&target).is_ok_and(|path| path.exists());
+    if !cached && config.download_models {
+        match size {
+            Some(bytes) => warnings.push(format!(
+                "{model_name} is not cached and needs a ~{} download",
+                format_size(bytes)
+            )),
+            None => warnings.push(format!("{model_name} is not cached and will be downloaded")),
+        }
+    }
+    if config.device == "cpu" && size.is_some_and(|bytes| bytes >= LARGE_MODEL_SIZE) {
+        warnings.push(format!(
+            "{model_name} will be very slow with device = \"cpu\""
+        ));
+    }
 
-    let mut samples = samples.context("Failed to read audio samples")?;
+    (!warnings.is_empty()).then(|| warnings.join("\n"))
+}
 
-    debug!("Read {} samples", samples.len());
+/// Where a model is written while it's being saved
+fn partial_path(model_path: &Path) -> PathBuf {
+    let mut path = model_path.as_os_str().to_owned();
+    path.push(".part");
+    PathBuf::from(path)
+}
 
-    // Calculate min/max and RMS for debugging
-    if !samples.is_empty() {
-        let min_val = samples.iter().cloned().fold(f32::INFINITY, f32::min);
-        let max_val = samples.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
-        let sum_squares: f32 = samples.iter().map(|&s| s * s).sum();
-        let rms = (sum_squares / samples.len() as f32).sqrt();
-        debug!(
-            "Raw f32 samples - Min: {:.4}, Max: {:.4}, RMS: {:.4}",
-            min_val, max_val, rms
-        );
+/// Remove what's left of an interrupted model download, returning whether there was anything
+pub fn remove_partial_download(config: &WhisperConfig) -> Result<bool> {
+    let part_path = partial_path(&get_model_path(config)?);
+    if !part_path.exists() {
+        return Ok(false);
     }
+    std::fs::remove_file(&part_path)
+        .with_context(|| format!("Failed to remove partial download: {part_path:?}"))?;
+    info!("Removed partial model download: {:?}", part_path);
+    Ok(true)
+}
 
-    // Convert stereo to mono if necessary
-    if spec.channels == 2 {
-        debug!("Converting stereo to mono");
-        samples = samples
-            .chunks(2)
-            .map(|chunk| (chunk[0] + chunk[1]) / 2.0)
-            .collect();
-    } else if spec.channels != 1 {
+/// Delete the downloaded model file so the next load downloads it again, returning
+/// whether there was one. Files set with `model_path` are the user's and left alone.
+pub fn remove_cached_model(config: &WhisperConfig) -> Result<bool> {
+    if config.model_path.is_some() {
         return Err(anyhow::anyhow!(
-            "Unsupported number of channels: {}",
-            spec.channels
+            "Not deleting a custom model_path, replace the file yourself"
         ));
     }
-
-    // Resample to 16kHz if necessary
-    if spec.sample_rate != 16000 {
-        debug!("Resampling from {} Hz to 16000 Hz", spec.sample_rate);
-        samples = resample_audio(samples, spec.sample_rate, 16000)?;
+    let model_path = get_model_path(config)?;
+    if !model_path.exists() {
+        return Ok(false);
     }
+    std::fs::remove_file(&model_path)
+        .with_context(|| format!("Failed to remove cached model: {model_path:?}"))?;
+    info!("Removed cached model: {:?}", model_path);
+    Ok(true)
+}
 
-    debug!("Final audio: {} samples at 16kHz mono", samples.len());
+/// A downloaded model file in the models directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedModel {
+    pub name: String, // Model name as used in whisper.model, e.g. "base.en"
+    pub path: PathBuf,
+    pub size: u64,
+}
 
-    Ok(samples)
+/// Downloaded models in the cache directory, sorted by name. Interrupted downloads count
+/// too, named with a `.part` suffix.
+pub fn cached_models() -> Result<Vec<CachedModel>> {
+    cached_models_in(&Config::models_dir())
 }
 
-/// Simple linear resampling (not high quality, but sufficient for speech)
-fn resample_audio(input: Vec<f32>, input_rate: u32, output_rate: u32) -> Result<Vec<f32>> {
-    if input_rate == output_rate {
-        return Ok(input);
+fn cached_models_in(dir: &Path) -> Result<Vec<CachedModel>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut models = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to read {dir:?}"))? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let Some(name) = file_name.strip_prefix("ggml-") else {
+            continue;
+        };
+        let name = match name.strip_suffix(".bin.part") {
+            Some(name) => format!("{name}.part"),
+            None => match name.strip_suffix(".bin") {
+                Some(name) => name.to_string(),
+                None => continue,
+            },
+        };
+        models.push(CachedModel {
+            name,
+            path: entry.path(),
+            size: entry.metadata()?.len(),
+        });
     }
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(models)
+}
 
-    let ratio = input_rate as f64 / output_rate as f64;
-    let output_len = (input.len() as f64 / ratio) as usize;
-    let mut output = Vec::with_capacity(output_len);
+/// Get the path where the model should be located
+fn get_model_path(config: &WhisperConfig) -> Result<PathBuf> {
+    if let Some(ref path) = config.model_path {
+        let expanded = shellexpand::tilde(path);
+        Ok(PathBuf::from(expanded.as_ref()))
+    } else {
+        // Default model path in cache directory
+        let model_file = format!("ggml-{}.bin", config.model);
 
-    for i in 0..output_len {
-        let src_index = (i as f64 * ratio) as usize;
-        if src_index < input.len() {
-            output.push(input[src_index]);
-        } else {
-            output.push(0.0);
+        Ok(Config::models_dir().join(model_file))
+    }
+}
+
+/// Probabilities of a segment's text tokens, special tokens (timestamps etc.) excluded
+fn text_token_probs(state: &whisper_rs::WhisperState, segment: i32, eot: i32) -> Result<Vec<f32>> {
+    let n_tokens = state
+        .full_n_tokens(segment)
+        .context("Failed to get segment tokens")?;
+    let mut probs = Vec::new();
+    for token in 0..n_tokens {
+        let id = state
+            .full_get_token_id(segment, token)
+            .context("Failed to get token id")?;
+        if id < eot {
+            probs.push(
+                state
+                    .full_get_token_prob(segment, token)
+                    .context("Failed to get token probability")?,
+            );
         }
     }
+    Ok(probs)
+}
 
-    Ok(output)
+/// How likely a segment is a hallucination on silence, from its token confidence.
+/// whisper.cpp doesn't expose its own no_speech_prob per segment, but hallucinated
+/// "Thank you"s come out with low confidence, so one minus the mean is a good stand-in.
+fn no_speech_estimate(token_probs: &[f32]) -> f32 {
+    if token_probs.is_empty() {
+        return 1.0;
+    }
+    1.0 - token_probs.iter().sum::<f32>() / token_probs.len() as f32
 }
 
 /// Clean Whisper output by removing special tokens and unwanted markers
-fn clean_whisper_output(text: &str) -> String {
+fn clean_whisper_output(text: &str, unwanted_tokens: &[String]) -> String {
     let text = text.trim();
 
-    // List of Whisper special tokens to filter out
-    let unwanted_tokens = [
-        "[BLANK_AUDIO]",
-        "[blank_audio]",
-        "[MUSIC]",
-        "[music]",
-        "[NOISE]",
-        "[noise]",
-        "[SILENCE]",
-        "[silence]",
-        "[SPEAKING]",
-        "[speaking]",
-        "[SOUND]",
-        "[sound]",
-        "[BEEP]",
-        "[beep]",
-        "[APPLAUSE]",
-        "[applause]",
-        "[LAUGHTER]",
-        "[laughter]",
-        "[COUGH]",
-        "[cough]",
-        "(blank)",
-        "(BLANK)",
-        "(no audio)",
-        "(NO AUDIO)",
-        "inaudible",
-        "INAUDIBLE",
-    ];
-
     // Check if the entire segment is just a special token
-    for token in &unwanted_tokens {
+    for token in unwanted_tokens {
         if text.eq_ignore_ascii_case(token) {
             return String::new(); // Return empty string for pure special tokens
         }
@@ -452,9 +895,9 @@ fn clean_whisper_output(text: &str) -> String {
 
     // Remove special tokens that appear within text
     let mut cleaned = text.to_string();
-    for token in &unwanted_tokens {
+    for token in unwanted_tokens {
         // Remove exact matches (case insensitive)
-        cleaned = cleaned.replace(token, "");
+        cleaned = cleaned.replace(token.as_str(), "");
         cleaned = cleaned.replace(&token.to_lowercase(), "");
         cleaned = cleaned.replace(&token.to_uppercase(), "");
     }
@@ -476,3 +919,243 @@ fn clean_whisper_output(text: &str) -> String {
 
     cleaned
 }
+
+/// Collapse a phrase repeated at least `threshold` times in a row into a single copy,
+/// catching whisper hallucination loops like "Thank you. Thank you. Thank you."
+/// Words are compared ignoring case and punctuation. A threshold below 2 disables it.
+fn collapse_repeats(text: &str, threshold: usize) -> String {
+    if threshold < 2 {
+        return text.to_string();
+    }
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let keys: Vec<String> = words
+        .iter()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .collect();
+
+    let mut kept = Vec::with_capacity(words.len());
+    let mut i = 0;
+    while i < words.len() {
+        // Shortest phrase first, so "a a a a" collapses to "a" rather than "a a"
+        let run = (1..=(words.len() - i) / threshold).find_map(|n| {
+            let phrase = &keys[i..i + n];
+            let repeats = 1 + keys[i + n..]
+                .chunks_exact(n)
+                .take_while(|chunk| *chunk == phrase)
+                .count();
+            (repeats >= threshold).then_some((n, repeats))
+        });
+        match run {
+            Some((n, repeats)) => {
+                debug!(
+                    "Collapsed {} repeats of {}",
+                    repeats,
+                    redact::transcript(&words[i..i + n].join(" "))
+                );
+                kept.extend_from_slice(&words[i..i + n]);
+                i += n * repeats;
+            }
+            None => {
+                kept.push(words[i]);
+                i += 1;
+            }
+        }
+    }
+    kept.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_model_path() {
+        let config = WhisperConfig {
+            model: "tiny.en".to_string(),
+            model_path: None,
+            ..WhisperConfig::default()
+        };
+        let path = get_model_path(&config).unwrap();
+        assert!(path.ends_with(
+            Path::new("simple-stt")
+                .join("models")
+                .join("ggml-tiny.en.bin")
+        ));
+        assert!(path.is_absolute());
+    }
+
+    #[test]
+    fn test_custom_model_path() {
+        let config = WhisperConfig {
+            model_path: Some("/opt/models/custom.bin".to_string()),
+            ..WhisperConfig::default()
+        };
+        assert_eq!(
+            get_model_path(&config).unwrap(),
+            PathBuf::from("/opt/models/custom.bin")
+        );
+    }
+
+    #[test]
+    fn test_no_speech_estimate() {
+        assert_eq!(no_speech_estimate(&[]), 1.0);
+        assert!((no_speech_estimate(&[0.9, 0.95, 0.85]) - 0.1).abs() < 1e-6);
+        assert!(no_speech_estimate(&[0.2, 0.3]) > 0.6);
+    }
+
+    #[test]
+    fn test_cached_models_in() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("ggml-small.en.bin"), b"small").unwrap();
+        std::fs::write(dir.path().join("ggml-base.bin"), b"base").unwrap();
+        std::fs::write(dir.path().join("ggml-large.bin.part"), b"la").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"not a model").unwrap();
+
+        let models = cached_models_in(dir.path()).unwrap();
+        let names: Vec<_> = models.iter().map(|model| model.name.as_str()).collect();
+        assert_eq!(names, ["base", "large.part", "small.en"]);
+        assert_eq!(models[0].size, 4);
+        assert!(cached_models_in(&dir.path().join("missing"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_remove_partial_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let model_path = dir.path().join("ggml-large.bin");
+        let config = WhisperConfig {
+            model_path: Some(model_path.to_string_lossy().to_string()),
+            ..WhisperConfig::default()
+        };
+        assert!(!remove_partial_download(&config).unwrap());
+
+        std::fs::write(dir.path().join("ggml-large.bin.part"), b"partial").unwrap();
+        assert!(remove_partial_download(&config).unwrap());
+        assert!(!dir.path().join("ggml-large.bin.part").exists());
+    }
+
+    #[test]
+    fn test_model_download_warning() {
+        let dir = tempfile::tempdir().unwrap();
+        let cached = dir.path().join("ggml-custom.bin");
+        std::fs::write(&cached, b"model").unwrap();
+        let config = WhisperConfig {
+            model_path: Some(cached.to_string_lossy().to_string()),
+            ..WhisperConfig::default()
+        };
+        assert_eq!(model_download_warning(&config, "large"), None);
+
+        let cpu = WhisperConfig {
+            device: "cpu".to_string(),
+            ..config.clone()
+        };
+        let warning = model_download_warning(&cpu, "large").unwrap();
+        assert!(warning.contains("very slow"));
+        assert_eq!(model_download_warning(&cpu, "base.en"), None);
+
+        let missing = WhisperConfig {
+            model_path: Some(dir.path().join("missing.bin").to_string_lossy().to_string()),
+            ..WhisperConfig::default()
+        };
+        let warning = model_download_warning(&missing, "large").unwrap();
+        assert!(warning.contains("~2.9 GB download"), "{warning}");
+
+        let api = WhisperConfig {
+            backend: "api".to_string(),
+            ..missing
+        };
+        assert_eq!(model_download_warning(&api, "large"), None);
+        assert_eq!(model_size("base.en"), Some(142 * MB));
+        assert_eq!(model_size("unknown"), None);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(75 * MB), "75 MB");
+        assert_eq!(format_size(999 * MB), "999 MB");
+        assert_eq!(format_size(GB), "1.0 GB");
+        assert_eq!(format_size(2900 * MB), "2.9 GB");
+    }
+
+    #[test]
+    fn test_collapse_repeats() {
+        let looped = "So anyway. Thank you. Thank you. thank you! Thank you. Bye";
+        assert_eq!(collapse_repeats(looped, 3), "So anyway. Thank you. Bye");
+        assert_eq!(collapse_repeats("no no no no no", 3), "no");
+        // Below the threshold, or disabled, nothing changes
+        assert_eq!(
+            collapse_repeats("Thank you. Thank you.", 3),
+            "Thank you. Thank you."
+        );
+        assert_eq!(collapse_repeats(looped, 0), looped);
+        assert_eq!(collapse_repeats("", 3), "");
+
+        // The default leaves deliberate repetition alone but still catches a loop
+        let threshold = WhisperConfig::default().collapse_repeats;
+        for said in ["No, no, no, no!", "It's very, very, very good. Bye bye"] {
+            assert_eq!(collapse_repeats(said, threshold), said);
+        }
+        let hallucinated = format!("Done.{}", " Thank you.".repeat(12));
+        assert_eq!(
+            collapse_repeats(&hallucinated, threshold),
+            "Done. Thank you."
+        );
+    }
+
+    #[test]
+    fn test_clean_whisper_output() {
+        let tokens = WhisperConfig::default().filter_tokens;
+        assert_eq!(clean_whisper_output(" [BLANK_AUDIO] ", &tokens), "");
+        assert_eq!(
+            clean_whisper_output("Hello [music] world .", &tokens),
+            "Hello world."
+        );
+        // Custom tokens replace the defaults
+        let tokens = vec!["(chuckles)".to_string()];
+        assert_eq!(
+            clean_whisper_output("[MUSIC] Hi (chuckles)", &tokens),
+            "[MUSIC] Hi"
+        );
+    }
+
+    #[test]
+    fn test_benchmark_speed() {
+        assert_eq!(benchmark_clip().len(), 30 * 16000);
+        assert_eq!(ms_per_audio_sec(Duration::from_millis(15000), 30), 500.0);
+    }
+
+    #[test]
+    fn test_remove_cached_model_keeps_custom_path() {
+        let config = WhisperConfig {
+            model_path: Some("~/models/my-model.bin".to_string()),
+            ..WhisperConfig::default()
+        };
+        assert!(remove_cached_model(&config).is_err());
+    }
+
+    #[test]
+    fn test_has_speech() {
+        let threshold = AudioConfig::default().silence_threshold;
+        assert!(!has_speech(
+            &vec![0.0; WHISPER_SAMPLE_RATE as usize],
+            threshold
+        ));
+        let speech = vec![0.5; WHISPER_SAMPLE_RATE as usize / 2];
+        assert!(has_speech(&speech, threshold));
+        // A click is too short to count
+        let mut click = vec![0.0; WHISPER_SAMPLE_RATE as usize];
+        click[..800].fill(0.5);
+        assert!(!has_speech(&click, threshold));
+
+        // Quiet speech only counts with a threshold tuned for it
+        let quiet = vec![0.05; WHISPER_SAMPLE_RATE as usize / 2];
+        assert!(!has_speech(&quiet, 50.0));
+        assert!(has_speech(&quiet, 1.0));
+    }
+}
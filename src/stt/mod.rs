@@ -1,21 +1,89 @@
 use anyhow::Result;
 use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::mpsc::Sender as TokioSender;
-use tracing::info; // New: Import TokioSender
+use tracing::{info, warn}; // New: Import TokioSender
 
 use crate::config::{Config, WhisperConfig};
 use crate::stt::api::ApiSttBackend;
 use crate::stt::local::LocalSttBackend;
+#[cfg(feature = "vosk")]
+use crate::stt::vosk::VoskSttBackend;
 
 mod api;
 mod local;
+#[cfg(feature = "vosk")]
+mod vosk;
 
+pub use local::{
+    cached_models, format_size, model_download_warning, model_size, remove_cached_model,
+    remove_partial_download, CachedModel,
+};
+
+pub mod subtitles;
 pub mod wav_utils;
+pub mod wer;
+
+/// Send a progress message to the TUI log panel, if a sender was provided
+pub(crate) async fn send_log(log_tx: &Option<TokioSender<String>>, message: String) {
+    if let Some(tx) = log_tx {
+        tx.send(message).await.ok();
+    }
+}
+
+/// Concise message for a failed API response: a rejected key points at `key_hint`, a rate
+/// limit says so, and anything else shows the provider's error message rather than the
+/// whole JSON body
+pub fn api_error_message(
+    service: &str,
+    key_hint: &str,
+    status: reqwest::StatusCode,
+    body: &str,
+) -> String {
+    match status.as_u16() {
+        401 | 403 => format!("🔑 {service} rejected the API key — check {key_hint}"),
+        429 => format!("⏳ {service} rate limit or quota exceeded, try again shortly"),
+        _ => {
+            let detail = serde_json::from_str::<serde_json::Value>(body)
+                .ok()
+                .and_then(|json| json["error"]["message"].as_str().map(str::to_string))
+                .unwrap_or_else(|| body.trim().to_string());
+            format!("{service} request failed with status {status}: {detail}")
+        }
+    }
+}
+
+/// A finished transcription with what's known about how it was made
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transcription {
+    pub text: String,
+    pub language: Option<String>, // Language code detected by whisper, or the configured one
+    pub confidence: Option<f32>,  // Mean token probability (0-1), local backend only
+    pub duration_ms: u64,         // Time spent transcribing
+    pub model: String,
+}
+
+impl Transcription {
+    /// Text from `model`, without metadata yet
+    pub fn new(text: String, model: &str) -> Self {
+        Self {
+            text,
+            language: None,
+            confidence: None,
+            duration_ms: 0,
+            model: model.to_string(),
+        }
+    }
+}
 
 /// Enum representing different STT backend implementations
 pub enum SttBackend {
     Api(ApiSttBackend),
     Local(LocalSttBackend),
+    #[cfg(feature = "vosk")]
+    Vosk(VoskSttBackend),
 }
 
 impl SttBackend {
@@ -27,6 +95,8 @@ impl SttBackend {
                 Ok(())
             }
             SttBackend::Local(backend) => backend.prepare().await,
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(backend) => backend.prepare().await,
         }
     }
 
@@ -35,6 +105,8 @@ impl SttBackend {
         match self {
             SttBackend::Api(backend) => backend.is_configured(),
             SttBackend::Local(backend) => backend.is_configured(),
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(backend) => backend.is_configured(),
         }
     }
 
@@ -43,6 +115,8 @@ impl SttBackend {
         match self {
             SttBackend::Api(_) => false, // API backend is always ready
             SttBackend::Local(backend) => backend.is_preparing(),
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(_) => false, // Loads the model within prepare
         }
     }
 
@@ -51,6 +125,82 @@ impl SttBackend {
         match self {
             SttBackend::Api(_) => None,
             SttBackend::Local(backend) => backend.preparation_failed(),
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(backend) => backend.preparation_failed(),
+        }
+    }
+
+    /// Set the flag that stops a running transcription early, where the backend supports it
+    pub fn set_cancel_flag(&mut self, cancel_flag: Option<Arc<AtomicBool>>) {
+        match self {
+            SttBackend::Local(backend) => backend.set_cancel_flag(cancel_flag),
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(backend) => backend.set_cancel_flag(cancel_flag),
+            // API requests stop when their future is dropped
+            SttBackend::Api(_) => {}
+        }
+    }
+
+    /// Set the level below which audio counts as silence, where the backend checks it
+    pub fn set_silence_threshold(&mut self, threshold: f32) {
+        match self {
+            SttBackend::Local(backend) => backend.set_silence_threshold(threshold),
+            SttBackend::Api(_) => {}
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(_) => {}
+        }
+    }
+
+    /// Change the transcription language without reloading the model
+    pub fn set_language(&mut self, language: Option<String>) {
+        match self {
+            SttBackend::Api(backend) => backend.set_language(language),
+            SttBackend::Local(backend) => backend.set_language(language),
+            // Vosk models are single-language
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(_) => {}
+        }
+    }
+
+    /// Transcription speed measured after loading, in milliseconds per second of audio
+    pub fn ms_per_audio_sec(&self) -> Option<f32> {
+        match self {
+            SttBackend::Local(backend) => backend.ms_per_audio_sec(),
+            SttBackend::Api(_) => None,
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(_) => None,
+        }
+    }
+
+    /// Free the loaded model until the next `prepare`, returning whether memory was freed
+    pub fn unload(&mut self) -> bool {
+        match self {
+            SttBackend::Local(backend) => backend.unload(),
+            // Large Vosk models take a couple of GB, so they are freed like whisper's
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(backend) => backend.unload(),
+            // Nothing is held in memory for the API
+            SttBackend::Api(_) => false,
+        }
+    }
+
+    fn is_unloaded(&self) -> bool {
+        match self {
+            SttBackend::Local(backend) => backend.is_unloaded(),
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(backend) => backend.is_unloaded(),
+            SttBackend::Api(_) => false,
+        }
+    }
+
+    /// Forget context carried over from previous transcriptions
+    pub fn reset_context(&self) {
+        match self {
+            SttBackend::Local(backend) => backend.reset_context(),
+            // Only the local backend keeps context between transcriptions
+            SttBackend::Api(_) => {}
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(_) => {}
         }
     }
 
@@ -59,6 +209,8 @@ impl SttBackend {
         match self {
             SttBackend::Api(backend) => backend.model(),
             SttBackend::Local(backend) => backend.model(),
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(backend) => backend.model(),
         }
     }
 
@@ -67,17 +219,42 @@ impl SttBackend {
         &self,
         audio_path: P,
         log_tx: Option<TokioSender<String>>,
-    ) -> Result<Option<String>> {
-        match self {
+    ) -> Result<Option<Transcription>> {
+        let start = Instant::now();
+        let transcription = match self {
             SttBackend::Api(backend) => backend.transcribe(audio_path, log_tx).await,
             SttBackend::Local(backend) => backend.transcribe(audio_path, log_tx).await,
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(backend) => backend.transcribe(audio_path, log_tx).await,
+        }?;
+        Ok(transcription.map(|transcription| Transcription {
+            duration_ms: start.elapsed().as_millis() as u64,
+            ..transcription
+        }))
+    }
+
+    /// Transcribe an audio file into timestamped `(text, start_secs, end_secs)` segments
+    pub async fn transcribe_segments<P: AsRef<Path>>(
+        &self,
+        audio_path: P,
+        log_tx: Option<TokioSender<String>>,
+    ) -> Result<Vec<(String, f64, f64)>> {
+        match self {
+            SttBackend::Api(backend) => backend.transcribe_segments(audio_path, log_tx).await,
+            SttBackend::Local(backend) => backend.transcribe_segments(audio_path, log_tx).await,
+            #[cfg(feature = "vosk")]
+            SttBackend::Vosk(backend) => backend.transcribe_segments(audio_path, log_tx).await,
         }
     }
 }
 
+/// OpenAI model used when the local backend falls back to the API
+const API_FALLBACK_MODEL: &str = "whisper-1";
+
 pub struct SttProcessor {
     backend: SttBackend,
     config: WhisperConfig,
+    fallback_reason: Option<String>, // Why the local backend was replaced by the API
 }
 
 impl SttProcessor {
@@ -92,6 +269,24 @@ impl SttProcessor {
                 info!("Using local Whisper backend");
                 SttBackend::Local(LocalSttBackend::new(config)?)
             }
+            "server" => {
+                info!(
+                    "Using whisper server backend at {}",
+                    config.whisper.server_url
+                );
+                SttBackend::Api(ApiSttBackend::server(config)?)
+            }
+            #[cfg(feature = "vosk")]
+            "vosk" => {
+                info!("Using Vosk backend");
+                SttBackend::Vosk(VoskSttBackend::new(config)?)
+            }
+            #[cfg(not(feature = "vosk"))]
+            "vosk" => {
+                return Err(anyhow::anyhow!(
+                    "This build has no Vosk support, rebuild with `--features vosk` (needs libvosk installed)"
+                ));
+            }
             backend => {
                 return Err(anyhow::anyhow!("Unknown STT backend: {}", backend));
             }
@@ -100,24 +295,113 @@ impl SttProcessor {
         Ok(Self {
             backend,
             config: config.whisper.clone(),
+            fallback_reason: None,
         })
     }
 
     /// Prepare the backend for transcription (download models, etc.)
-    /// This can be called in parallel with audio recording
+    /// This can be called in parallel with audio recording. With `whisper.fallback_to_api`
+    /// a local backend that fails to prepare is replaced by the OpenAI API.
     pub async fn prepare(&mut self) -> Result<()> {
-        self.backend.prepare().await
+        match self.backend.prepare().await {
+            Err(e) if self.can_fall_back_to_api() => self.fall_back_to_api(e),
+            result => result,
+        }
+    }
+
+    fn can_fall_back_to_api(&self) -> bool {
+        matches!(self.backend, SttBackend::Local(_))
+            && self.config.fallback_to_api
+            && self.config.api_key.is_some()
+    }
+
+    fn fall_back_to_api(&mut self, error: anyhow::Error) -> Result<()> {
+        warn!(
+            "Local backend failed ({:#}), falling back to the OpenAI API",
+            error
+        );
+        let config = WhisperConfig {
+            backend: "api".to_string(),
+            model: API_FALLBACK_MODEL.to_string(),
+            ..self.config.clone()
+        };
+        self.backend = SttBackend::Api(ApiSttBackend::openai(&config)?);
+        self.config = config;
+        self.fallback_reason = Some(format!("{error:#}"));
+        Ok(())
+    }
+
+    /// Why the local backend was replaced by the API, if it was
+    pub fn fallback_reason(&self) -> Option<&str> {
+        self.fallback_reason.as_deref()
+    }
+
+    /// Free the local model's memory while idle, see `ensure_loaded`
+    pub fn unload(&mut self) -> bool {
+        self.backend.unload()
+    }
+
+    /// Load the model again if it was unloaded, returning whether it had to be loaded
+    pub async fn ensure_loaded(&mut self) -> Result<bool> {
+        if !self.backend.is_unloaded() {
+            return Ok(false);
+        }
+        info!("🔄 Reloading {} after it was unloaded", self.model());
+        self.prepare().await.map(|_| true)
     }
 
-    /// Transcribe audio file using the configured backend
+    /// Transcribe audio file using the configured backend, `None` when there was no speech
     pub async fn transcribe<P: AsRef<Path>>(
         &self,
         audio_path: P,
         log_tx: Option<TokioSender<String>>,
-    ) -> Result<Option<String>> {
+    ) -> Result<Option<Transcription>> {
         self.backend.transcribe(audio_path, log_tx).await
     }
 
+    /// Transcribe audio file into timestamped `(text, start_secs, end_secs)` segments
+    pub async fn transcribe_segments<P: AsRef<Path>>(
+        &self,
+        audio_path: P,
+        log_tx: Option<TokioSender<String>>,
+    ) -> Result<Vec<(String, f64, f64)>> {
+        self.backend.transcribe_segments(audio_path, log_tx).await
+    }
+
+    /// Set the flag that stops a running transcription early, where the backend supports it
+    pub fn set_cancel_flag(&mut self, cancel_flag: Option<Arc<AtomicBool>>) {
+        self.backend.set_cancel_flag(cancel_flag);
+    }
+
+    /// Use the silence threshold in effect when a recording was made
+    pub fn set_silence_threshold(&mut self, threshold: f32) {
+        self.backend.set_silence_threshold(threshold);
+    }
+
+    /// Forget context carried over from previous transcriptions
+    pub fn reset_context(&self) {
+        self.backend.reset_context();
+    }
+
+    /// Transcription speed from `whisper.benchmark_on_load`, in ms per second of audio
+    pub fn ms_per_audio_sec(&self) -> Option<f32> {
+        self.backend.ms_per_audio_sec()
+    }
+
+    /// Language to tag `transcription` with when `whisper.tag_language` is on
+    pub fn language_tag<'a>(&self, transcription: &'a Transcription) -> Option<&'a str> {
+        self.config
+            .tag_language
+            .then_some(transcription.language.as_deref())
+            .flatten()
+    }
+
+    /// Change the transcription language without reloading the model
+    pub fn set_language(&mut self, language: Option<String>) {
+        self.config.language = language.clone();
+        self.backend.set_language(language);
+    }
+
     /// Check if the backend is configured and ready
     pub fn is_configured(&self) -> bool {
         self.backend.is_configured()
@@ -144,11 +428,59 @@ impl SttProcessor {
     }
 }
 
+/// Prefix a transcription with its language code, e.g. `[es] hola`
+pub fn tag_language(text: &str, language: &str) -> String {
+    format!("[{language}] {text}")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config::Config;
 
+    #[test]
+    fn test_api_error_message() {
+        let rejected = api_error_message(
+            "OpenAI",
+            "OPENAI_API_KEY",
+            reqwest::StatusCode::UNAUTHORIZED,
+            r#"{"error": {"message": "Incorrect API key provided: sk-abc"}}"#,
+        );
+        assert_eq!(
+            rejected,
+            "🔑 OpenAI rejected the API key — check OPENAI_API_KEY"
+        );
+
+        let failed = api_error_message(
+            "OpenAI",
+            "OPENAI_API_KEY",
+            reqwest::StatusCode::BAD_REQUEST,
+            r#"{"error": {"message": "Audio file is too short", "type": "invalid_request"}}"#,
+        );
+        assert_eq!(
+            failed,
+            "OpenAI request failed with status 400 Bad Request: Audio file is too short"
+        );
+    }
+
+    #[test]
+    fn test_language_tag() {
+        assert_eq!(tag_language("hola", "es"), "[es] hola");
+
+        let mut config = Config::default();
+        config.whisper.backend = "api".to_string();
+        config.whisper.tag_language = true;
+        let mut processor = SttProcessor::new(&config).unwrap();
+        let mut transcription = Transcription::new("hola".to_string(), "whisper-1");
+        // Nothing to tag until the backend reports the language
+        assert_eq!(processor.language_tag(&transcription), None);
+
+        transcription.language = Some("es".to_string());
+        assert_eq!(processor.language_tag(&transcription), Some("es"));
+        processor.config.tag_language = false;
+        assert_eq!(processor.language_tag(&transcription), None);
+    }
+
     #[tokio::test]
     async fn test_stt_processor_creation_api() {
         let mut config = Config::default();
@@ -166,6 +498,71 @@ mod tests {
         assert!(processor.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_stt_processor_creation_server() {
+        let mut config = Config::default();
+        config.whisper.backend = "server".to_string();
+        let processor = SttProcessor::new(&config).unwrap();
+        // Self-hosted servers don't need an API key
+        assert!(processor.is_configured());
+    }
+
+    #[cfg(feature = "vosk")]
+    #[tokio::test]
+    async fn test_stt_processor_creation_vosk() {
+        let mut config = Config::default();
+        config.whisper.backend = "vosk".to_string();
+        let processor = SttProcessor::new(&config).unwrap();
+        // Not configured until a model directory has been located
+        assert!(!processor.is_configured());
+    }
+
+    #[cfg(not(feature = "vosk"))]
+    #[test]
+    fn test_stt_processor_creation_vosk_unsupported() {
+        let mut config = Config::default();
+        config.whisper.backend = "vosk".to_string();
+        let error = SttProcessor::new(&config).err().unwrap();
+        assert!(error.to_string().contains("--features vosk"));
+    }
+
+    fn missing_local_model_config() -> Config {
+        let mut config = Config::default();
+        config.whisper.backend = "local".to_string();
+        config.whisper.model_path = Some("/nonexistent/ggml-missing.bin".to_string());
+        config.whisper.download_models = false;
+        config.whisper.api_key = Some("test-key".to_string());
+        config
+    }
+
+    #[tokio::test]
+    async fn test_local_falls_back_to_api() {
+        let mut config = missing_local_model_config();
+        config.whisper.fallback_to_api = true;
+        let mut processor = SttProcessor::new(&config).unwrap();
+
+        processor.prepare().await.unwrap();
+        assert_eq!(processor.backend_type(), "api");
+        assert_eq!(processor.model(), API_FALLBACK_MODEL);
+        assert!(processor.is_configured());
+        assert!(processor.fallback_reason().unwrap().contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_local_failure_without_fallback() {
+        let mut processor = SttProcessor::new(&missing_local_model_config()).unwrap();
+        assert!(processor.prepare().await.is_err());
+        assert_eq!(processor.backend_type(), "local");
+
+        // Without an API key there's nothing to fall back to
+        let mut config = missing_local_model_config();
+        config.whisper.fallback_to_api = true;
+        config.whisper.api_key = None;
+        let mut processor = SttProcessor::new(&config).unwrap();
+        assert!(processor.prepare().await.is_err());
+        assert_eq!(processor.fallback_reason(), None);
+    }
+
     #[tokio::test]
     async fn test_unknown_backend() {
         let mut config = Config::default();
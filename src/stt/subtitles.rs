@@ -0,0 +1,102 @@
+/// Format seconds as `HH:MM:SS<sep>mmm`
+fn format_timestamp(seconds: f64, millis_separator: char) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    let hours = total_ms / 3_600_000;
+    let minutes = (total_ms / 60_000) % 60;
+    let secs = (total_ms / 1000) % 60;
+    let millis = total_ms % 1000;
+    format!("{hours:02}:{minutes:02}:{secs:02}{millis_separator}{millis:03}")
+}
+
+/// Render `(text, start_secs, end_secs)` segments as SubRip (SRT) subtitles
+pub fn to_srt(segments: &[(String, f64, f64)]) -> String {
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, (text, start, end))| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                i + 1,
+                format_timestamp(*start, ','),
+                format_timestamp(*end, ','),
+                text.trim()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Render `(text, start_secs, end_secs)` segments as WebVTT subtitles
+pub fn to_vtt(segments: &[(String, f64, f64)]) -> String {
+    let mut output = String::from("WEBVTT\n");
+    for (i, (text, start, end)) in segments.iter().enumerate() {
+        output.push_str(&format!(
+            "\n{}\n{} --> {}\n{}\n",
+            i + 1,
+            format_timestamp(*start, '.'),
+            format_timestamp(*end, '.'),
+            text.trim()
+        ));
+    }
+    output
+}
+
+/// Parse an `HH:MM:SS,mmm` (or `HH:MM:SS.mmm`) timestamp into seconds
+fn parse_timestamp(timestamp: &str) -> Option<f64> {
+    let (time, millis) = timestamp.trim().split_once([',', '.'])?;
+    let mut parts = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, secs) = (parts.next()??, parts.next()??, parts.next()??);
+    let millis: u64 = millis.parse().ok()?;
+    Some((hours * 3600 + minutes * 60 + secs) as f64 + millis as f64 / 1000.0)
+}
+
+/// Parse SubRip (SRT) subtitles into `(text, start_secs, end_secs)` segments
+pub fn from_srt(srt: &str) -> Vec<(String, f64, f64)> {
+    srt.replace("\r\n", "\n")
+        .split("\n\n")
+        .filter_map(|block| {
+            let mut lines = block.lines().skip_while(|line| line.trim().is_empty());
+            let _index = lines.next()?;
+            let (start, end) = lines.next()?.split_once("-->")?;
+            let text = lines.collect::<Vec<_>>().join(" ").trim().to_string();
+            (!text.is_empty()).then_some((text, parse_timestamp(start)?, parse_timestamp(end)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_segments() -> Vec<(String, f64, f64)> {
+        vec![
+            ("Hello there.".to_string(), 0.0, 2.5),
+            ("General Kenobi!".to_string(), 3661.25, 3663.004),
+        ]
+    }
+
+    #[test]
+    fn test_to_srt() {
+        let expected = "1\n00:00:00,000 --> 00:00:02,500\nHello there.\n\n\
+                        2\n01:01:01,250 --> 01:01:03,004\nGeneral Kenobi!\n";
+        assert_eq!(to_srt(&two_segments()), expected);
+    }
+
+    #[test]
+    fn test_to_vtt() {
+        let expected = "WEBVTT\n\n1\n00:00:00.000 --> 00:00:02.500\nHello there.\n\n\
+                        2\n01:01:01.250 --> 01:01:03.004\nGeneral Kenobi!\n";
+        assert_eq!(to_vtt(&two_segments()), expected);
+    }
+
+    #[test]
+    fn test_from_srt_round_trip() {
+        assert_eq!(from_srt(&to_srt(&two_segments())), two_segments());
+    }
+
+    #[test]
+    fn test_empty_segments() {
+        assert_eq!(to_srt(&[]), "");
+        assert_eq!(to_vtt(&[]), "WEBVTT\n");
+    }
+}
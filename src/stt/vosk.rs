@@ -0,0 +1,333 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc::Sender as TokioSender;
+use tracing::{debug, info, warn};
+use vosk::{CompleteResult, DecodingState, LogLevel, Model, Recognizer};
+
+use crate::config::{Config, WhisperConfig};
+use crate::redact;
+use crate::stt::{send_log, wav_utils, Transcription};
+
+/// Vosk models are trained on 16kHz mono audio
+const VOSK_SAMPLE_RATE: u32 = 16000;
+
+/// Samples fed to the recognizer at a time, a quarter second
+const CHUNK_SAMPLES: usize = VOSK_SAMPLE_RATE as usize / 4;
+
+pub struct VoskSttBackend {
+    config: WhisperConfig,
+    model_dir: Option<PathBuf>,
+    model: Option<Arc<Model>>,
+    cancel_flag: Option<Arc<AtomicBool>>,
+    preparation_error: Option<String>,
+}
+
+impl VoskSttBackend {
+    /// Create a new VoskSttBackend instance without loading the model
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            config: config.whisper.clone(),
+            model_dir: None,
+            model: None,
+            cancel_flag: None,
+            preparation_error: None,
+        })
+    }
+
+    /// Prepare the backend by locating and loading the Vosk model
+    pub async fn prepare(&mut self) -> Result<()> {
+        if self.model_dir.is_some() {
+            return Ok(()); // Already prepared
+        }
+
+        info!("🔄 Preparing Vosk backend...");
+
+        match self.load_model().await {
+            Ok(model_dir) => {
+                info!("✅ Vosk model loaded from {:?}", model_dir);
+                self.model_dir = Some(model_dir);
+                self.preparation_error = None;
+                Ok(())
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to prepare Vosk backend: {e:#}");
+                warn!("{}", error_msg);
+                self.preparation_error = Some(error_msg.clone());
+                Err(anyhow::anyhow!(error_msg))
+            }
+        }
+    }
+
+    fn locate_model(&self) -> Result<PathBuf> {
+        let model_path = self.config.model_path.as_ref().context(
+            "Vosk backend requires whisper.model_path to point at a Vosk model directory",
+        )?;
+        let model_dir = PathBuf::from(shellexpand::tilde(model_path).as_ref());
+
+        if !model_dir.is_dir() {
+            return Err(anyhow::anyhow!(
+                "Vosk model directory not found: {:?}",
+                model_dir
+            ));
+        }
+
+        Ok(model_dir)
+    }
+
+    async fn load_model(&mut self) -> Result<PathBuf> {
+        let model_dir = self.locate_model()?;
+        let path = model_dir
+            .to_str()
+            .context("Vosk model path is not valid UTF-8")?
+            .to_string();
+        // Kaldi logs every loading step to stderr, which would garble the TUI
+        vosk::set_log_level(LogLevel::Warn);
+        // Loading a large model takes a few seconds
+        let model = tokio::task::spawn_blocking(move || Model::new(path))
+            .await
+            .context("Vosk model loader task failed")?
+            .with_context(|| format!("Failed to load Vosk model from {model_dir:?}"))?;
+        self.model = Some(Arc::new(model));
+        Ok(model_dir)
+    }
+
+    /// Check if the backend is ready for transcription
+    pub fn is_configured(&self) -> bool {
+        self.model_dir.is_some()
+    }
+
+    /// Free the model until the next `prepare`, returning whether memory was freed
+    pub fn unload(&mut self) -> bool {
+        // A transcription in flight keeps its own handle until it finishes
+        self.model = None;
+        self.model_dir.take().is_some()
+    }
+
+    /// Whether the model was never loaded or was unloaded, as opposed to failing to load
+    pub fn is_unloaded(&self) -> bool {
+        self.model_dir.is_none() && self.preparation_error.is_none()
+    }
+
+    /// Check if preparation failed
+    pub fn preparation_failed(&self) -> Option<&str> {
+        self.preparation_error.as_deref()
+    }
+
+    /// Set the flag that stops recognition between chunks
+    pub fn set_cancel_flag(&mut self, cancel_flag: Option<Arc<AtomicBool>>) {
+        self.cancel_flag = cancel_flag;
+    }
+
+    pub fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    pub async fn transcribe<P: AsRef<Path>>(
+        &self,
+        audio_path: P,
+        log_tx: Option<TokioSender<String>>,
+    ) -> Result<Option<Transcription>> {
+        let segments = self.transcribe_segments(audio_path, log_tx).await?;
+
+        let text = segments
+            .iter()
+            .map(|(text, _, _)| text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if text.is_empty() {
+            info!("❌ No speech detected in audio");
+            Ok(None)
+        } else {
+            info!(
+                "✅ Vosk transcription successful: {}",
+                redact::transcript(&text)
+            );
+            Ok(Some(Transcription::new(text, &self.config.model)))
+        }
+    }
+
+    /// Transcribe an audio file into `(text, start_secs, end_secs)` segments, one per utterance
+    pub async fn transcribe_segments<P: AsRef<Path>>(
+        &self,
+        audio_path: P,
+        log_tx: Option<TokioSender<String>>,
+    ) -> Result<Vec<(String, f64, f64)>> {
+        let audio_path = audio_path.as_ref();
+
+        if !audio_path.exists() {
+            return Err(anyhow::anyhow!("Audio file not found: {:?}", audio_path));
+        }
+        if self.model_dir.is_none() {
+            return Err(anyhow::anyhow!(
+                "Vosk transcription not available - model not loaded. Check logs for details."
+            ));
+        }
+
+        info!("🔄 Transcribing audio file with Vosk: {:?}", audio_path);
+        let audio_data = wav_utils::load_mono(audio_path, VOSK_SAMPLE_RATE)?;
+        send_log(
+            &log_tx,
+            format!(
+                "Running Vosk on {:.1}s of audio...",
+                audio_data.len() as f32 / VOSK_SAMPLE_RATE as f32
+            ),
+        )
+        .await;
+        let vosk_start = Instant::now();
+
+        let segments = self.recognize(audio_data).await?;
+        if self.is_cancelled() {
+            return Err(anyhow::anyhow!("Transcription cancelled"));
+        }
+
+        debug!("Vosk transcription completed: {} segments", segments.len());
+        send_log(
+            &log_tx,
+            format!(
+                "Vosk finished in {} ms: got {} segments",
+                vosk_start.elapsed().as_millis(),
+                segments.len()
+            ),
+        )
+        .await;
+
+        Ok(segments)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_flag
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    /// Run the recognizer over 16kHz mono samples, off the async runtime
+    async fn recognize(&self, audio_data: Vec<f32>) -> Result<Vec<(String, f64, f64)>> {
+        let model = self
+            .model
+            .clone()
+            .context("Vosk transcription not available - model not loaded")?;
+        let cancel_flag = self.cancel_flag.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut recognizer = Recognizer::new(&model, VOSK_SAMPLE_RATE as f32)
+                .context("Failed to create Vosk recognizer")?;
+            recognizer.set_words(true);
+            let mut segments = Vec::new();
+            for chunk in to_pcm16(&audio_data).chunks(CHUNK_SAMPLES) {
+                if cancel_flag
+                    .as_ref()
+                    .is_some_and(|flag| flag.load(Ordering::Relaxed))
+                {
+                    return Ok(segments);
+                }
+                // An utterance ended in this chunk
+                let state = recognizer
+                    .accept_waveform(chunk)
+                    .context("Vosk rejected the audio")?;
+                if state == DecodingState::Finalized {
+                    segments.extend(segment_from_result(recognizer.result()));
+                }
+            }
+            segments.extend(segment_from_result(recognizer.final_result()));
+            Ok(segments)
+        })
+        .await
+        .context("Vosk recognizer task failed")?
+    }
+}
+
+/// Convert float samples to the 16-bit PCM the recognizer takes
+fn to_pcm16(samples: &[f32]) -> Vec<i16> {
+    samples
+        .iter()
+        .map(|sample| (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+        .collect()
+}
+
+/// A recognizer result as a `(text, start_secs, end_secs)` segment, timed by its words
+fn segment_from_result(result: CompleteResult) -> Option<(String, f64, f64)> {
+    let result = result.single()?;
+    let text = result.text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let start = result.result.first().map_or(0.0, |word| word.start);
+    let end = result.result.last().map_or(0.0, |word| word.end);
+    Some((text.to_string(), f64::from(start), f64::from(end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment_from_result() {
+        let parse = |json| serde_json::from_str::<CompleteResult>(json).unwrap();
+        let result = parse(
+            r#"{
+                "result": [
+                    {"conf": 1.0, "end": 1.125, "start": 0.875, "word": "turn"},
+                    {"conf": 0.98, "end": 1.5, "start": 1.125, "word": "left"}
+                ],
+                "text": "turn left"
+            }"#,
+        );
+        assert_eq!(
+            segment_from_result(result),
+            Some(("turn left".to_string(), 0.875, 1.5))
+        );
+        // Silence gives an empty final result
+        assert_eq!(segment_from_result(parse(r#"{"text": ""}"#)), None);
+    }
+
+    #[test]
+    fn test_to_pcm16() {
+        assert_eq!(
+            to_pcm16(&[0.0, 1.0, -1.0, 2.0]),
+            vec![0, 32767, -32767, 32767]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_model_dir() {
+        let mut config = Config::default();
+        config.whisper.model_path = Some("/nonexistent/vosk-model".to_string());
+        let mut backend = VoskSttBackend::new(&config).unwrap();
+        assert!(backend.prepare().await.is_err());
+        assert!(backend
+            .preparation_failed()
+            .unwrap()
+            .contains("model directory not found"));
+        // A failed load isn't retried as if the model had been unloaded
+        assert!(!backend.is_unloaded());
+    }
+
+    #[test]
+    fn test_unload() {
+        let mut backend = VoskSttBackend::new(&Config::default()).unwrap();
+        assert!(backend.is_unloaded());
+        backend.model_dir = Some(PathBuf::from("/models/vosk"));
+        assert!(!backend.is_unloaded());
+        assert!(backend.unload());
+        assert!(backend.is_unloaded());
+        assert!(!backend.unload());
+    }
+
+    /// Runs the real recognizer on the model directory `VOSK_TEST_MODEL` points at,
+    /// e.g. vosk-model-small-en-us-0.15
+    #[tokio::test]
+    #[ignore = "needs a Vosk model in VOSK_TEST_MODEL"]
+    async fn test_recognizer_on_silence() {
+        let model_dir = std::env::var("VOSK_TEST_MODEL").expect("VOSK_TEST_MODEL is not set");
+        let mut config = Config::default();
+        config.whisper.model_path = Some(model_dir);
+        let mut backend = VoskSttBackend::new(&config).unwrap();
+        backend.prepare().await.unwrap();
+
+        let silence = vec![0.0; VOSK_SAMPLE_RATE as usize * 2];
+        assert!(backend.recognize(silence).await.unwrap().is_empty());
+    }
+}
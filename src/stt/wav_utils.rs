@@ -1,39 +1,587 @@
-use anyhow::Result;
-use hound::{WavSpec, WavWriter};
+use anyhow::{Context, Result};
+use hound::{WavReader, WavSpec, WavWriter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use tempfile::NamedTempFile;
+use tracing::{debug, warn};
 
-pub fn save_wav(samples: &[f32], sample_rate: u32, channels: u16) -> Result<NamedTempFile> {
-    const MIN_AUDIO_DURATION_MS: u32 = 1000; // 1 second
+use crate::config::Config;
+
+/// Opus always decodes to 48 kHz, the rate its granule positions count in
+const OPUS_DECODE_RATE: u32 = 48_000;
+
+/// Longest Opus packet, 120 ms at 48 kHz, in samples per channel
+const OPUS_MAX_PACKET: usize = OPUS_DECODE_RATE as usize * 120 / 1000;
+
+/// Prefix of the temporary recordings written by `save_wav`
+const TEMP_WAV_PREFIX: &str = "simple-stt-";
+
+/// Directory holding temporary recordings while they're transcribed
+pub fn temp_wav_dir() -> PathBuf {
+    Config::cache_dir().join("tmp")
+}
+
+/// Write samples to a temporary WAV file, padding with silence up to
+/// `min_duration_ms` (0 disables padding). `bits_per_sample` is 8, 16 or 24
+/// for integer PCM, or 32 for float.
+pub fn save_wav(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    min_duration_ms: u32,
+    bits_per_sample: u16,
+) -> Result<NamedTempFile> {
     let current_duration_ms = (samples.len() as f32 / sample_rate as f32 * 1000.0) as u32;
 
     let mut padded_samples = samples.to_vec();
 
-    if current_duration_ms < MIN_AUDIO_DURATION_MS {
-        let samples_to_add = (sample_rate as f32
-            * (MIN_AUDIO_DURATION_MS - current_duration_ms) as f32
-            / 1000.0) as usize;
+    if current_duration_ms < min_duration_ms {
+        let samples_to_add =
+            (sample_rate as f32 * (min_duration_ms - current_duration_ms) as f32 / 1000.0) as usize;
         padded_samples.extend(vec![0.0; samples_to_add]);
         tracing::debug!(
-            "Padded audio with {} samples of silence to reach {} ms",
+            "Padded audio with {} samples of silence to reach configured minimum of {} ms",
             samples_to_add,
-            MIN_AUDIO_DURATION_MS
+            min_duration_ms
         );
     }
 
-    let temp_file = NamedTempFile::new()?;
-    let mut writer = WavWriter::create(
+    let temp_dir = temp_wav_dir();
+    std::fs::create_dir_all(&temp_dir)
+        .with_context(|| format!("Failed to create temp directory: {temp_dir:?}"))?;
+    let temp_file = tempfile::Builder::new()
+        .prefix(TEMP_WAV_PREFIX)
+        .suffix(".wav")
+        .tempfile_in(&temp_dir)?;
+    write_wav(
         temp_file.path(),
+        &padded_samples,
+        sample_rate,
+        channels,
+        bits_per_sample,
+    )?;
+    Ok(temp_file)
+}
+
+/// Write samples to `path` as a WAV file of the given bit depth
+fn write_wav(
+    path: &Path,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    bits_per_sample: u16,
+) -> Result<()> {
+    let sample_format = match bits_per_sample {
+        8 | 16 | 24 => hound::SampleFormat::Int,
+        32 => hound::SampleFormat::Float,
+        bits => {
+            return Err(anyhow::anyhow!(
+                "Unsupported WAV bit depth {bits}, expected 8, 16, 24 or 32"
+            ))
+        }
+    };
+    let mut writer = WavWriter::create(
+        path,
         WavSpec {
             channels,
             sample_rate,
-            bits_per_sample: 16,
-            sample_format: hound::SampleFormat::Int,
+            bits_per_sample,
+            sample_format,
         },
     )?;
 
-    for &sample in &padded_samples {
-        writer.write_sample((sample * i16::MAX as f32) as i16)?;
+    for &sample in samples {
+        let sample = sample.clamp(-1.0, 1.0);
+        match bits_per_sample {
+            8 => writer.write_sample((sample * i8::MAX as f32) as i8)?,
+            16 => writer.write_sample((sample * i16::MAX as f32) as i16)?,
+            24 => writer.write_sample((sample * 8388607.0) as i32)?,
+            _ => writer.write_sample(sample)?,
+        }
     }
     writer.finalize()?;
-    Ok(temp_file)
+    Ok(())
+}
+
+/// Copy a recording into `dir` to keep it, named after the current time.
+/// Returns the absolute path of the copy.
+pub fn keep_recording(wav: &Path, dir: &str) -> Result<PathBuf> {
+    let dir = PathBuf::from(shellexpand::tilde(dir).as_ref());
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create recordings directory: {dir:?}"))?;
+    let name = chrono::Local::now()
+        .format("recording-%Y%m%d-%H%M%S%.3f.wav")
+        .to_string();
+    let path = dir.join(name);
+    std::fs::copy(wav, &path).with_context(|| format!("Failed to save recording to {path:?}"))?;
+    debug!("Kept recording: {:?}", path);
+    Ok(std::path::absolute(&path).unwrap_or(path))
+}
+
+/// Dump raw samples to `dir` as a NumPy `.npy` float32 array for offline analysis, named
+/// after the current time and the sample rate. Returns the absolute path of the file.
+pub fn export_samples(samples: &[f32], sample_rate: u32, dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create export directory: {dir:?}"))?;
+    let name = chrono::Local::now()
+        .format(&format!("recording-%Y%m%d-%H%M%S%.3f-{sample_rate}hz.npy"))
+        .to_string();
+    let path = dir.join(name);
+    std::fs::write(&path, npy_bytes(samples))
+        .with_context(|| format!("Failed to export samples to {path:?}"))?;
+    debug!("Exported {} samples to {:?}", samples.len(), path);
+    Ok(std::path::absolute(&path).unwrap_or(path))
+}
+
+/// A 1-D little-endian float32 array in the `.npy` v1.0 format
+fn npy_bytes(samples: &[f32]) -> Vec<u8> {
+    const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({},), }}",
+        samples.len()
+    );
+    // The header is padded with spaces and a newline so the data starts 64-byte aligned
+    let unpadded = MAGIC.len() + 2 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+    header.push('\n');
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
+
+/// Delete temporary recordings older than `max_age` left behind by a crash,
+/// returning how many were removed
+pub fn cleanup_orphaned_wavs(max_age: Duration) -> Result<usize> {
+    cleanup_orphaned_wavs_in(&temp_wav_dir(), max_age)
+}
+
+fn cleanup_orphaned_wavs_in(dir: &Path, max_age: Duration) -> Result<usize> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to read {dir:?}"))? {
+        let path = entry?.path();
+        let is_temp_wav = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(TEMP_WAV_PREFIX) && name.ends_with(".wav"));
+        if !is_temp_wav {
+            continue;
+        }
+
+        let age = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        if age.is_some_and(|age| age > max_age) {
+            match std::fs::remove_file(&path) {
+                Ok(()) => {
+                    debug!("Removed orphaned temp recording {:?}", path);
+                    removed += 1;
+                }
+                Err(e) => warn!("Failed to remove orphaned temp recording {:?}: {}", path, e),
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// Get the playback duration of a WAV file
+pub fn wav_duration<P: AsRef<Path>>(path: P) -> Result<Duration> {
+    let path = path.as_ref();
+    let reader =
+        WavReader::open(path).with_context(|| format!("Failed to open WAV file: {path:?}"))?;
+    let frames = reader.duration() as f64;
+    Ok(Duration::from_secs_f64(
+        frames / reader.spec().sample_rate as f64,
+    ))
+}
+
+/// Extensions of the audio files `load_mono` can decode
+pub const AUDIO_EXTENSIONS: &[&str] = &["wav", "mp3", "flac", "ogg", "opus", "m4a"];
+
+/// Load an audio file as mono f32 samples at the given sample rate. WAV is read
+/// with hound, Ogg Opus with libopus, and the other compressed formats (MP3, FLAC,
+/// Ogg Vorbis, M4A) are decoded with symphonia.
+pub fn load_mono<P: AsRef<Path>>(audio_path: P, target_rate: u32) -> Result<Vec<f32>> {
+    let audio_path = audio_path.as_ref();
+
+    debug!("Loading audio file: {:?}", audio_path);
+
+    let is_wav = audio_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_none_or(|ext| ext.eq_ignore_ascii_case("wav"));
+    let (samples, channels, sample_rate) = if is_wav {
+        read_wav(audio_path)?
+    } else if let Some(decoded) = decode_ogg_opus(audio_path)? {
+        decoded
+    } else {
+        decode_compressed(audio_path)?
+    };
+
+    debug!("Read {} samples", samples.len());
+
+    // Calculate min/max and RMS for debugging
+    if !samples.is_empty() {
+        let min_val = samples.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max_val = samples.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let sum_squares: f32 = samples.iter().map(|&s| s * s).sum();
+        let rms = (sum_squares / samples.len() as f32).sqrt();
+        debug!(
+            "Raw f32 samples - Min: {:.4}, Max: {:.4}, RMS: {:.4}",
+            min_val, max_val, rms
+        );
+    }
+
+    // Average the channels down to mono if necessary
+    let mut samples = match channels {
+        0 => return Err(anyhow::anyhow!("Audio file has no channels")),
+        1 => samples,
+        channels => {
+            debug!("Converting {} channels to mono", channels);
+            samples
+                .chunks(channels as usize)
+                .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+                .collect()
+        }
+    };
+
+    // Resample to the target rate if necessary
+    if sample_rate != target_rate {
+        debug!("Resampling from {} Hz to {} Hz", sample_rate, target_rate);
+        samples = resample_audio(samples, sample_rate, target_rate)?;
+    }
+
+    debug!(
+        "Final audio: {} samples at {} Hz mono",
+        samples.len(),
+        target_rate
+    );
+
+    Ok(samples)
+}
+
+/// Read a WAV file as interleaved f32 samples, with its channel count and sample rate
+fn read_wav(audio_path: &Path) -> Result<(Vec<f32>, u16, u32)> {
+    // Use hound to read the WAV file
+    let reader = hound::WavReader::open(audio_path).context("Failed to open audio file")?;
+
+    let spec = reader.spec();
+    debug!("Audio spec: {:?}", spec);
+
+    // Read samples based on the bit depth
+    let samples: Result<Vec<f32>, _> = match spec.bits_per_sample {
+        16 => reader
+            .into_samples::<i16>()
+            .map(|s| s.map(|sample| sample as f32 / 32768.0))
+            .collect(),
+        32 => {
+            if spec.sample_format == hound::SampleFormat::Float {
+                reader.into_samples::<f32>().collect()
+            } else {
+                reader
+                    .into_samples::<i32>()
+                    .map(|s| s.map(|sample| sample as f32 / 2147483648.0))
+                    .collect()
+            }
+        }
+        24 => {
+            // 24-bit samples are read as sign-extended i32 values
+            reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|sample| sample as f32 / 8388608.0))
+                .collect()
+        }
+        8 => {
+            // Convert 8-bit unsigned to signed first
+            reader
+                .into_samples::<i8>()
+                .map(|s| s.map(|sample| sample as f32 / 128.0))
+                .collect()
+        }
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unsupported bit depth: {} bits",
+                spec.bits_per_sample
+            ));
+        }
+    };
+
+    let samples = samples.context("Failed to read audio samples")?;
+    Ok((samples, spec.channels, spec.sample_rate))
+}
+
+/// Decode an Ogg Opus file, which symphonia has no decoder for, to interleaved f32
+/// samples with its channel count and sample rate. Returns `None` for anything else.
+fn decode_ogg_opus(audio_path: &Path) -> Result<Option<(Vec<f32>, u16, u32)>> {
+    let file = std::fs::File::open(audio_path)
+        .with_context(|| format!("Failed to open audio file: {audio_path:?}"))?;
+    let mut reader = ogg::PacketReader::new(std::io::BufReader::new(file));
+    // Other Ogg codecs and other containers are left to symphonia
+    let head = match reader.read_packet() {
+        Ok(Some(packet)) if packet.data.starts_with(b"OpusHead") && packet.data.len() >= 19 => {
+            packet.data
+        }
+        _ => return Ok(None),
+    };
+    let opus_channels = match head[9] {
+        1 => opus::Channels::Mono,
+        2 => opus::Channels::Stereo,
+        count => {
+            return Err(anyhow::anyhow!(
+                "Opus with {count} channels is not supported, only mono and stereo"
+            ))
+        }
+    };
+    let channels = head[9] as usize;
+    let pre_skip = u16::from_le_bytes([head[10], head[11]]) as usize;
+    reader
+        .read_packet()
+        .context("Failed to read Opus comment header")?;
+
+    let mut decoder = opus::Decoder::new(OPUS_DECODE_RATE, opus_channels)
+        .context("Failed to create Opus decoder")?;
+    let mut frame = vec![0.0f32; OPUS_MAX_PACKET * channels];
+    let mut samples = Vec::new();
+    let mut last_granule = None;
+    while let Some(packet) = reader
+        .read_packet()
+        .context("Failed to read audio packet")?
+    {
+        match decoder.decode_float(&packet.data, &mut frame, false) {
+            Ok(len) => samples.extend_from_slice(&frame[..len * channels]),
+            // A corrupt packet is skipped rather than failing the whole file
+            Err(e) => warn!("Skipping undecodable packet: {}", e),
+        }
+        last_granule = Some(packet.absgp_page());
+    }
+
+    // Drop the encoder delay at the start, and the padding the last granule excludes
+    if let Some(granule) = last_granule {
+        let len = (granule as usize).saturating_sub(pre_skip) * channels;
+        samples.truncate(len + pre_skip * channels);
+    }
+    samples.drain(..(pre_skip * channels).min(samples.len()));
+    Ok(Some((samples, channels as u16, OPUS_DECODE_RATE)))
+}
+
+/// Decode a compressed audio file to interleaved f32 samples, with its channel count and
+/// sample rate. The container is probed from the contents, using the extension as a hint.
+fn decode_compressed(audio_path: &Path) -> Result<(Vec<f32>, u16, u32)> {
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+    use symphonia::core::errors::Error as SymphoniaError;
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+
+    let file = std::fs::File::open(audio_path)
+        .with_context(|| format!("Failed to open audio file: {audio_path:?}"))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = audio_path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .with_context(|| format!("Unsupported audio format: {audio_path:?}"))?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .context("No audio track found")?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .context("Unsupported audio codec")?;
+    let mut sample_rate = track.codec_params.sample_rate;
+    let mut channels = track.codec_params.channels.map(|c| c.count() as u16);
+
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(e) => return Err(e).context("Failed to read audio packet"),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                sample_rate.get_or_insert(spec.rate);
+                channels.get_or_insert(spec.channels.count() as u16);
+                let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                buffer.copy_interleaved_ref(decoded);
+                samples.extend_from_slice(buffer.samples());
+            }
+            // A corrupt packet is skipped rather than failing the whole file
+            Err(SymphoniaError::DecodeError(e)) => warn!("Skipping undecodable packet: {}", e),
+            Err(e) => return Err(e).context("Failed to decode audio"),
+        }
+    }
+
+    let sample_rate = sample_rate.context("Unknown sample rate")?;
+    Ok((samples, channels.unwrap_or(1), sample_rate))
+}
+
+/// Linear-interpolation resampling (not high quality, but sufficient for speech)
+pub fn resample_audio(input: Vec<f32>, input_rate: u32, output_rate: u32) -> Result<Vec<f32>> {
+    if input_rate == output_rate || input.is_empty() {
+        return Ok(input);
+    }
+
+    let ratio = input_rate as f64 / output_rate as f64;
+    let output_len = (input.len() as f64 / ratio) as usize;
+    let mut output = Vec::with_capacity(output_len);
+
+    for i in 0..output_len {
+        let position = i as f64 * ratio;
+        let index = position as usize;
+        let fraction = (position - index as f64) as f32;
+        let current = input[index.min(input.len() - 1)];
+        let next = input[(index + 1).min(input.len() - 1)];
+        output.push(current + (next - current) * fraction);
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Silent MPEG-1 Layer III stream: mono, 44.1 kHz, 128 kbps frames of 1152 samples
+    /// whose side info and main data are all zero
+    fn silent_mp3(frames: usize) -> Vec<u8> {
+        const FRAME_LEN: usize = 417; // 144 * 128000 / 44100, without padding
+        let mut frame = vec![0u8; FRAME_LEN];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0xC0]);
+        frame.repeat(frames)
+    }
+
+    #[test]
+    fn test_load_mp3() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memo.mp3");
+        std::fs::write(&path, silent_mp3(10)).unwrap();
+
+        let samples = load_mono(&path, 44100).unwrap();
+        assert_eq!(samples.len(), 10 * 1152);
+        assert!(samples.iter().all(|&sample| sample == 0.0));
+
+        // Resampled like WAV input
+        assert_eq!(load_mono(&path, 22050).unwrap().len(), 10 * 1152 / 2);
+    }
+
+    #[test]
+    fn test_load_opus() {
+        use crate::audio::calculate_rms;
+
+        let samples: Vec<f32> = (0..16000)
+            .map(|i| 0.3 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 16000.0).sin())
+            .collect();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memo.opus");
+        std::fs::write(&path, crate::stt::api::encode_ogg_opus(&samples).unwrap()).unwrap();
+
+        // Trimmed back to the encoded length at 48 kHz, then resampled
+        let decoded = load_mono(&path, 16000).unwrap();
+        assert_eq!(decoded.len(), samples.len());
+        let rms = calculate_rms(&decoded);
+        assert!((rms - calculate_rms(&samples)).abs() < 0.05, "rms {rms}");
+    }
+
+    #[test]
+    fn test_npy_bytes() {
+        let bytes = npy_bytes(&[0.5, -1.0]);
+        assert!(bytes.starts_with(b"\x93NUMPY\x01\x00"));
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let data_start = 10 + header_len;
+        assert_eq!(data_start % 64, 0);
+        let header = std::str::from_utf8(&bytes[10..data_start]).unwrap();
+        assert!(header.contains("'shape': (2,)"));
+        assert!(header.ends_with('\n'));
+        assert_eq!(&bytes[data_start..data_start + 4], &0.5f32.to_le_bytes());
+        assert_eq!(bytes.len(), data_start + 8);
+    }
+
+    #[test]
+    fn test_cleanup_orphaned_wavs() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = SystemTime::now() - Duration::from_secs(7200);
+        for name in ["simple-stt-old.wav", "other-old.wav"] {
+            let file = std::fs::File::create(dir.path().join(name)).unwrap();
+            file.set_modified(old).unwrap();
+        }
+        std::fs::File::create(dir.path().join("simple-stt-new.wav")).unwrap();
+
+        let removed = cleanup_orphaned_wavs_in(dir.path(), Duration::from_secs(3600)).unwrap();
+        assert_eq!(removed, 1);
+        assert!(!dir.path().join("simple-stt-old.wav").exists());
+        assert!(dir.path().join("other-old.wav").exists());
+        assert!(dir.path().join("simple-stt-new.wav").exists());
+    }
+
+    #[test]
+    fn test_save_wav_bit_depths_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let samples = [0.0, 0.5, -0.5, 0.25];
+        for bits in [8, 16, 24, 32] {
+            let path = dir.path().join(format!("{bits}.wav"));
+            write_wav(&path, &samples, 16000, 1, bits).unwrap();
+            assert_eq!(WavReader::open(&path).unwrap().spec().bits_per_sample, bits);
+            let loaded = load_mono(&path, 16000).unwrap();
+            for (original, loaded) in samples.iter().zip(&loaded) {
+                assert!((original - loaded).abs() < 0.01, "{bits}-bit: {loaded}");
+            }
+        }
+        assert!(write_wav(&dir.path().join("12.wav"), &samples, 16000, 1, 12).is_err());
+    }
+
+    #[test]
+    fn test_resample_interpolates() {
+        let output = resample_audio(vec![0.0, 1.0, 0.0, -1.0], 2, 4).unwrap();
+        assert_eq!(output, vec![0.0, 0.5, 1.0, 0.5, 0.0, -0.5, -1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_resample_downsamples_length() {
+        let output = resample_audio(vec![0.25; 48000], 48000, 16000).unwrap();
+        assert_eq!(output.len(), 16000);
+        assert!(output.iter().all(|&s| s == 0.25));
+    }
+
+    #[test]
+    fn test_keep_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("take.wav");
+        std::fs::write(&wav, b"RIFF").unwrap();
+        let recordings = dir.path().join("recordings");
+
+        let kept = keep_recording(&wav, &recordings.to_string_lossy()).unwrap();
+        assert!(kept.is_absolute());
+        assert!(kept.starts_with(&recordings));
+        assert_eq!(std::fs::read(&kept).unwrap(), b"RIFF");
+        assert!(wav.exists());
+    }
 }
@@ -0,0 +1,68 @@
+/// Split text into lowercase words, ignoring punctuation
+fn tokenize(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric() || *c == '\'')
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Compute the word error rate of a hypothesis against a reference transcript.
+///
+/// WER is the word-level Levenshtein distance (substitutions + deletions +
+/// insertions) divided by the number of reference words.
+pub fn word_error_rate(reference: &str, hypothesis: &str) -> f64 {
+    let reference = tokenize(reference);
+    let hypothesis = tokenize(hypothesis);
+
+    if reference.is_empty() {
+        return if hypothesis.is_empty() { 0.0 } else { 1.0 };
+    }
+
+    // Single-row Levenshtein over words
+    let mut row: Vec<usize> = (0..=hypothesis.len()).collect();
+    for (i, ref_word) in reference.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, hyp_word) in hypothesis.iter().enumerate() {
+            let substitution = diagonal + usize::from(ref_word != hyp_word);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[hypothesis.len()] as f64 / reference.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_transcripts() {
+        assert_eq!(word_error_rate("Hello, world!", "hello world"), 0.0);
+    }
+
+    #[test]
+    fn test_substitution_insertion_deletion() {
+        // One substitution out of four words
+        assert_eq!(
+            word_error_rate("the cat sat down", "the bat sat down"),
+            0.25
+        );
+        // One deletion
+        assert_eq!(word_error_rate("the cat sat down", "the cat down"), 0.25);
+        // Two insertions
+        assert_eq!(word_error_rate("the cat", "the big fat cat"), 1.0);
+    }
+
+    #[test]
+    fn test_empty_reference() {
+        assert_eq!(word_error_rate("", ""), 0.0);
+        assert_eq!(word_error_rate("", "hello"), 1.0);
+    }
+}
@@ -0,0 +1,392 @@
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+
+use crate::config::{Config, Replacement};
+
+/// Post-processing run on every transcript before it's copied
+#[derive(Debug, Clone, Default)]
+pub struct TextProcessor {
+    vocabulary: Vocabulary,
+    replacer: Replacer,
+    spoken_punctuation: bool,
+}
+
+impl TextProcessor {
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            vocabulary: Vocabulary::load(config.whisper.vocab_file.as_deref())?,
+            replacer: Replacer::new(&config.replacements)?,
+            spoken_punctuation: config.text.spoken_punctuation,
+        })
+    }
+
+    pub fn process(&self, text: &str) -> String {
+        let text = &self.vocabulary.correct(text);
+        if self.spoken_punctuation {
+            self.replacer.apply(&apply_spoken_punctuation(text))
+        } else {
+            self.replacer.apply(text)
+        }
+    }
+}
+
+/// Applies the configured `[[replacements]]` to transcripts, in order
+#[derive(Debug, Clone, Default)]
+pub struct Replacer {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Replacer {
+    pub fn new(replacements: &[Replacement]) -> Result<Self> {
+        let rules = replacements
+            .iter()
+            .map(|rule| {
+                let (pattern, replacement) = if rule.regex {
+                    (rule.pattern.clone(), rule.replacement.clone())
+                } else {
+                    plain_rule(&rule.pattern, &rule.replacement)
+                };
+                let regex = RegexBuilder::new(&pattern)
+                    .case_insensitive(!rule.case_sensitive)
+                    .build()
+                    .with_context(|| format!("Invalid replacement pattern: {:?}", rule.pattern))?;
+                Ok((regex, replacement))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { rules })
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        self.rules
+            .iter()
+            .fold(text.to_string(), |text, (regex, replacement)| {
+                regex.replace_all(&text, replacement.as_str()).into_owned()
+            })
+    }
+}
+
+/// Known words and phrases from `whisper.vocab_file`, one per line with `#` comments.
+/// They prompt whisper and near-misses in transcripts are corrected to them.
+#[derive(Debug, Clone, Default)]
+pub struct Vocabulary {
+    entries: Vec<String>,
+}
+
+impl Vocabulary {
+    /// Read the vocabulary file, an empty vocabulary when none is configured
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let path = shellexpand::tilde(path);
+        let contents = std::fs::read_to_string(path.as_ref())
+            .with_context(|| format!("Failed to read vocabulary file {path}"))?;
+        Ok(Self::parse(&contents))
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let entries = contents
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect();
+        Self { entries }
+    }
+
+    /// Initial prompt listing the vocabulary, which biases whisper towards these spellings
+    pub fn prompt(&self) -> Option<String> {
+        (!self.entries.is_empty()).then(|| self.entries.join(", "))
+    }
+
+    /// Replace words and phrases within a few edits of a vocabulary entry with the entry,
+    /// keeping the punctuation around them. Entries under five letters only fix the case.
+    pub fn correct(&self, text: &str) -> String {
+        if self.entries.is_empty() {
+            return text.to_string();
+        }
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        let bare: Vec<String> = tokens
+            .iter()
+            .map(|token| {
+                token
+                    .trim_matches(|c: char| !c.is_alphanumeric())
+                    .to_lowercase()
+            })
+            .collect();
+
+        let mut output = Vec::with_capacity(tokens.len());
+        let mut i = 0;
+        while i < tokens.len() {
+            // Closest entry starting at this word, the longer one on a tie
+            let best = self
+                .entries
+                .iter()
+                .filter_map(|entry| {
+                    let words = entry.split(' ').count();
+                    let heard = bare.get(i..i + words)?.join(" ");
+                    let distance = edit_distance(&heard, &entry.to_lowercase());
+                    (distance <= entry.chars().count() / 5).then_some((distance, words, entry))
+                })
+                .min_by_key(|&(distance, words, _)| (distance, std::cmp::Reverse(words)));
+
+            match best {
+                // A lowercase entry heard exactly keeps its sentence-start capital
+                Some((0, words, entry)) if entry.to_lowercase() == *entry => {
+                    output.push(tokens[i..i + words].join(" "));
+                    i += words;
+                }
+                Some((_, words, entry)) => {
+                    let first = tokens[i];
+                    let last = tokens[i + words - 1];
+                    let leading = &first[..first.len()
+                        - first
+                            .trim_start_matches(|c: char| !c.is_alphanumeric())
+                            .len()];
+                    let trailing =
+                        &last[last.trim_end_matches(|c: char| !c.is_alphanumeric()).len()..];
+                    output.push(format!("{leading}{entry}{trailing}"));
+                    i += words;
+                }
+                None => {
+                    output.push(tokens[i].to_string());
+                    i += 1;
+                }
+            }
+        }
+        output.join(" ")
+    }
+}
+
+/// Character-level Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != *b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Turn a plain pattern into a whole-word regex and a literal replacement.
+///
+/// Whitespace-only replacements are spoken commands ("new line" → "\n"), so
+/// they also swallow the spaces and punctuation whisper puts around them.
+fn plain_rule(pattern: &str, replacement: &str) -> (String, String) {
+    let mut regex = regex::escape(pattern.trim());
+    if pattern.trim().starts_with(|c: char| c.is_alphanumeric()) {
+        regex.insert_str(0, r"\b");
+    }
+    if pattern.trim().ends_with(|c: char| c.is_alphanumeric()) {
+        regex.push_str(r"\b");
+    }
+    if !replacement.is_empty() && replacement.trim().is_empty() {
+        regex = format!(r"[ \t]*{regex}[.,!?]*[ \t]*");
+    }
+    (regex, replacement.replace('$', "$$"))
+}
+
+/// Spoken punctuation words and the symbols they stand for, longest phrases first
+const SPOKEN_PUNCTUATION: &[(&[&str], &str)] = &[
+    (&["new", "paragraph"], "\n\n"),
+    (&["new", "line"], "\n"),
+    (&["question", "mark"], "?"),
+    (&["exclamation", "mark"], "!"),
+    (&["exclamation", "point"], "!"),
+    (&["full", "stop"], "."),
+    (&["period"], "."),
+    (&["comma"], ","),
+    (&["colon"], ":"),
+    (&["semicolon"], ";"),
+];
+
+/// Said before a punctuation word to keep the word itself, e.g. "literal period"
+const LITERAL: &str = "literal";
+
+/// Replace dictated punctuation ("comma", "new paragraph") with symbols, fix
+/// the spacing around them and capitalize the start of each sentence.
+/// "literal" escapes the next punctuation word, so "the literal period" stays "the period".
+pub fn apply_spoken_punctuation(text: &str) -> String {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    // Whisper often punctuates the spoken words themselves ("Hello, comma, there")
+    let bare: Vec<String> = tokens
+        .iter()
+        .map(|token| {
+            token
+                .trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .collect();
+
+    let spoken_at = |i: usize| {
+        SPOKEN_PUNCTUATION.iter().find(|(phrase, _)| {
+            bare.get(i..i + phrase.len())
+                .is_some_and(|words| words.iter().zip(phrase.iter()).all(|(a, b)| a == b))
+        })
+    };
+
+    let mut output = String::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if bare[i] == LITERAL {
+            if let Some((phrase, _)) = spoken_at(i + 1) {
+                // Drop "literal" and keep the phrase as said
+                for token in &tokens[i + 1..i + 1 + phrase.len()] {
+                    push_word(&mut output, token);
+                }
+                i += 1 + phrase.len();
+                continue;
+            }
+        }
+
+        match spoken_at(i) {
+            Some((phrase, symbol)) => {
+                // A spoken symbol replaces any punctuation whisper already added, line breaks keep it
+                let mut trimmed = output.trim_end();
+                if !symbol.starts_with('\n') {
+                    trimmed = trimmed.trim_end_matches(['.', ',', '?', '!', ':', ';']);
+                }
+                let trimmed = trimmed.len();
+                output.truncate(trimmed);
+                output.push_str(symbol);
+                i += phrase.len();
+            }
+            None => {
+                push_word(&mut output, tokens[i]);
+                i += 1;
+            }
+        }
+    }
+
+    capitalize_sentences(&output)
+}
+
+fn push_word(output: &mut String, word: &str) {
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push(' ');
+    }
+    output.push_str(word);
+}
+
+/// Uppercase the first letter of the text and of every sentence after `.`, `?`, `!` or a new line
+fn capitalize_sentences(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut sentence_start = true;
+    let mut previous = '\n';
+    for c in text.chars() {
+        if c.is_alphabetic() && sentence_start {
+            output.extend(c.to_uppercase());
+            sentence_start = false;
+        } else {
+            if c == '\n' || (c.is_whitespace() && matches!(previous, '.' | '?' | '!')) {
+                sentence_start = true;
+            } else if !c.is_whitespace() {
+                sentence_start = false;
+            }
+            output.push(c);
+        }
+        previous = c;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, replacement: &str) -> Replacement {
+        Replacement {
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            regex: false,
+            case_sensitive: false,
+        }
+    }
+
+    #[test]
+    fn test_plain_replacements_match_whole_words() {
+        let replacer =
+            Replacer::new(&[rule("cube cuddle", "kubectl"), rule("cat", "dog")]).unwrap();
+        assert_eq!(
+            replacer.apply("Run Cube Cuddle to concatenate the cat"),
+            "Run kubectl to concatenate the dog"
+        );
+    }
+
+    #[test]
+    fn test_spoken_commands_and_order() {
+        let replacer = Replacer::new(&[
+            rule("new paragraph", "\n\n"),
+            rule("new line", "\n"),
+            Replacement {
+                regex: true,
+                case_sensitive: true,
+                ..rule(r"TODO (\w+)", "- [ ] $1")
+            },
+        ])
+        .unwrap();
+        assert_eq!(
+            replacer.apply("First. New line. Second new paragraph TODO milk todo eggs"),
+            "First.\nSecond\n\n- [ ] milk todo eggs"
+        );
+    }
+
+    #[test]
+    fn test_spoken_punctuation() {
+        assert_eq!(
+            apply_spoken_punctuation("hello comma how are you question mark"),
+            "Hello, how are you?"
+        );
+        assert_eq!(
+            apply_spoken_punctuation("Dear Sam, comma. new paragraph thanks period see you soon."),
+            "Dear Sam,\n\nThanks. See you soon."
+        );
+        assert_eq!(
+            apply_spoken_punctuation("Note colon buy milk exclamation point new line call mom"),
+            "Note: buy milk!\nCall mom"
+        );
+        assert_eq!(
+            apply_spoken_punctuation("the literal period ends with a period"),
+            "The period ends with a."
+        );
+        assert_eq!(
+            apply_spoken_punctuation("Use a Literal new line, comma, then literal literal"),
+            "Use a new line, then literal literal"
+        );
+    }
+
+    #[test]
+    fn test_vocabulary_corrects_near_misses() {
+        let vocabulary =
+            Vocabulary::parse("# Apps\nFirefox\nkubectl\nVisual Studio Code  # editor\n\nopen\n");
+        assert_eq!(
+            vocabulary.prompt().as_deref(),
+            Some("Firefox, kubectl, Visual Studio Code, open")
+        );
+        assert_eq!(
+            vocabulary.correct("Open fire fox, then visual studio coat. Opens the door."),
+            "Open fire fox, then Visual Studio Code. Opens the door."
+        );
+        assert_eq!(
+            vocabulary.correct("launch firefix and run kubectel"),
+            "launch Firefox and run kubectl"
+        );
+        assert_eq!(Vocabulary::default().prompt(), None);
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        let invalid = Replacement {
+            regex: true,
+            ..rule("(unclosed", "")
+        };
+        assert!(Replacer::new(&[invalid]).is_err());
+    }
+}
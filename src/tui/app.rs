@@ -1,5 +1,42 @@
-use crate::config::Config;
-use std::time::Duration;
+use crate::audio::suggest_silence_threshold;
+use crate::config::{Config, RecordMode, WhisperConfig};
+use crate::output::ProfileOutput;
+use crate::stats::Stats;
+use crate::stt::{model_download_warning, Transcription};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const NO_INPUT_DEVICE: &str = "No input device";
+/// How long the mic test samples the noise floor before suggesting a threshold
+pub const MIC_TEST_CALIBRATION: Duration = Duration::from_secs(3);
+/// How much + and - change the input gain
+pub const GAIN_STEP: f32 = 0.1;
+/// How long "● Recording now" flashes once a `ui.record_countdown` runs out
+const GO_SIGNAL: Duration = Duration::from_secs(1);
+/// How long the status shows where a recording was saved
+const SAVED_NOTICE: Duration = Duration::from_secs(5);
+
+/// Whisper transcribes audio in windows of this length, padding the last one
+const WHISPER_WINDOW_SECS: f32 = 30.0;
+
+/// Outcome of a transcription job
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranscriptionResult {
+    Text(Transcription),
+    NoSpeech,
+    Error(String),
+    Cancelled,
+}
+
+/// What to do with a chunk of audio captured while recording
+#[derive(Debug, PartialEq)]
+pub enum VoiceActivity {
+    /// Voice-activated recording is still waiting for speech
+    Armed,
+    /// Speech has started (or voice activation is off), keep the chunk
+    Capture,
+}
 
 #[derive(PartialEq)]
 pub enum AppState {
@@ -11,37 +48,104 @@ pub enum AppState {
     Finished,
     ModelSelection,
     ShowingShortcuts,
+    MicTest,
+    RefinementSelection,
+    Settings,
 }
 
+/// An LLM refinement of the transcription with one profile: `(profile, text or error)`
+pub type Refinement = (String, Result<String, String>);
+
 pub struct App {
     pub state: AppState,
     pub config: Config,
     pub recording_duration: Duration,
     pub audio_waveform: Vec<f32>,
+    pub waveform_history: VecDeque<f32>, // Recent raw samples for the envelope and RMS styles
     pub running: bool,
     pub device_name: String,
+    pub input_device_available: bool,
     pub model_status: String,
     pub audio_level: f32,
-    pub transcribed_text: Option<String>,
+    pub transcription: Option<TranscriptionResult>,
     pub logs: Vec<String>,
     pub show_logs: bool,
     pub transcription_initiated: bool,
     pub available_models: Vec<String>,
     pub selected_model_index: usize,
     pub model_change_requested: bool,
+    pub model_reload_requested: bool,
+    pub model_redownload_requested: bool, // Delete the cached model file before reloading
+    pub model_load_cancel_requested: bool,
+    pub key_release_events: bool, // The terminal reports key releases, needed for hold-to-record
+    pub transcribing_since: Option<Instant>, // Set by tick while transcribing, for the spinner
+    pub last_activity: Instant,   // Last key press or recording, for ui.auto_quit_after
+    pub model_unloaded: bool, // ui.idle_action = "unload" freed the model, the next transcription reloads it
+    pub ms_per_audio_sec: Option<f32>, // Benchmarked model speed, for the time left estimate
+    pub loaded_whisper: Option<WhisperConfig>, // Settings of the last model that loaded successfully
+    pub model_warning: Option<String>, // Shown before a confirmed model change, Enter again to proceed
+    pub supported_audio_configs: Vec<(u32, u16)>, // (sample_rate, channels) the input device supports
+    pub unsupported_setting: Option<String>, // Value warned about as unsupported, Enter again to save it
+    pub clipboard_restore_requested: bool,
+    pub copy_requested: bool,
+    pub pending_transcriptions: usize,
+    pub pending_audio_secs: VecDeque<(u64, f32)>, // Audio length of each queued job, oldest (in flight) first
+    pub next_job_id: u64,
+    pub last_job_id: Option<u64>,
+    pub mic_test_levels: Vec<f32>,
+    pub last_recording: Vec<f32>, // Kept until the next recording starts so it can be re-transcribed
+    pub retry_pending: bool,
+    pub config_reload_requested: bool,
+    pub language_change_requested: bool,
+    pub cancel_requested: bool,
+    pub speech_started: bool,
+    pub trailing_silence: Duration,
+    pub stats: Stats,
+    pub finishing_up: bool, // Quit once pending transcriptions are done
+    pub refine_requested: bool,
+    pub clipboard_refine_requested: bool, // Refine the clipboard text instead of a transcription
+    pub export_requested: bool, // Dump the last recording's raw samples for offline analysis
+    pub logs_copy_requested: bool, // Copy the log panel's messages, e.g. for a bug report
+    pub profile_output: Option<(ProfileOutput, String)>, // A chosen refinement for a file or command
+    pub live_transcript: Option<String>, // Evolving text of the recording so far, with whisper.stream_interval
+    pub refinements: Vec<Refinement>,
+    pub selected_refinement: usize,
+    pub selected_setting: usize,
+    pub saved_recording: Option<(PathBuf, Instant)>, // Last recording kept by audio.save_recordings
+    pub countdown_until: Option<Instant>, // When a ui.record_countdown runs out and recording starts
+    pub session_text: String,             // Transcriptions joined for clipboard.accumulate
+    pub setting_input: Option<String>,    // Value being typed for the selected setting
 }
 
+/// Settings offered on the settings screen (S), as dotted config keys
+pub const SETTINGS: &[&str] = &[
+    "audio.silence_threshold",
+    "audio.silence_duration",
+    "audio.max_recording_time",
+    "audio.voice_activated",
+    "audio.gain",
+    "audio.trim_silence",
+    "audio.sample_rate",
+    "audio.channels",
+    "whisper.backend",
+    "whisper.model",
+    "whisper.language",
+    "whisper.n_threads",
+    "llm.provider",
+    "llm.model",
+    "llm.default_profile",
+    "clipboard.auto_copy",
+    "clipboard.auto_paste",
+    "clipboard.paste_delay",
+    "ui.queue_recordings",
+    "ui.waveform_style",
+    "ui.record_mode",
+];
+
 impl App {
-    pub fn new(config: Config, device_name: String) -> Self {
+    pub fn new(config: Config, device_name: Option<String>) -> Self {
         let model_name = config.whisper.model.clone();
-        let available_models = vec![
-            "tiny.en".to_string(),
-            "base.en".to_string(),
-            "small.en".to_string(),
-            "medium.en".to_string(),
-            "large".to_string(),
-            "large-v3-turbo".to_string(),
-        ];
+        let available_models = config.whisper.model_list();
         let selected_model_index = available_models
             .iter()
             .position(|m| m == &model_name)
@@ -52,24 +156,135 @@ impl App {
             config,
             recording_duration: Duration::default(),
             audio_waveform: Vec::new(),
+            waveform_history: VecDeque::new(),
             running: true,
-            device_name,
+            input_device_available: device_name.is_some(),
+            device_name: device_name.unwrap_or_else(|| NO_INPUT_DEVICE.to_string()),
             model_status: format!("Loading {model_name}..."),
             audio_level: 0.0,
-            transcribed_text: None,
+            transcription: None,
             logs: Vec::new(),
             show_logs: false,
             transcription_initiated: false,
             available_models,
             selected_model_index,
             model_change_requested: false,
+            model_reload_requested: false,
+            model_redownload_requested: false,
+            model_load_cancel_requested: false,
+            selected_setting: 0,
+            saved_recording: None,
+            countdown_until: None,
+            session_text: String::new(),
+            setting_input: None,
+            key_release_events: false,
+            transcribing_since: None,
+            last_activity: Instant::now(),
+            model_unloaded: false,
+            ms_per_audio_sec: None,
+            loaded_whisper: None,
+            model_warning: None,
+            supported_audio_configs: Vec::new(),
+            unsupported_setting: None,
+            clipboard_restore_requested: false,
+            copy_requested: false,
+            pending_transcriptions: 0,
+            pending_audio_secs: VecDeque::new(),
+            next_job_id: 0,
+            last_job_id: None,
+            mic_test_levels: Vec::new(),
+            last_recording: Vec::new(),
+            retry_pending: false,
+            config_reload_requested: false,
+            language_change_requested: false,
+            cancel_requested: false,
+            speech_started: false,
+            trailing_silence: Duration::default(),
+            stats: Stats::default(),
+            finishing_up: false,
+            refine_requested: false,
+            clipboard_refine_requested: false,
+            export_requested: false,
+            logs_copy_requested: false,
+            profile_output: None,
+            live_transcript: None,
+            refinements: Vec::new(),
+            selected_refinement: 0,
         }
     }
 
+    /// Swap in a reloaded config, keeping the model selector in sync
+    pub fn apply_config(&mut self, config: Config) {
+        self.available_models = config.whisper.model_list();
+        self.selected_model_index = self
+            .available_models
+            .iter()
+            .position(|m| m == &config.whisper.model)
+            .unwrap_or(0);
+        self.config = config;
+    }
+
+    /// Keep the last couple of seconds of raw samples for the waveform display
+    pub fn push_waveform_history(&mut self, samples: &[f32]) {
+        const WAVEFORM_HISTORY_SECS: usize = 2;
+        let capacity = self.config.audio.sample_rate as usize
+            * self.config.audio.channels as usize
+            * WAVEFORM_HISTORY_SECS;
+        self.waveform_history.extend(samples);
+        let excess = self.waveform_history.len().saturating_sub(capacity);
+        self.waveform_history.drain(..excess);
+    }
+
     pub fn tick(&mut self) {
-        if let AppState::Recording = self.state {
+        if let AppState::Recording | AppState::MicTest = self.state {
             self.recording_duration += Duration::from_millis(100);
         }
+        if self.state == AppState::Transcribing {
+            self.transcribing_since.get_or_insert_with(Instant::now);
+        } else {
+            self.transcribing_since = None;
+        }
+        if !matches!(self.state, AppState::Idle | AppState::Finished) {
+            self.touch(); // Recording, transcribing, a menu open and so on isn't idle
+        }
+    }
+
+    /// Note user activity, restarting the `ui.auto_quit_after` timer
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// Whether `ui.auto_quit_after` has passed without activity and its action is still due
+    pub fn idle_expired(&self) -> bool {
+        let Some(after) = self.config.ui.auto_quit_after.filter(|secs| *secs > 0.0) else {
+            return false;
+        };
+        matches!(self.state, AppState::Idle | AppState::Finished)
+            && !self.has_pending_transcriptions()
+            && !self.model_unloaded
+            && self.last_activity.elapsed() >= Duration::from_secs_f64(after)
+    }
+
+    /// How long the current transcription has been running
+    pub fn transcribing_elapsed(&self) -> Duration {
+        self.transcribing_since
+            .map(|since| since.elapsed())
+            .unwrap_or_default()
+    }
+
+    /// Estimated time left for the transcription in flight, from the benchmarked model
+    /// speed. Audio is rounded up to whole whisper windows since each one costs the same.
+    pub fn transcription_time_left(&self, elapsed: Duration) -> Option<Duration> {
+        let &(_, audio_secs) = self.pending_audio_secs.front()?;
+        let audio_secs = (audio_secs / WHISPER_WINDOW_SECS).ceil().max(1.0) * WHISPER_WINDOW_SECS;
+        let estimate = Duration::from_secs_f32(audio_secs * self.ms_per_audio_sec? / 1000.0);
+        Some(estimate.saturating_sub(elapsed))
+    }
+
+    /// Update the current default input device, `None` when no device is available
+    pub fn set_input_device(&mut self, device_name: Option<String>) {
+        self.input_device_available = device_name.is_some();
+        self.device_name = device_name.unwrap_or_else(|| NO_INPUT_DEVICE.to_string());
     }
 
     pub fn start_recording(&mut self) {
@@ -77,9 +292,83 @@ impl App {
             self.state = AppState::Recording;
             self.recording_duration = Duration::default();
             self.audio_waveform.clear();
-            self.transcribed_text = None;
+            self.waveform_history.clear();
+            self.transcription = None;
+            self.live_transcript = None;
             self.transcription_initiated = false;
+            self.last_recording.clear();
+            self.speech_started = false;
+            self.trailing_silence = Duration::default();
+        }
+    }
+
+    /// Record only while Space is held, with `ui.record_mode = "hold"` on a terminal
+    /// that reports key releases
+    pub fn hold_to_record(&self) -> bool {
+        self.config.ui.record_mode == RecordMode::Hold && self.key_release_events
+    }
+
+    /// Count down `ui.record_countdown` seconds before recording starts
+    pub fn start_countdown(&mut self) {
+        let secs = u64::from(self.config.ui.record_countdown);
+        self.countdown_until = Some(Instant::now() + Duration::from_secs(secs));
+    }
+
+    /// Whole seconds left before a counted-down recording starts
+    pub fn countdown_left(&self) -> Option<u64> {
+        self.countdown_until.map(|until| {
+            let left = until.saturating_duration_since(Instant::now());
+            left.as_millis().div_ceil(1000) as u64
+        })
+    }
+
+    /// Clear a countdown that has run out, returning whether recording should start now
+    pub fn take_finished_countdown(&mut self) -> bool {
+        match self.countdown_until {
+            Some(until) if Instant::now() >= until => {
+                self.countdown_until = None;
+                // Opening another screen meanwhile abandons the recording
+                self.state == AppState::Idle
+            }
+            _ => false,
+        }
+    }
+
+    /// Flash the go signal during the first moments of a counted-down recording
+    pub fn show_go_signal(&self) -> bool {
+        self.config.ui.record_countdown > 0
+            && self.state == AppState::Recording
+            && !self.is_armed()
+            && self.recording_duration < GO_SIGNAL
+    }
+
+    /// Whether a voice-activated recording is still waiting for speech
+    pub fn is_armed(&self) -> bool {
+        self.state == AppState::Recording
+            && self.config.audio.voice_activated
+            && !self.speech_started
+    }
+
+    /// Gate a recorded chunk on voice activity. With `audio.voice_activated` capture only
+    /// starts once the level crosses the silence threshold, and the recording stops after
+    /// `silence_duration` of trailing silence.
+    pub fn voice_activity(&mut self, level: f32, chunk: Duration) -> VoiceActivity {
+        let audio = &self.config.audio;
+        if !audio.voice_activated {
+            return VoiceActivity::Capture;
+        }
+        if level >= audio.silence_threshold {
+            self.speech_started = true;
+            self.trailing_silence = Duration::default();
+        } else if !self.speech_started {
+            return VoiceActivity::Armed;
+        } else {
+            self.trailing_silence += chunk;
+            if self.trailing_silence.as_secs_f64() >= audio.silence_duration {
+                self.stop_recording();
+            }
         }
+        VoiceActivity::Capture
     }
 
     pub fn stop_recording(&mut self) {
@@ -88,9 +377,72 @@ impl App {
         }
     }
 
-    pub fn finish_processing(&mut self, text: String) {
-        self.transcribed_text = Some(text);
-        self.state = AppState::Finished;
+    /// Hand a recording to the transcription queue, returning its job id
+    pub fn enqueue_transcription(&mut self, audio: &[f32]) -> u64 {
+        self.next_job_id += 1;
+        self.pending_transcriptions += 1;
+        let audio_secs = audio.len() as f32
+            / (self.config.audio.sample_rate * self.config.audio.channels as u32) as f32;
+        self.pending_audio_secs
+            .push_back((self.next_job_id, audio_secs));
+        if self.config.ui.queue_recordings && self.state == AppState::Transcribing {
+            // Don't wait for the result, allow the next recording right away
+            self.state = AppState::Idle;
+            self.transcription_initiated = false;
+        }
+        self.next_job_id
+    }
+
+    pub fn finish_processing(&mut self, job_id: u64, result: TranscriptionResult) {
+        self.pending_transcriptions = self.pending_transcriptions.saturating_sub(1);
+        self.pending_audio_secs.retain(|&(id, _)| id != job_id);
+        self.live_transcript = None; // The final transcription replaces it
+        if result == TranscriptionResult::Cancelled {
+            return; // Already back to idle since the cancel key press
+        }
+        self.last_job_id = Some(job_id);
+        self.transcription = Some(result);
+        if !self.config.ui.queue_recordings && self.state == AppState::Transcribing {
+            self.state = AppState::Finished;
+        }
+    }
+
+    /// Drop a stopped recording without transcribing it, e.g. when it's too short
+    pub fn discard_recording(&mut self) {
+        self.live_transcript = None;
+        if self.state == AppState::Transcribing {
+            self.state = AppState::Idle;
+            self.transcription_initiated = false;
+            self.audio_waveform.clear();
+            self.waveform_history.clear();
+        }
+    }
+
+    /// Abandon the transcription being waited on and return to idle
+    pub fn cancel_transcription(&mut self) {
+        if self.state == AppState::Transcribing {
+            self.state = AppState::Idle;
+            self.transcription_initiated = false;
+            self.cancel_requested = true;
+        }
+    }
+
+    /// Text of the last successful transcription
+    pub fn transcribed_text(&self) -> Option<&str> {
+        match &self.transcription {
+            Some(TranscriptionResult::Text(transcription)) => Some(&transcription.text),
+            _ => None,
+        }
+    }
+
+    /// Clear a "no speech" or error message from the transcription panel
+    pub fn dismiss_result(&mut self) {
+        if matches!(
+            self.transcription,
+            Some(TranscriptionResult::NoSpeech | TranscriptionResult::Error(_))
+        ) {
+            self.transcription = None;
+        }
     }
 
     pub fn reset(&mut self) {
@@ -98,10 +450,26 @@ impl App {
             self.state = AppState::Idle;
             self.transcription_initiated = false;
             self.audio_waveform.clear(); // Clear waveform when finished
+            self.waveform_history.clear();
         }
     }
 
+    /// Whether a recording is still being handed over or transcribed
+    pub fn has_pending_transcriptions(&self) -> bool {
+        self.pending_transcriptions > 0 || self.state == AppState::Transcribing
+    }
+
+    /// Quit, first letting pending transcriptions finish. Quitting again while finishing up
+    /// quits right away and cancels them.
     pub fn quit(&mut self) {
+        if self.has_pending_transcriptions() && !self.finishing_up {
+            self.finishing_up = true;
+            self.add_log_message(format!(
+                "⏳ Finishing up {} transcription(s) before quitting, press Q again to cancel them",
+                self.pending_transcriptions.max(1)
+            ));
+            return;
+        }
         self.running = false;
     }
 
@@ -124,10 +492,99 @@ impl App {
     pub fn exit_model_selection(&mut self) {
         if self.state == AppState::ModelSelection {
             self.state = AppState::Idle;
+            self.model_warning = None;
+        }
+    }
+
+    pub fn cancel_model_selection(&mut self) {
+        if self.model_warning.take().is_some() {
+            return; // Back to the list
+        }
+        self.retry_pending = false;
+        self.exit_model_selection();
+    }
+
+    /// Load the current model again, e.g. when it loaded but every transcription fails.
+    /// With `redownload` the cached file is deleted first so it's fetched again.
+    pub fn request_model_reload(&mut self, redownload: bool) {
+        if matches!(self.state, AppState::Idle | AppState::Finished) {
+            self.state = AppState::Idle;
+            self.model_reload_requested = true;
+            self.model_redownload_requested = redownload;
+        }
+    }
+
+    /// Export the last recording's full-resolution samples, e.g. to look into a bad transcription
+    pub fn request_export(&mut self) {
+        if self.last_recording.is_empty() {
+            self.add_log_message("No recording to export".to_string());
+        } else {
+            self.export_requested = true;
+        }
+    }
+
+    /// Show the latest live transcription until the final one replaces it on stop
+    pub fn set_live_transcript(&mut self, text: String) {
+        if matches!(self.state, AppState::Recording | AppState::Transcribing) && !text.is_empty() {
+            self.live_transcript = Some(text);
+        }
+    }
+
+    /// Copy the log messages to the clipboard, e.g. to paste into a bug report
+    pub fn request_logs_copy(&mut self) {
+        if self.logs.is_empty() {
+            self.add_log_message("No logs to copy".to_string());
+        } else {
+            self.logs_copy_requested = true;
+        }
+    }
+
+    /// Pick a model to re-transcribe the last recording with
+    pub fn request_retry(&mut self) {
+        if self.last_recording.is_empty() {
+            self.add_log_message("No recording to re-transcribe".to_string());
+            return;
+        }
+        if matches!(self.state, AppState::Idle | AppState::Finished) {
+            self.state = AppState::Idle;
+            self.enter_model_selection();
+            self.retry_pending = true;
+        }
+    }
+
+    /// Start a pending retry once the chosen model is ready, returning the audio to transcribe
+    pub fn take_retry(&mut self) -> Option<Vec<f32>> {
+        if !self.retry_pending || self.state != AppState::Idle {
+            return None;
+        }
+        self.retry_pending = false;
+        self.state = AppState::Transcribing;
+        self.transcription_initiated = true; // Audio is already captured, nothing to stop
+        self.transcription = None;
+        Some(self.last_recording.clone())
+    }
+
+    /// Go back to the last successfully loaded model after a model load was cancelled
+    pub fn revert_model_change(&mut self) {
+        self.retry_pending = false;
+        self.state = AppState::Idle;
+        let Some(whisper) = self.loaded_whisper.clone() else {
+            self.model_status = "❌ No model loaded".to_string();
+            return;
+        };
+        self.model_status = "✅ Model Ready".to_string();
+        if whisper != self.config.whisper {
+            let mut config = self.config.clone();
+            config.whisper = whisper;
+            self.apply_config(config);
+            if let Err(e) = self.config.save() {
+                self.add_log_message(format!("Failed to save config: {e}"));
+            }
         }
     }
 
     pub fn select_previous_model(&mut self) {
+        self.model_warning = None;
         if self.selected_model_index > 0 {
             self.selected_model_index -= 1;
         } else {
@@ -136,6 +593,7 @@ impl App {
     }
 
     pub fn select_next_model(&mut self) {
+        self.model_warning = None;
         if self.selected_model_index < self.available_models.len() - 1 {
             self.selected_model_index += 1;
         } else {
@@ -151,10 +609,225 @@ impl App {
         &self.config.whisper.model
     }
 
+    /// Confirm the selected model, first showing any download size or speed warning
     pub fn confirm_model_selection(&mut self) {
+        if self.model_warning.is_none() && self.get_selected_model() != self.get_current_model() {
+            self.model_warning =
+                model_download_warning(&self.config.whisper, self.get_selected_model());
+            if self.model_warning.is_some() {
+                return;
+            }
+        }
+        self.model_warning = None;
         self.model_change_requested = true;
     }
 
+    pub fn start_mic_test(&mut self) {
+        if self.state == AppState::Idle {
+            self.state = AppState::MicTest;
+            self.recording_duration = Duration::default();
+            self.audio_waveform.clear();
+            self.waveform_history.clear();
+            self.mic_test_levels.clear();
+        }
+    }
+
+    pub fn stop_mic_test(&mut self) {
+        if self.state == AppState::MicTest {
+            self.state = AppState::Idle;
+            self.recording_duration = Duration::default();
+            self.audio_waveform.clear();
+            self.waveform_history.clear();
+            self.audio_level = 0.0;
+        }
+    }
+
+    /// Record a live level, sampling the noise floor during the calibration window
+    pub fn record_mic_test_level(&mut self, level: f32) {
+        self.audio_level = level;
+        if self.recording_duration < MIC_TEST_CALIBRATION {
+            self.mic_test_levels.push(level);
+        }
+    }
+
+    pub fn mic_test_calibrated(&self) -> bool {
+        self.recording_duration >= MIC_TEST_CALIBRATION && !self.mic_test_levels.is_empty()
+    }
+
+    /// Average level observed during the calibration window
+    pub fn mic_test_noise_floor(&self) -> Option<f32> {
+        if self.mic_test_levels.is_empty() {
+            return None;
+        }
+        Some(self.mic_test_levels.iter().sum::<f32>() / self.mic_test_levels.len() as f32)
+    }
+
+    pub fn suggested_silence_threshold(&self) -> Option<f32> {
+        if !self.mic_test_calibrated() {
+            return None;
+        }
+        suggest_silence_threshold(&self.mic_test_levels)
+    }
+
+    /// Save the suggested silence threshold to the config file
+    pub fn apply_suggested_threshold(&mut self) {
+        let Some(threshold) = self.suggested_silence_threshold() else {
+            self.add_log_message("Still measuring the noise floor, stay quiet...".to_string());
+            return;
+        };
+        match self.config.update_silence_threshold(threshold) {
+            Ok(()) => self.add_log_message(format!("🎚️ Silence threshold set to {threshold:.1}")),
+            Err(e) => self.add_log_message(format!("Failed to save silence threshold: {e}")),
+        }
+    }
+
+    /// Raise or lower the input gain by `step` and save it
+    pub fn adjust_gain(&mut self, step: f32) {
+        let gain = next_gain(self.config.audio.gain, step);
+        match self.config.update_gain(gain) {
+            Ok(()) => self.add_log_message(format!("🔊 Input gain {gain:.1}x")),
+            Err(e) => self.add_log_message(format!("Failed to save input gain: {e}")),
+        }
+    }
+
+    /// Switch to the next language in `whisper.languages` and save it as the default
+    pub fn cycle_language(&mut self) {
+        let languages = &self.config.whisper.languages;
+        if languages.is_empty() {
+            self.add_log_message(
+                "No languages to switch between, set whisper.languages in the config".to_string(),
+            );
+            return;
+        }
+        let current = self.config.whisper.language.as_deref().unwrap_or("auto");
+        let next = languages
+            .iter()
+            .position(|language| language == current)
+            .map_or(0, |i| (i + 1) % languages.len());
+        let language = languages[next].clone();
+
+        self.config.whisper.language = (language != "auto").then(|| language.clone());
+        self.language_change_requested = true;
+        if let Err(e) = self.config.save() {
+            self.add_log_message(format!("Failed to save language: {e}"));
+        }
+        self.add_log_message(format!("🌐 Language: {language}"));
+    }
+
+    /// Refine the transcription with several LLM profiles to compare side by side
+    pub fn request_refinement(&mut self) {
+        if !matches!(self.state, AppState::Idle | AppState::Finished) {
+            return;
+        }
+        if self.transcribed_text().is_some() {
+            self.refine_requested = true;
+        } else {
+            self.add_log_message("Nothing to refine".to_string());
+        }
+    }
+
+    /// Add a transcription to the session with `clipboard.accumulate`, returning the
+    /// combined text to copy
+    pub fn accumulate(&mut self, text: &str) -> &str {
+        if !self.session_text.is_empty() {
+            self.session_text
+                .push_str(&self.config.clipboard.accumulate_separator);
+        }
+        self.session_text.push_str(text);
+        &self.session_text
+    }
+
+    /// Start a new accumulated session, the clipboard keeps the old one until the next copy
+    pub fn new_session(&mut self) {
+        self.session_text.clear();
+        if self.config.clipboard.accumulate {
+            self.add_log_message("📋 Started a new dictation session".to_string());
+        }
+    }
+
+    /// Note where a recording was kept, shown in the status for a few seconds
+    pub fn recording_saved(&mut self, path: PathBuf) {
+        self.add_log_message(format!("💾 Recording saved to {}", path.display()));
+        self.saved_recording = Some((path, Instant::now()));
+    }
+
+    /// The recently saved recording, while its notice is still showing
+    pub fn saved_recording_notice(&self) -> Option<&Path> {
+        self.saved_recording
+            .as_ref()
+            .filter(|(_, saved)| saved.elapsed() < SAVED_NOTICE)
+            .map(|(path, _)| path.as_path())
+    }
+
+    /// Refine whatever text is in the clipboard, no recording needed
+    pub fn request_clipboard_refinement(&mut self) {
+        if matches!(self.state, AppState::Idle | AppState::Finished) {
+            self.clipboard_refine_requested = true;
+        }
+    }
+
+    pub fn show_refinements(&mut self, refinements: Vec<Refinement>) {
+        if !matches!(self.state, AppState::Idle | AppState::Finished) {
+            self.add_log_message("Discarded LLM refinements, a recording started".to_string());
+            return;
+        }
+        self.refinements = refinements;
+        self.selected_refinement = 0;
+        self.state = AppState::RefinementSelection;
+    }
+
+    pub fn select_previous_refinement(&mut self) {
+        let count = self.refinements.len().max(1);
+        self.selected_refinement = (self.selected_refinement + count - 1) % count;
+    }
+
+    pub fn select_next_refinement(&mut self) {
+        self.selected_refinement = (self.selected_refinement + 1) % self.refinements.len().max(1);
+    }
+
+    /// Use the selected refinement as the transcription and send it to its profile's output
+    pub fn choose_refinement(&mut self) {
+        match self.refinements.get(self.selected_refinement) {
+            Some((profile, Ok(text))) => {
+                let text = text.clone();
+                let output = self
+                    .config
+                    .llm
+                    .profiles
+                    .get(profile)
+                    .and_then(|profile| profile.output.as_deref());
+                match ProfileOutput::parse(output) {
+                    Ok(ProfileOutput::Clipboard) => self.copy_requested = true,
+                    Ok(output) => self.profile_output = Some((output, text.clone())),
+                    Err(e) => {
+                        self.add_log_message(format!("⚠️ {profile}: {e:#}, copying instead"));
+                        self.copy_requested = true;
+                    }
+                }
+                match &mut self.transcription {
+                    Some(TranscriptionResult::Text(transcription)) => transcription.text = text,
+                    _ => {
+                        let model = self.config.llm.model.clone();
+                        self.transcription =
+                            Some(TranscriptionResult::Text(Transcription::new(text, &model)));
+                    }
+                }
+                self.exit_refinement_selection();
+            }
+            Some((profile, Err(_))) => {
+                self.add_log_message(format!("Refinement with {profile} failed, pick another"));
+            }
+            None => self.exit_refinement_selection(),
+        }
+    }
+
+    pub fn exit_refinement_selection(&mut self) {
+        if self.state == AppState::RefinementSelection {
+            self.state = AppState::Idle;
+            self.refinements.clear();
+        }
+    }
+
     pub fn enter_shortcuts(&mut self) {
         if matches!(self.state, AppState::Idle | AppState::Finished) {
             self.state = AppState::ShowingShortcuts;
@@ -166,4 +839,542 @@ impl App {
             self.state = AppState::Idle;
         }
     }
+
+    /// Open the settings screen, `supported_audio_configs` lists the device's valid
+    /// sample rate and channel combinations
+    pub fn enter_settings(&mut self, supported_audio_configs: Vec<(u32, u16)>) {
+        if matches!(self.state, AppState::Idle | AppState::Finished) {
+            self.supported_audio_configs = supported_audio_configs;
+            self.state = AppState::Settings;
+            self.setting_input = None;
+        }
+    }
+
+    pub fn exit_settings(&mut self) {
+        if self.state == AppState::Settings {
+            self.state = AppState::Idle;
+            self.setting_input = None;
+        }
+    }
+
+    pub fn select_previous_setting(&mut self) {
+        self.selected_setting = (self.selected_setting + SETTINGS.len() - 1) % SETTINGS.len();
+    }
+
+    pub fn select_next_setting(&mut self) {
+        self.selected_setting = (self.selected_setting + 1) % SETTINGS.len();
+    }
+
+    pub fn selected_setting_key(&self) -> &'static str {
+        SETTINGS[self.selected_setting]
+    }
+
+    /// Start typing a new value, beginning from the current one
+    pub fn edit_setting(&mut self) {
+        self.unsupported_setting = None;
+        let current = self.config.get_nested(self.selected_setting_key());
+        self.setting_input = Some(current.unwrap_or_default());
+    }
+
+    /// Check the typed value and save it, reloading the config so it takes effect.
+    /// Invalid values are logged and stay in the input to be corrected.
+    pub fn confirm_setting(&mut self) {
+        let Some(input) = self.setting_input.clone() else {
+            return;
+        };
+        let key = self.selected_setting_key();
+        let mut config = self.config.clone();
+        if let Err(e) = config.set_nested(key, &input) {
+            self.add_log_message(format!("❌ {e:#}"));
+            return;
+        }
+        let audio = (config.audio.sample_rate, config.audio.channels);
+        if matches!(key, "audio.sample_rate" | "audio.channels")
+            && !self.supported_audio_configs.is_empty()
+            && !self.supported_audio_configs.contains(&audio)
+            && self.unsupported_setting.as_ref() != Some(&input)
+        {
+            self.add_log_message(format!(
+                "⚠️ {} Hz with {} channel(s) isn't supported by {}, Enter again to save anyway",
+                audio.0, audio.1, self.device_name
+            ));
+            self.unsupported_setting = Some(input);
+            return;
+        }
+        self.unsupported_setting = None;
+        if let Err(e) = config.save() {
+            self.add_log_message(format!("Failed to save config: {e}"));
+            return;
+        }
+        self.setting_input = None;
+        let value = config
+            .get_nested(key)
+            .unwrap_or_else(|| "unset".to_string());
+        self.add_log_message(format!("⚙️ {key} = {value}"));
+        self.config_reload_requested = true;
+    }
+}
+
+/// Gain after a `step`, rounded to a tenth and kept within 0.1x-10x
+fn next_gain(gain: f32, step: f32) -> f32 {
+    ((gain + step) * 10.0).round().clamp(1.0, 100.0) / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHUNK: Duration = Duration::from_millis(500);
+
+    fn text_result(text: &str) -> TranscriptionResult {
+        TranscriptionResult::Text(Transcription::new(text.to_string(), "base.en"))
+    }
+
+    #[test]
+    fn test_voice_activity() {
+        let mut config = Config::default();
+        config.audio.voice_activated = true;
+        config.audio.silence_threshold = 10.0;
+        config.audio.silence_duration = 1.0;
+        let mut app = App::new(config, Some("mic".to_string()));
+        app.state = AppState::Idle;
+        app.start_recording();
+
+        assert!(app.is_armed());
+        assert_eq!(app.voice_activity(2.0, CHUNK), VoiceActivity::Armed);
+        assert_eq!(app.voice_activity(30.0, CHUNK), VoiceActivity::Capture);
+        assert!(!app.is_armed());
+
+        // Trailing silence is kept until it lasts silence_duration
+        assert_eq!(app.voice_activity(2.0, CHUNK), VoiceActivity::Capture);
+        assert_eq!(app.voice_activity(30.0, CHUNK), VoiceActivity::Capture);
+        assert_eq!(app.voice_activity(2.0, CHUNK), VoiceActivity::Capture);
+        assert!(app.state == AppState::Recording);
+        assert_eq!(app.voice_activity(2.0, CHUNK), VoiceActivity::Capture);
+        assert!(app.state == AppState::Transcribing);
+    }
+
+    #[test]
+    fn test_cycle_language_without_languages() {
+        let mut app = App::new(Config::default(), None);
+        app.cycle_language();
+        assert_eq!(app.config.whisper.language.as_deref(), Some("en"));
+        assert!(!app.language_change_requested);
+    }
+
+    #[test]
+    fn test_quit_waits_for_pending_transcriptions() {
+        let mut app = App::new(Config::default(), None);
+        app.state = AppState::Idle;
+        app.quit();
+        assert!(!app.running);
+
+        let mut app = App::new(Config::default(), None);
+        app.state = AppState::Transcribing;
+        let id = app.enqueue_transcription(&[]);
+        app.quit();
+        assert!(app.running && app.finishing_up);
+        app.finish_processing(id, text_result("done"));
+        assert!(!app.has_pending_transcriptions());
+
+        // Quitting again while finishing up doesn't wait
+        let mut app = App::new(Config::default(), None);
+        app.state = AppState::Transcribing;
+        app.quit();
+        app.quit();
+        assert!(!app.running);
+    }
+
+    #[test]
+    fn test_choose_refinement() {
+        let mut app = App::new(Config::default(), None);
+        app.state = AppState::Finished;
+        app.transcription = Some(text_result("raw"));
+        app.show_refinements(vec![
+            ("email".to_string(), Err("timeout".to_string())),
+            ("general".to_string(), Ok("Refined.".to_string())),
+        ]);
+        assert!(app.state == AppState::RefinementSelection);
+
+        app.choose_refinement();
+        assert!(app.state == AppState::RefinementSelection);
+        app.select_previous_refinement();
+        app.choose_refinement();
+        assert_eq!(app.transcribed_text(), Some("Refined."));
+        assert!(app.copy_requested);
+        assert!(app.state == AppState::Idle);
+    }
+
+    #[test]
+    fn test_choose_refinement_with_profile_output() {
+        let mut config = Config::default();
+        config.llm.profiles.get_mut("todo").unwrap().output = Some("command:todo.sh add".into());
+        let mut app = App::new(config, None);
+        app.state = AppState::Finished;
+        app.show_refinements(vec![("todo".to_string(), Ok("Buy milk".to_string()))]);
+
+        app.choose_refinement();
+        assert!(!app.copy_requested);
+        assert_eq!(
+            app.profile_output,
+            Some((
+                ProfileOutput::Command("todo.sh add".to_string()),
+                "Buy milk".to_string()
+            ))
+        );
+        assert_eq!(app.transcribed_text(), Some("Buy milk"));
+    }
+
+    #[test]
+    fn test_revert_model_change() {
+        let mut app = App::new(Config::default(), None);
+        app.state = AppState::LoadingModel;
+        app.revert_model_change();
+        assert!(app.state == AppState::Idle);
+        assert_eq!(app.model_status, "❌ No model loaded");
+
+        app.loaded_whisper = Some(app.config.whisper.clone());
+        app.state = AppState::LoadingModel;
+        app.retry_pending = true;
+        app.revert_model_change();
+        assert!(app.state == AppState::Idle);
+        assert!(!app.retry_pending);
+        assert_eq!(app.model_status, "✅ Model Ready");
+    }
+
+    #[test]
+    fn test_voice_activity_disabled() {
+        let mut app = App::new(Config::default(), Some("mic".to_string()));
+        app.state = AppState::Idle;
+        app.start_recording();
+
+        assert!(!app.is_armed());
+        for _ in 0..10 {
+            assert_eq!(app.voice_activity(0.0, CHUNK), VoiceActivity::Capture);
+        }
+        assert!(app.state == AppState::Recording);
+    }
+
+    #[test]
+    fn test_discard_recording() {
+        let mut app = App::new(Config::default(), Some("mic".to_string()));
+        app.state = AppState::Idle;
+        app.start_recording();
+        app.stop_recording();
+        app.transcription_initiated = true;
+
+        app.discard_recording();
+        assert!(app.state == AppState::Idle);
+        assert!(!app.transcription_initiated);
+        assert!(!app.has_pending_transcriptions());
+    }
+
+    #[test]
+    fn test_wait_for_transcription() {
+        let config = Config {
+            ui: crate::config::UiConfig {
+                queue_recordings: false,
+                ..crate::config::UiConfig::default()
+            },
+            ..Config::default()
+        };
+        let mut app = App::new(config, Some("mic".to_string()));
+        app.state = AppState::Idle;
+        app.start_recording();
+        app.stop_recording();
+        let id = app.enqueue_transcription(&[]);
+        assert!(app.state == AppState::Transcribing);
+
+        // No new recording until the result is in
+        app.start_recording();
+        assert!(app.state == AppState::Transcribing);
+        app.finish_processing(id, text_result("one"));
+        assert!(app.state == AppState::Finished);
+        assert_eq!(app.transcribed_text(), Some("one"));
+        app.reset();
+        assert!(app.state == AppState::Idle);
+    }
+
+    #[test]
+    fn test_queue_recordings() {
+        let config = Config {
+            ui: crate::config::UiConfig {
+                queue_recordings: true,
+                ..crate::config::UiConfig::default()
+            },
+            ..Config::default()
+        };
+        let mut app = App::new(config, Some("mic".to_string()));
+        app.state = AppState::Idle;
+        app.start_recording();
+        app.stop_recording();
+        let first = app.enqueue_transcription(&[]);
+        assert!(app.state == AppState::Idle);
+        assert!(app.has_pending_transcriptions());
+
+        // Record the next clip while the first is still transcribing
+        app.start_recording();
+        assert!(app.state == AppState::Recording);
+        app.finish_processing(first, text_result("one"));
+        assert!(app.state == AppState::Recording);
+        assert_eq!(app.transcribed_text(), Some("one"));
+        app.stop_recording();
+        let second = app.enqueue_transcription(&[]);
+        assert!(second > first);
+        assert!(app.state == AppState::Idle);
+
+        app.finish_processing(second, text_result("two"));
+        assert!(app.state == AppState::Idle);
+        assert_eq!(app.last_job_id, Some(second));
+        assert!(!app.has_pending_transcriptions());
+    }
+
+    #[test]
+    fn test_next_gain() {
+        assert_eq!(next_gain(1.0, GAIN_STEP), 1.1);
+        assert_eq!(next_gain(0.1, -GAIN_STEP), 0.1);
+        assert_eq!(next_gain(10.0, GAIN_STEP), 10.0);
+    }
+
+    #[test]
+    fn test_hold_to_record_needs_key_releases() {
+        let mut config = Config::default();
+        config.ui.record_mode = RecordMode::Hold;
+        let mut app = App::new(config, None);
+        assert!(!app.hold_to_record());
+        app.key_release_events = true;
+        assert!(app.hold_to_record());
+        app.config.ui.record_mode = RecordMode::Toggle;
+        assert!(!app.hold_to_record());
+    }
+
+    #[test]
+    fn test_settings_navigation_and_invalid_value() {
+        let mut app = App::new(Config::default(), None);
+        app.state = AppState::Idle;
+        app.enter_settings(Vec::new());
+        assert!(app.state == AppState::Settings);
+
+        app.select_previous_setting();
+        assert_eq!(app.selected_setting_key(), SETTINGS[SETTINGS.len() - 1]);
+        app.select_next_setting();
+        assert_eq!(app.selected_setting_key(), "audio.silence_threshold");
+
+        app.edit_setting();
+        assert_eq!(app.setting_input.as_deref(), Some("15.0"));
+        app.setting_input = Some("loud".to_string());
+        app.confirm_setting();
+        // Rejected values stay in the input and leave the config alone
+        assert_eq!(app.setting_input.as_deref(), Some("loud"));
+        assert_eq!(app.config.audio.silence_threshold, 15.0);
+        assert!(!app.config_reload_requested);
+
+        app.exit_settings();
+        assert!(app.state == AppState::Idle);
+        assert_eq!(app.setting_input, None);
+    }
+
+    #[test]
+    fn test_unsupported_audio_setting_warns() {
+        let mut app = App::new(Config::default(), None);
+        app.state = AppState::Idle;
+        app.enter_settings(vec![(16000, 1), (48000, 1), (48000, 2)]);
+        app.selected_setting = SETTINGS
+            .iter()
+            .position(|key| *key == "audio.sample_rate")
+            .unwrap();
+
+        app.edit_setting();
+        app.setting_input = Some("44100".to_string());
+        app.confirm_setting();
+        // Not saved until confirmed a second time
+        assert_eq!(app.unsupported_setting.as_deref(), Some("44100"));
+        assert_eq!(app.setting_input.as_deref(), Some("44100"));
+        assert!(!app.config_reload_requested);
+    }
+
+    #[test]
+    fn test_request_clipboard_refinement() {
+        let mut app = App::new(Config::default(), None);
+        app.request_clipboard_refinement();
+        assert!(!app.clipboard_refine_requested); // Still loading the model
+        app.state = AppState::Idle;
+        app.request_clipboard_refinement();
+        // No transcription needed, unlike P
+        assert!(app.clipboard_refine_requested);
+    }
+
+    #[test]
+    fn test_recording_saved_notice() {
+        let mut app = App::new(Config::default(), None);
+        assert_eq!(app.saved_recording_notice(), None);
+        let path = PathBuf::from("/tmp/recordings/recording-20260101-120000.000.wav");
+        app.recording_saved(path.clone());
+        assert_eq!(app.saved_recording_notice(), Some(path.as_path()));
+        assert!(app
+            .logs
+            .last()
+            .unwrap()
+            .contains("recording-20260101-120000.000.wav"));
+
+        app.saved_recording = Some((path, Instant::now() - SAVED_NOTICE));
+        assert_eq!(app.saved_recording_notice(), None);
+    }
+
+    #[test]
+    fn test_countdown() {
+        let config = Config {
+            ui: crate::config::UiConfig {
+                record_countdown: 3,
+                ..crate::config::UiConfig::default()
+            },
+            ..Config::default()
+        };
+        let mut app = App::new(config, None);
+        app.state = AppState::Idle;
+        assert_eq!(app.countdown_left(), None);
+        app.start_countdown();
+        assert_eq!(app.countdown_left(), Some(3));
+        assert!(!app.take_finished_countdown());
+
+        app.countdown_until = Some(Instant::now() - Duration::from_millis(1));
+        assert_eq!(app.countdown_left(), Some(0));
+        assert!(app.take_finished_countdown());
+        assert_eq!(app.countdown_until, None);
+
+        // Abandoned when another screen was opened during the countdown
+        app.countdown_until = Some(Instant::now() - Duration::from_millis(1));
+        app.state = AppState::ModelSelection;
+        assert!(!app.take_finished_countdown());
+        assert_eq!(app.countdown_until, None);
+    }
+
+    #[test]
+    fn test_go_signal() {
+        let mut config = Config::default();
+        config.ui.record_countdown = 3;
+        let mut app = App::new(config, None);
+        app.state = AppState::Idle;
+        app.start_recording();
+        assert!(app.show_go_signal());
+        app.recording_duration = GO_SIGNAL;
+        assert!(!app.show_go_signal());
+
+        app.config.ui.record_countdown = 0;
+        app.recording_duration = Duration::ZERO;
+        assert!(!app.show_go_signal());
+    }
+
+    #[test]
+    fn test_request_model_reload() {
+        let mut app = App::new(Config::default(), None);
+        app.request_model_reload(false);
+        assert!(!app.model_reload_requested); // Already loading
+
+        app.state = AppState::Finished;
+        app.request_model_reload(true);
+        assert!(app.state == AppState::Idle);
+        assert!(app.model_reload_requested);
+        assert!(app.model_redownload_requested);
+    }
+
+    #[test]
+    fn test_accumulate_session() {
+        let mut config = Config::default();
+        config.clipboard.accumulate = true;
+        config.clipboard.accumulate_separator = "\n".to_string();
+        let mut app = App::new(config, None);
+        assert_eq!(app.accumulate("First line."), "First line.");
+        assert_eq!(app.accumulate("Second line."), "First line.\nSecond line.");
+
+        app.new_session();
+        assert_eq!(app.accumulate("Fresh start."), "Fresh start.");
+    }
+
+    #[test]
+    fn test_transcription_time_left() {
+        let mut app = App::new(Config::default(), None);
+        assert_eq!(app.transcription_time_left(Duration::ZERO), None);
+        let config = &app.config.audio;
+        let first = vec![0.0; (config.sample_rate * config.channels as u32) as usize * 4];
+        let second = vec![0.0; first.len() * 10];
+        let first_id = app.enqueue_transcription(&first);
+        app.enqueue_transcription(&second);
+        assert_eq!(app.transcription_time_left(Duration::ZERO), None);
+
+        // A 4 s recording still costs a full 30 s window
+        app.ms_per_audio_sec = Some(50.0);
+        assert_eq!(
+            app.transcription_time_left(Duration::from_millis(500)),
+            Some(Duration::from_millis(1000))
+        );
+        // Running over the estimate doesn't go negative
+        assert_eq!(
+            app.transcription_time_left(Duration::from_secs(2)),
+            Some(Duration::ZERO)
+        );
+        // Then the 40 s one queued behind it, two windows
+        app.finish_processing(first_id, TranscriptionResult::NoSpeech);
+        assert_eq!(
+            app.transcription_time_left(Duration::ZERO),
+            Some(Duration::from_secs(3))
+        );
+    }
+
+    #[test]
+    fn test_request_logs_copy() {
+        let mut app = App::new(Config::default(), None);
+        app.logs.clear();
+        app.request_logs_copy();
+        assert!(!app.logs_copy_requested);
+
+        app.request_logs_copy();
+        assert!(app.logs_copy_requested);
+    }
+
+    #[test]
+    fn test_live_transcript_until_final() {
+        let mut app = App::new(Config::default(), None);
+        app.set_live_transcript("too early".to_string());
+        assert_eq!(app.live_transcript, None);
+
+        app.state = AppState::Idle;
+        app.start_recording();
+        app.set_live_transcript("hello".to_string());
+        app.set_live_transcript("hello world".to_string());
+        assert_eq!(app.live_transcript.as_deref(), Some("hello world"));
+
+        app.stop_recording();
+        let id = app.enqueue_transcription(&[]);
+        app.finish_processing(id, text_result("Hello, world."));
+        assert_eq!(app.live_transcript, None);
+    }
+
+    #[test]
+    fn test_idle_expired() {
+        let config = Config {
+            ui: crate::config::UiConfig {
+                auto_quit_after: Some(60.0),
+                ..Default::default()
+            },
+            ..Config::default()
+        };
+        let mut app = App::new(config, None);
+        app.state = AppState::Idle;
+        assert!(!app.idle_expired());
+
+        app.last_activity = Instant::now() - Duration::from_secs(61);
+        assert!(app.idle_expired());
+
+        // Recording keeps the app active
+        app.start_recording();
+        app.tick();
+        app.stop_recording();
+        app.discard_recording();
+        assert!(!app.idle_expired());
+
+        app.last_activity = Instant::now() - Duration::from_secs(61);
+        app.model_unloaded = true;
+        assert!(!app.idle_expired());
+    }
 }
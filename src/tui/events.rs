@@ -1,16 +1,69 @@
-use crate::tui::app::{App, AppState};
-use crossterm::event::{self, Event, KeyCode};
+use crate::audio::{input_device_name, supported_input_configs};
+use crate::tui::app::{App, AppState, GAIN_STEP};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, MediaKeyCode};
 use std::sync::mpsc::Sender;
 use std::time::Duration;
 
+/// Parse `keybindings.record_media_key`
+pub fn media_key(name: &str) -> Option<MediaKeyCode> {
+    match name {
+        "play_pause" => Some(MediaKeyCode::PlayPause),
+        "play" => Some(MediaKeyCode::Play),
+        "pause" => Some(MediaKeyCode::Pause),
+        "stop" => Some(MediaKeyCode::Stop),
+        "next" => Some(MediaKeyCode::TrackNext),
+        "previous" => Some(MediaKeyCode::TrackPrevious),
+        _ => None,
+    }
+}
+
+fn start_recording(app: &mut App, start_audio_tx: &Sender<()>) {
+    // With ui.record_countdown the key starts the countdown, and pressing it again cancels
+    if app.countdown_until.take().is_some() {
+        app.add_log_message("⏹️ Countdown cancelled".to_string());
+    } else if app.config.ui.record_countdown > 0 && !app.hold_to_record() {
+        app.start_countdown();
+    } else {
+        begin_recording(app, start_audio_tx);
+    }
+}
+
+fn begin_recording(app: &mut App, start_audio_tx: &Sender<()>) {
+    // Re-check on every attempt so a mic plugged in after launch is picked up
+    app.set_input_device(input_device_name(&app.config.audio));
+    if app.input_device_available {
+        app.start_recording();
+        start_audio_tx.send(()).ok(); // Signal audio thread to start
+    } else {
+        app.add_log_message(
+            "❌ No input device available - connect a microphone and try again".to_string(),
+        );
+    }
+}
+
 pub fn handle_key_events(
     app: &mut App,
     stop_audio_tx: Sender<()>,
     start_audio_tx: Sender<()>,
 ) -> anyhow::Result<()> {
+    if app.take_finished_countdown() {
+        begin_recording(app, &start_audio_tx);
+    }
     if event::poll(Duration::from_millis(50))? {
         // Reduced polling interval
         if let Event::Key(key) = event::read()? {
+            app.touch();
+            // Releases are only reported in hold-to-record mode, where they stop the recording
+            if key.kind == KeyEventKind::Release {
+                if key.code == KeyCode::Char(' ')
+                    && app.hold_to_record()
+                    && app.state == AppState::Recording
+                {
+                    stop_audio_tx.send(()).ok();
+                    app.stop_recording();
+                }
+                return Ok(());
+            }
             match app.state {
                 AppState::ModelSelection => match key.code {
                     KeyCode::Up => app.select_previous_model(),
@@ -18,7 +71,52 @@ pub fn handle_key_events(
                     KeyCode::Enter => {
                         app.confirm_model_selection();
                     }
-                    KeyCode::Esc => app.exit_model_selection(),
+                    KeyCode::Esc => app.cancel_model_selection(),
+                    KeyCode::Char('q') => app.quit(),
+                    _ => {}
+                },
+                AppState::RefinementSelection => match key.code {
+                    KeyCode::Up => app.select_previous_refinement(),
+                    KeyCode::Down => app.select_next_refinement(),
+                    KeyCode::Enter => app.choose_refinement(),
+                    KeyCode::Esc => app.exit_refinement_selection(),
+                    KeyCode::Char('q') => app.quit(),
+                    _ => {}
+                },
+                AppState::MicTest => match key.code {
+                    KeyCode::Char('a') => app.apply_suggested_threshold(),
+                    KeyCode::Char('+') | KeyCode::Char('=') => app.adjust_gain(GAIN_STEP),
+                    KeyCode::Char('-') => app.adjust_gain(-GAIN_STEP),
+                    KeyCode::Char('t') | KeyCode::Esc => {
+                        stop_audio_tx.send(()).ok();
+                        app.stop_mic_test();
+                    }
+                    KeyCode::Char('q') => {
+                        stop_audio_tx.send(()).ok();
+                        app.quit();
+                    }
+                    _ => {}
+                },
+                AppState::Settings if app.setting_input.is_some() => match key.code {
+                    KeyCode::Enter => app.confirm_setting(),
+                    KeyCode::Esc => app.setting_input = None,
+                    KeyCode::Backspace => {
+                        if let Some(input) = &mut app.setting_input {
+                            input.pop();
+                        }
+                    }
+                    KeyCode::Char(c) => {
+                        if let Some(input) = &mut app.setting_input {
+                            input.push(c);
+                        }
+                    }
+                    _ => {}
+                },
+                AppState::Settings => match key.code {
+                    KeyCode::Up => app.select_previous_setting(),
+                    KeyCode::Down => app.select_next_setting(),
+                    KeyCode::Enter => app.edit_setting(),
+                    KeyCode::Esc | KeyCode::Char('s') => app.exit_settings(),
                     KeyCode::Char('q') => app.quit(),
                     _ => {}
                 },
@@ -29,21 +127,95 @@ pub fn handle_key_events(
                 },
                 _ => {
                     match key.code {
+                        KeyCode::Esc if app.state == AppState::Transcribing => {
+                            app.cancel_transcription();
+                        }
+                        // The first load has no model to go back to, so Esc still quits then
+                        KeyCode::Esc
+                            if app.state == AppState::LoadingModel
+                                && app.loaded_whisper.is_some() =>
+                        {
+                            app.model_load_cancel_requested = true;
+                        }
                         KeyCode::Char('q') | KeyCode::Esc => app.quit(),
                         KeyCode::Char('l') => app.show_logs = !app.show_logs,
-                        KeyCode::Char('m') => {
-                            if app.state == AppState::Idle {
-                                app.enter_model_selection();
+                        KeyCode::Char('+') | KeyCode::Char('=') => app.adjust_gain(GAIN_STEP),
+                        KeyCode::Char('-') => app.adjust_gain(-GAIN_STEP),
+                        KeyCode::Char('m') => app.enter_model_selection(),
+                        KeyCode::Char('t') if app.state == AppState::Idle => {
+                            app.set_input_device(input_device_name(&app.config.audio));
+                            if app.input_device_available {
+                                app.start_mic_test();
+                                start_audio_tx.send(()).ok();
+                            } else {
+                                app.add_log_message(
+                                    "❌ No input device available - connect a microphone and try again"
+                                        .to_string(),
+                                );
                             }
                         }
+                        KeyCode::Char('R') => app.request_retry(),
+                        KeyCode::Char('M') => app.request_model_reload(false),
+                        KeyCode::Char('D') => app.request_model_reload(true),
+                        KeyCode::Char('L')
+                            if matches!(app.state, AppState::Idle | AppState::Finished) =>
+                        {
+                            app.cycle_language();
+                        }
+                        KeyCode::Enter => app.dismiss_result(),
+                        KeyCode::Char('r')
+                            if matches!(app.state, AppState::Idle | AppState::Finished) =>
+                        {
+                            app.config_reload_requested = true;
+                        }
+                        KeyCode::Char('p') => app.request_refinement(),
+                        KeyCode::Char('f') => app.request_clipboard_refinement(),
+                        KeyCode::Char('n') => app.new_session(),
+                        KeyCode::Char('x') => app.request_export(),
+                        KeyCode::Char('u') => app.clipboard_restore_requested = true,
+                        KeyCode::Char('c') => app.copy_requested = true,
+                        KeyCode::Char('y') => app.request_logs_copy(),
                         KeyCode::Char('?') => {
                             app.enter_shortcuts();
                         }
-                        KeyCode::Char(' ') => match app.state {
-                            AppState::Idle => {
-                                app.start_recording();
-                                start_audio_tx.send(()).ok(); // Signal audio thread to start
+                        KeyCode::Char('s') => {
+                            let supported = match app.config.audio.input_file {
+                                Some(_) => Vec::new(),
+                                None => supported_input_configs(&app.config.audio),
+                            };
+                            app.enter_settings(supported);
+                        }
+                        // Holding Space repeats the press, only the first one starts recording
+                        KeyCode::Char(' ')
+                            if app.hold_to_record() && key.kind == KeyEventKind::Repeat => {}
+                        KeyCode::Media(_) if key.kind == KeyEventKind::Repeat => {}
+                        // Headset buttons can't be held, so the media key always toggles and
+                        // starts straight from a finished result for hands-free dictation
+                        KeyCode::Media(media)
+                            if app
+                                .config
+                                .keybindings
+                                .record_media_key
+                                .as_deref()
+                                .and_then(media_key)
+                                == Some(media) =>
+                        {
+                            match app.state {
+                                AppState::Idle | AppState::Finished => {
+                                    app.state = AppState::Idle;
+                                    start_recording(app, &start_audio_tx);
+                                }
+                                AppState::Recording => {
+                                    stop_audio_tx.send(()).ok();
+                                    app.stop_recording();
+                                }
+                                _ => {}
                             }
+                        }
+                        KeyCode::Char(' ') => match app.state {
+                            AppState::Idle => start_recording(app, &start_audio_tx),
+                            // Stops on release instead
+                            AppState::Recording if app.hold_to_record() => {}
                             AppState::Recording => {
                                 stop_audio_tx.send(()).ok();
                                 app.stop_recording();
@@ -62,3 +234,15 @@ pub fn handle_key_events(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_key_names() {
+        assert_eq!(media_key("play_pause"), Some(MediaKeyCode::PlayPause));
+        assert_eq!(media_key("next"), Some(MediaKeyCode::TrackNext));
+        assert_eq!(media_key("volume_up"), None);
+    }
+}
@@ -1,22 +1,73 @@
-use crate::tui::app::{App, AppState};
+use crate::audio::is_monitor_device;
+use crate::config::WaveformStyle;
+use crate::stt::{format_size, model_size};
+use crate::tui::app::{App, AppState, TranscriptionResult, MIC_TEST_CALIBRATION, SETTINGS};
 use ratatui::{
     prelude::*,
-    widgets::{BarChart, Block, Borders, List, ListItem, Paragraph},
+    widgets::{
+        canvas::{Canvas, Line as CanvasLine},
+        BarChart, Block, Borders, List, ListItem, ListState, Paragraph, Wrap,
+    },
 };
 
+/// Smallest terminal the full layout fits in: margins, status, a few lines of
+/// transcription, the bottom panels and, when shown, the logs
+const MIN_WIDTH: u16 = 60;
+const MIN_HEIGHT: u16 = 16;
+const LOGS_HEIGHT: u16 = 10;
+/// Braille spinner frames for the transcribing status, one per 100ms
+const SPINNER: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+fn min_size(show_logs: bool) -> (u16, u16) {
+    let height = if show_logs {
+        MIN_HEIGHT + LOGS_HEIGHT
+    } else {
+        MIN_HEIGHT
+    };
+    (MIN_WIDTH, height)
+}
+
+fn fits(area: Rect, show_logs: bool) -> bool {
+    let (width, height) = min_size(show_logs);
+    area.width >= width && area.height >= height
+}
+
+/// Shown instead of the full layout on a terminal too small to fit it
+fn draw_too_small(frame: &mut Frame, app: &App) {
+    let area = frame.size();
+    let (width, height) = min_size(app.show_logs);
+    let mut text = format!(
+        "Terminal too small\nNeed {width}x{height}, have {}x{}",
+        area.width, area.height
+    );
+    if app.show_logs && fits(area, false) {
+        text.push_str("\nPress L to hide logs");
+    }
+    let message = Paragraph::new(text)
+        .style(Style::default().fg(Color::Yellow))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    frame.render_widget(message, area);
+}
+
 pub fn draw(frame: &mut Frame, app: &App) {
+    if !fits(frame.size(), app.show_logs) {
+        draw_too_small(frame, app);
+        return;
+    }
+
     let main_constraints = if app.show_logs {
         vec![
             Constraint::Length(3),
             Constraint::Min(0),
-            Constraint::Length(3),
-            Constraint::Length(10), // Logs
+            Constraint::Length(5), // Status, device and model: up to three lines
+            Constraint::Length(LOGS_HEIGHT),
         ]
     } else {
         vec![
             Constraint::Length(3),
             Constraint::Min(0),
-            Constraint::Length(3),
+            Constraint::Length(5), // Status, device and model: up to three lines
         ]
     };
 
@@ -47,15 +98,55 @@ pub fn draw(frame: &mut Frame, app: &App) {
     let status_text = match app.state {
         AppState::Idle => "Idle",
         AppState::LoadingModel => "🔄 Loading Model...",
+        AppState::Recording if app.is_armed() => "👂 Armed - waiting for speech",
+        AppState::Recording if app.show_go_signal() => "● Recording now",
         AppState::Recording => "🎤 Recording",
         AppState::Processing => "🤖 Processing...",
         AppState::Transcribing => "🧠 Transcribing...",
         AppState::Finished => "✅ Finished",
         AppState::ModelSelection => "📋 Select Model",
         AppState::ShowingShortcuts => "❓ Shortcuts",
+        AppState::MicTest => "🎚️ Mic Test",
+        AppState::RefinementSelection => "🤖 Compare Refinements",
+        AppState::Settings => "⚙️ Settings",
+    };
+    let status_text = if app.state == AppState::Transcribing && app.config.ui.spinner {
+        let elapsed = app.transcribing_elapsed();
+        let frame = SPINNER[(elapsed.as_millis() / 100) as usize % SPINNER.len()];
+        match app.transcription_time_left(elapsed) {
+            Some(left) => format!(
+                "🧠 Transcribing {frame} {:.1}s (~{}s left)",
+                elapsed.as_secs_f32(),
+                left.as_secs_f32().ceil()
+            ),
+            None => format!("🧠 Transcribing {frame} {:.1}s", elapsed.as_secs_f32()),
+        }
+    } else {
+        status_text.to_string()
+    };
+    let status_text = match app.countdown_left() {
+        Some(secs) if app.state == AppState::Idle => format!("⏳ Recording in {secs}s..."),
+        _ => status_text,
+    };
+    let status_text = if app.pending_transcriptions > 0 && app.state != AppState::Transcribing {
+        format!(
+            "{status_text} ({} transcribing)",
+            app.pending_transcriptions
+        )
+    } else {
+        status_text
+    };
+    let status_text = match app.saved_recording_notice() {
+        Some(path) => format!("{status_text} · 💾 {}", path.display()),
+        None => status_text,
+    };
+    let status_style = if app.show_go_signal() || app.countdown_until.is_some() {
+        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::Yellow)
     };
     let status = Paragraph::new(status_text)
-        .style(Style::default().fg(Color::Yellow))
+        .style(status_style)
         .block(Block::default().title("Status").borders(Borders::ALL));
     frame.render_widget(status, top_layout[0]);
 
@@ -80,27 +171,66 @@ pub fn draw(frame: &mut Frame, app: &App) {
                     if model == app.get_current_model() {
                         style = style.add_modifier(Modifier::BOLD);
                     }
-                    ListItem::new(format!("  {model}")).style(style)
+                    let label = match model_size(model) {
+                        Some(bytes) => format!("  {model} (~{})", format_size(bytes)),
+                        None => format!("  {model}"),
+                    };
+                    ListItem::new(label).style(style)
                 })
                 .collect();
 
+            let title = if app.retry_pending {
+                "Re-transcribe With Model (↑/↓ to navigate, Enter to select, Esc to cancel)"
+            } else {
+                "Select Model (↑/↓ to navigate, Enter to select, Esc to cancel)"
+            };
             let model_list = List::new(model_items)
-                .block(
-                    Block::default()
-                        .title("Select Model (↑/↓ to navigate, Enter to select, Esc to cancel)")
-                        .borders(Borders::ALL),
-                )
+                .block(Block::default().title(title).borders(Borders::ALL))
                 .style(Style::default().fg(Color::White));
-            frame.render_widget(model_list, main_layout[middle_area_index]);
+            match &app.model_warning {
+                Some(warning) => {
+                    let layout = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Min(3), Constraint::Length(4)])
+                        .split(main_layout[middle_area_index]);
+                    frame.render_widget(model_list, layout[0]);
+                    let warning = Paragraph::new(format!("⚠️ {warning}"))
+                        .style(Style::default().fg(Color::Yellow))
+                        .wrap(Wrap { trim: true })
+                        .block(
+                            Block::default()
+                                .title("Enter to continue, Esc to go back")
+                                .borders(Borders::ALL),
+                        );
+                    frame.render_widget(warning, layout[1]);
+                }
+                None => frame.render_widget(model_list, main_layout[middle_area_index]),
+            }
         }
         AppState::ShowingShortcuts => {
             let shortcuts_text = vec![
                 "Keyboard Shortcuts:",
                 "",
-                "Space         - Start/Stop recording",
-                "Q / Escape    - Quit application",
+                "Space         - Start/Stop recording (hold to record with ui.record_mode = hold)",
+                "Q / Escape    - Quit (waits for pending transcriptions, again to cancel)",
                 "M             - Change model (when idle)",
+                "Shift+R       - Re-transcribe last recording with another model",
+                "Shift+M       - Reload the current model",
+                "Shift+D       - Delete and re-download the current model, then reload",
+                "Shift+L       - Switch transcription language",
+                "T             - Mic test / calibrate silence threshold (when idle)",
                 "L             - Toggle logs",
+                "+ / -         - Raise/lower input gain",
+                "R             - Reload config file",
+                "C             - Copy transcription to clipboard",
+                "P             - Compare LLM refinements of the transcription",
+                "F             - Refine the clipboard text with the LLM, no recording",
+                "U             - Undo last copy (restore previous clipboard)",
+                "N             - New session for clipboard.accumulate",
+                "X             - Export the last recording's raw samples (.npy)",
+                "Y             - Copy the log messages to the clipboard (for bug reports)",
+                "Enter         - Dismiss a no-speech or error message",
+                "S             - Edit settings",
                 "?             - Show/hide this help",
                 "",
                 "Model Selection:",
@@ -111,6 +241,27 @@ pub fn draw(frame: &mut Frame, app: &App) {
                 "Recording:",
                 "Space         - Stop recording",
                 "",
+                "Transcribing:",
+                "Escape        - Cancel transcription",
+                "",
+                "Loading Model:",
+                "Escape        - Cancel and keep the previous model",
+                "",
+                "Compare Refinements:",
+                "↑/↓           - Navigate profiles",
+                "Enter         - Use and copy the selected refinement",
+                "Escape        - Keep the original transcription",
+                "",
+                "Settings:",
+                "↑/↓           - Navigate settings",
+                "Enter         - Edit, then Enter again to save (empty for the default)",
+                "Escape        - Stop editing / close settings",
+                "",
+                "Mic Test:",
+                "A             - Apply suggested silence threshold",
+                "+ / -         - Raise/lower input gain while watching the level",
+                "T / Escape    - Leave mic test",
+                "",
                 "Press Escape to close this help.",
             ]
             .join("\n");
@@ -125,55 +276,223 @@ pub fn draw(frame: &mut Frame, app: &App) {
                 .style(Style::default().fg(Color::Cyan));
             frame.render_widget(shortcuts, main_layout[middle_area_index]);
         }
-        _ => {
-            if app.transcribed_text.is_some() {
-                let text = app.transcribed_text.as_deref().unwrap_or("");
-                let paragraph = Paragraph::new(text)
-                    .wrap(ratatui::widgets::Wrap { trim: true })
+        AppState::RefinementSelection => {
+            let layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(app.refinements.len() as u16 + 2),
+                    Constraint::Min(0),
+                ])
+                .split(main_layout[middle_area_index]);
+
+            let items: Vec<ListItem> = app
+                .refinements
+                .iter()
+                .enumerate()
+                .map(|(i, (profile, result))| {
+                    let mut style = match result {
+                        Ok(_) => Style::default(),
+                        Err(_) => Style::default().fg(Color::Red),
+                    };
+                    if i == app.selected_refinement {
+                        style = style.bg(Color::Blue).fg(Color::White);
+                    }
+                    ListItem::new(format!("  {profile}")).style(style)
+                })
+                .collect();
+            let list = List::new(items).block(
+                Block::default()
+                    .title("Compare Refinements (↑/↓ to navigate, Enter to copy, Esc to cancel)")
+                    .borders(Borders::ALL),
+            );
+            frame.render_widget(list, layout[0]);
+
+            let preview = match app.refinements.get(app.selected_refinement) {
+                Some((_, Ok(text))) => Paragraph::new(text.as_str()),
+                Some((_, Err(e))) => Paragraph::new(format!("Refinement failed: {e}"))
+                    .style(Style::default().fg(Color::Red)),
+                None => Paragraph::new(""),
+            };
+            frame.render_widget(
+                preview
+                    .wrap(Wrap { trim: true })
+                    .block(Block::default().title("Preview").borders(Borders::ALL)),
+                layout[1],
+            );
+        }
+        AppState::Settings => {
+            let width = SETTINGS.iter().map(|key| key.len()).max().unwrap_or(0);
+            let items: Vec<ListItem> = SETTINGS
+                .iter()
+                .enumerate()
+                .map(|(i, key)| {
+                    let selected = i == app.selected_setting;
+                    let value = match &app.setting_input {
+                        Some(input) if selected => format!("{input}▏"),
+                        _ => app
+                            .config
+                            .get_nested(key)
+                            .unwrap_or_else(|| "(unset)".to_string()),
+                    };
+                    let style = if selected {
+                        Style::default().bg(Color::Blue).fg(Color::White)
+                    } else {
+                        Style::default()
+                    };
+                    ListItem::new(format!("  {key:<width$}  {value}")).style(style)
+                })
+                .collect();
+            let title = if app.setting_input.is_some() {
+                "Settings (Enter to save, Esc to cancel, empty for the default)".to_string()
+            } else {
+                "Settings (↑/↓ to navigate, Enter to edit, Esc to close)".to_string()
+            };
+            // Offer the valid choices for the device instead of leaving it to guesswork
+            let title = match app.selected_setting_key() {
+                "audio.sample_rate" | "audio.channels"
+                    if !app.supported_audio_configs.is_empty() =>
+                {
+                    let supported: Vec<String> = app
+                        .supported_audio_configs
+                        .iter()
+                        .map(|(rate, channels)| format!("{rate}/{channels}ch"))
+                        .collect();
+                    format!("{title} · Supported: {}", supported.join(", "))
+                }
+                _ => title,
+            };
+            let list = List::new(items)
+                .block(Block::default().title(title).borders(Borders::ALL))
+                .style(Style::default().fg(Color::White));
+            // Scrolls to keep the selection visible on short terminals
+            let mut list_state = ListState::default();
+            list_state.select(Some(app.selected_setting));
+            frame.render_stateful_widget(list, main_layout[middle_area_index], &mut list_state);
+        }
+        AppState::MicTest => {
+            let mic_test_layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(7), Constraint::Min(0)])
+                .split(main_layout[middle_area_index]);
+
+            let noise_floor = app
+                .mic_test_noise_floor()
+                .map_or("-".to_string(), |level| format!("{level:.1}"));
+            let suggestion = match app.suggested_silence_threshold() {
+                Some(threshold) => format!("{threshold:.1} (A to apply)"),
+                None => format!("stay quiet for {}s...", MIC_TEST_CALIBRATION.as_secs()),
+            };
+            let mic_test_text = format!(
+                "Live level:          {:.1}\n\
+                 Noise floor:         {noise_floor}\n\
+                 Current threshold:   {:.1}\n\
+                 Suggested threshold: {suggestion}\n\
+                 Nothing is recorded. T or Esc to leave.",
+                app.audio_level, app.config.audio.silence_threshold
+            );
+            let mic_test = Paragraph::new(mic_test_text)
+                .block(Block::default().title("Mic Test").borders(Borders::ALL))
+                .style(Style::default().fg(Color::Cyan));
+            frame.render_widget(mic_test, mic_test_layout[0]);
+            draw_waveform(frame, app, mic_test_layout[1]);
+        }
+        _ => match &app.transcription {
+            Some(result) if app.state != AppState::Recording => {
+                let job = match app.last_job_id {
+                    Some(id) if app.config.ui.queue_recordings => format!(" #{id}"),
+                    _ => String::new(),
+                };
+                let paragraph = match result {
+                    TranscriptionResult::Text(transcription) => {
+                        let mut title = format!("Transcription{job}");
+                        if !app.config.clipboard.auto_copy {
+                            title.push_str(" (C to copy)");
+                        }
+                        Paragraph::new(transcription.text.as_str())
+                            .block(Block::default().title(title).borders(Borders::ALL))
+                    }
+                    TranscriptionResult::NoSpeech => Paragraph::new(
+                        "No speech detected — try again.\n\n\
+                         Check the microphone with T (mic test) and speak a little closer.",
+                    )
+                    .style(Style::default().fg(Color::Yellow))
                     .block(
                         Block::default()
-                            .title("Transcription")
+                            .title(format!("No Speech Detected{job} (Enter to dismiss)"))
                             .borders(Borders::ALL),
-                    );
-                frame.render_widget(paragraph, main_layout[middle_area_index]);
-            } else {
-                let data: Vec<(&str, u64)> = app
-                    .audio_waveform
-                    .iter()
-                    .map(|v| {
-                        let scaled = (v.abs() * 1000.0) as u64; // Scale up more for visibility
-                        let min_height = if scaled > 0 { 1 } else { 0 }; // Ensure non-zero values show
-                        ("", scaled.max(min_height))
-                    })
-                    .collect();
-                // Add debug info to title
-                let title = if app.audio_waveform.is_empty() {
-                    "Waveform (no data)".to_string()
-                } else {
-                    format!("Waveform ({} samples)", app.audio_waveform.len())
+                    ),
+                    TranscriptionResult::Error(e) => {
+                        Paragraph::new(format!("Transcription error: {e}"))
+                            .style(Style::default().fg(Color::Red))
+                            .block(
+                                Block::default()
+                                    .title(format!("Transcription Failed{job} (Enter to dismiss)"))
+                                    .borders(Borders::ALL),
+                            )
+                    }
+                    TranscriptionResult::Cancelled => Paragraph::new("Transcription cancelled.")
+                        .block(
+                            Block::default()
+                                .title("Transcription")
+                                .borders(Borders::ALL),
+                        ),
                 };
-
-                let barchart = BarChart::default()
-                    .block(Block::default().title(title).borders(Borders::ALL))
-                    .data(&data)
-                    .bar_width(1)
-                    .style(Style::default().fg(Color::Green));
-                frame.render_widget(barchart, main_layout[middle_area_index]);
+                frame.render_widget(
+                    paragraph.wrap(ratatui::widgets::Wrap { trim: true }),
+                    main_layout[middle_area_index],
+                );
             }
-        }
+            _ => match app.live_transcript.as_deref() {
+                Some(live) => {
+                    let live_layout = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                        .split(main_layout[middle_area_index]);
+                    draw_waveform(frame, app, live_layout[0]);
+                    let live = Paragraph::new(live)
+                        .style(Style::default().fg(Color::Gray))
+                        .wrap(Wrap { trim: true })
+                        .block(
+                            Block::default()
+                                .title("Live Transcript")
+                                .borders(Borders::ALL),
+                        );
+                    frame.render_widget(live, live_layout[1]);
+                }
+                None => draw_waveform(frame, app, main_layout[middle_area_index]),
+            },
+        },
     }
 
     // Audio Level, Device, and Model
-    let level_text = format!("Level: {:.0}", app.audio_level);
+    let mut level_text = format!("Level: {:.0}", app.audio_level);
+    if app.config.audio.gain != 1.0 {
+        level_text.push_str(&format!(" (gain {:.1}x)", app.config.audio.gain));
+    }
     let level = Paragraph::new(level_text)
         .block(Block::default().title("Audio Level").borders(Borders::ALL));
     frame.render_widget(level, bottom_layout[0]);
 
-    let device = Paragraph::new(app.device_name.as_str())
-        .block(Block::default().title("Device").borders(Borders::ALL));
+    let device = if app.input_device_available && is_monitor_device(&app.device_name) {
+        Paragraph::new(format!("🔊 {} (system audio)", app.device_name))
+    } else if app.input_device_available {
+        Paragraph::new(app.device_name.as_str())
+    } else {
+        Paragraph::new("❌ No input device\nConnect a microphone to record")
+            .style(Style::default().fg(Color::Red))
+    }
+    .wrap(ratatui::widgets::Wrap { trim: true })
+    .block(Block::default().title("Device").borders(Borders::ALL));
     frame.render_widget(device, bottom_layout[1]);
 
-    let model_info = format!("{}\n{}", app.get_current_model(), app.model_status);
+    let model_name = match &app.config.whisper.language {
+        Some(language) => format!("{} ({language})", app.get_current_model()),
+        None => app.get_current_model().to_string(),
+    };
+    let mut model_info = format!("{model_name}\n{}", app.model_status);
+    if app.config.whisper.backend == "local" {
+        model_info.push_str(&format!("\nSampling: {}", app.config.whisper.sampling()));
+    }
     let model = Paragraph::new(model_info)
         .wrap(ratatui::widgets::Wrap { trim: true })
         .block(
@@ -196,3 +515,135 @@ pub fn draw(frame: &mut Frame, app: &App) {
         frame.render_widget(log_list, main_layout[3]);
     }
 }
+
+fn draw_waveform(frame: &mut Frame, app: &App, area: Rect) {
+    match app.config.ui.waveform_style {
+        WaveformStyle::Envelope => draw_envelope(frame, app, area),
+        WaveformStyle::Rms => draw_rms(frame, app, area),
+        WaveformStyle::Bars => draw_bars(frame, app, area),
+    }
+}
+
+fn draw_bars(frame: &mut Frame, app: &App, area: Rect) {
+    let data: Vec<(&str, u64)> = app
+        .audio_waveform
+        .iter()
+        .map(|v| {
+            let scaled = (v.abs() * 1000.0) as u64; // Scale up more for visibility
+            let min_height = if scaled > 0 { 1 } else { 0 }; // Ensure non-zero values show
+            ("", scaled.max(min_height))
+        })
+        .collect();
+    // Add debug info to title
+    let title = if app.audio_waveform.is_empty() {
+        "Waveform (no data)".to_string()
+    } else {
+        format!("Waveform ({} samples)", app.audio_waveform.len())
+    };
+
+    let barchart = BarChart::default()
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .data(&data)
+        .bar_width(1)
+        .style(Style::default().fg(Color::Green));
+    frame.render_widget(barchart, area);
+}
+
+/// Symmetric min/max envelope of the recent audio, one bucket per terminal column
+fn draw_envelope(frame: &mut Frame, app: &App, area: Rect) {
+    let columns = area.width.saturating_sub(2) as usize;
+    let samples: Vec<f32> = app.waveform_history.iter().copied().collect();
+    let envelope = bucket_envelope(&samples, columns);
+    let canvas = Canvas::default()
+        .block(
+            Block::default()
+                .title("Waveform (envelope)")
+                .borders(Borders::ALL),
+        )
+        .x_bounds([0.0, columns.max(1) as f64])
+        .y_bounds([-1.0, 1.0])
+        .paint(move |ctx| {
+            for (i, (min, max)) in envelope.iter().enumerate() {
+                ctx.draw(&CanvasLine {
+                    x1: i as f64,
+                    y1: *min as f64,
+                    x2: i as f64,
+                    y2: *max as f64,
+                    color: Color::Green,
+                });
+            }
+        });
+    frame.render_widget(canvas, area);
+}
+
+/// RMS loudness of the recent audio, one bar per terminal column
+fn draw_rms(frame: &mut Frame, app: &App, area: Rect) {
+    let columns = area.width.saturating_sub(2) as usize;
+    let samples: Vec<f32> = app.waveform_history.iter().copied().collect();
+    let data: Vec<(&str, u64)> = bucket_rms(&samples, columns)
+        .into_iter()
+        .map(|rms| ("", (rms * 1000.0) as u64))
+        .collect();
+    let barchart = BarChart::default()
+        .block(
+            Block::default()
+                .title("Waveform (RMS)")
+                .borders(Borders::ALL),
+        )
+        .data(&data)
+        .bar_width(1)
+        .bar_gap(0)
+        .style(Style::default().fg(Color::Green));
+    frame.render_widget(barchart, area);
+}
+
+/// Split samples into `buckets` equal slices, skipping any that would be empty
+fn buckets(samples: &[f32], buckets: usize) -> impl Iterator<Item = &[f32]> {
+    let size = samples.len().div_ceil(buckets.max(1)).max(1);
+    samples.chunks(size)
+}
+
+/// `(min, max)` of each bucket
+fn bucket_envelope(samples: &[f32], count: usize) -> Vec<(f32, f32)> {
+    buckets(samples, count)
+        .map(|bucket| {
+            bucket
+                .iter()
+                .fold((0.0f32, 0.0f32), |(min, max), &s| (min.min(s), max.max(s)))
+        })
+        .collect()
+}
+
+/// Root mean square of each bucket
+fn bucket_rms(samples: &[f32], count: usize) -> Vec<f32> {
+    buckets(samples, count)
+        .map(|bucket| (bucket.iter().map(|s| s * s).sum::<f32>() / bucket.len() as f32).sqrt())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimum_size() {
+        assert!(fits(Rect::new(0, 0, 80, 24), false));
+        assert!(!fits(Rect::new(0, 0, 80, 24), true));
+        assert!(!fits(Rect::new(0, 0, 40, 40), false));
+        assert!(fits(Rect::new(0, 0, MIN_WIDTH, MIN_HEIGHT), false));
+    }
+
+    #[test]
+    fn test_bucketing() {
+        let samples = [0.5, -0.5, 0.25, -1.0, 0.0, 0.0];
+        assert_eq!(
+            bucket_envelope(&samples, 3),
+            vec![(-0.5, 0.5), (-1.0, 0.25), (0.0, 0.0)]
+        );
+        assert_eq!(
+            bucket_rms(&samples, 3),
+            vec![0.5, (1.0625f32 / 2.0).sqrt(), 0.0]
+        );
+        assert!(bucket_rms(&[], 10).is_empty());
+    }
+}
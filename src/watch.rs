@@ -0,0 +1,145 @@
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::stt::wav_utils::AUDIO_EXTENSIONS;
+use crate::stt::SttProcessor;
+
+/// A file is transcribed once it has gone this long without changes, so a
+/// recorder still writing it isn't read half-way
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// Transcribe audio files dropped into `dir`, writing a `.txt` next to each.
+/// Files are handled one at a time with a single loaded model, until interrupted.
+pub async fn run(config: &Config, dir: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(anyhow::anyhow!("Not a directory: {dir:?}"));
+    }
+
+    let mut processor = SttProcessor::new(config)?;
+    processor.prepare().await?;
+
+    // Catch up on anything dropped in while we weren't watching
+    let mut existing: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {dir:?}"))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| needs_transcript(path))
+        .collect();
+    existing.sort();
+    for path in existing {
+        transcribe_file(&processor, &path).await;
+    }
+
+    // Events arrive on notify's thread, a tokio channel lets us wait for them without
+    // blocking the runtime
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        event_tx.send(event).ok();
+    })?;
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch directory: {dir:?}"))?;
+    eprintln!("👀 Watching {} for new recordings", dir.display());
+
+    // Last change seen for each file that's waiting to settle
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    loop {
+        match tokio::time::timeout(Duration::from_millis(250), event_rx.recv()).await {
+            Ok(Some(Ok(event))) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        if is_audio_file(&path) {
+                            debug!("Change in watched file: {:?}", path);
+                            pending.insert(path, Instant::now());
+                        }
+                    }
+                }
+            }
+            Ok(Some(Err(e))) => warn!("Watch error: {}", e),
+            Ok(None) => return Err(anyhow::anyhow!("Directory watcher stopped")),
+            Err(_) => {} // Nothing new, check whether pending files have settled
+        }
+
+        let mut settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        settled.sort();
+        for path in settled {
+            pending.remove(&path);
+            if needs_transcript(&path) {
+                transcribe_file(&processor, &path).await;
+            }
+        }
+    }
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            AUDIO_EXTENSIONS
+                .iter()
+                .any(|audio| ext.eq_ignore_ascii_case(audio))
+        })
+}
+
+/// Where the transcript of an audio file is written
+fn transcript_path(path: &Path) -> PathBuf {
+    path.with_extension("txt")
+}
+
+/// An audio file that exists and hasn't been transcribed yet
+fn needs_transcript(path: &Path) -> bool {
+    is_audio_file(path) && path.is_file() && !transcript_path(path).exists()
+}
+
+/// Transcribe one file, failures are reported and the file skipped
+async fn transcribe_file(processor: &SttProcessor, path: &Path) {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    eprintln!("🔄 Transcribing {name}");
+    let text = match processor.transcribe(path, None).await {
+        Ok(transcription) => transcription
+            .map(|transcription| transcription.text)
+            .unwrap_or_default(),
+        Err(e) => {
+            eprintln!("❌ {name}: {e:#}");
+            return;
+        }
+    };
+    let transcript = transcript_path(path);
+    match std::fs::write(&transcript, format!("{text}\n")) {
+        Ok(()) => {
+            info!("Wrote transcript: {:?}", transcript);
+            eprintln!("✅ {name} → {}", transcript.display());
+        }
+        Err(e) => eprintln!("❌ Failed to write {}: {e}", transcript.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_transcript() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("memo.WAV");
+        let notes = dir.path().join("notes.md");
+        std::fs::write(&wav, b"").unwrap();
+        std::fs::write(&notes, b"").unwrap();
+
+        assert!(needs_transcript(&wav));
+        assert!(!needs_transcript(&notes));
+        assert!(!needs_transcript(&dir.path().join("missing.mp3")));
+
+        std::fs::write(transcript_path(&wav), b"hello\n").unwrap();
+        assert!(!needs_transcript(&wav));
+    }
+}